@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 
 use quote::quote;
-use syn::{parse_macro_input, ItemStruct, LitStr, Path};
+use syn::{parse_macro_input, Expr, ItemStruct, LitStr, Path};
 
 // 定义一个属性宏
 #[proc_macro_attribute]
@@ -9,6 +9,12 @@ pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
     let mut name: Option<LitStr> = None;
     let mut events: Vec<Path> = vec![];
     let mut regex: Option<LitStr> = None;
+    // 越小越先执行，默认为0
+    let mut priority: Option<Expr> = None;
+    // 只在没有其他服务匹配时执行
+    let mut fallback = false;
+    // 需要的角色，默认为member
+    let mut permission: Option<LitStr> = None;
 
     let tea_parser = syn::meta::parser(|meta| {
         if meta.path.is_ident("name") {
@@ -22,6 +28,15 @@ pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
         } else if meta.path.is_ident("pattern") {
             regex = meta.value()?.parse()?;
             Ok(())
+        } else if meta.path.is_ident("priority") {
+            priority = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("fallback") {
+            fallback = true;
+            Ok(())
+        } else if meta.path.is_ident("permission") {
+            permission = meta.value()?.parse()?;
+            Ok(())
         } else {
             Err(meta.error("unsupported tea property"))
         }
@@ -33,6 +48,29 @@ pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
 
     let name = name.unwrap().value();
     let events_tokens = quote! { vec![#(#events),*] };
+    let priority = priority.map(|p| quote! { #p }).unwrap_or(quote! { 0 });
+    let role = match permission.as_ref().map(|p| p.value()).as_deref() {
+        None | Some("member") => quote! { Member },
+        Some("group_admin") => quote! { GroupAdmin },
+        Some("group_owner") => quote! { GroupOwner },
+        Some("admin") => quote! { Admin },
+        Some("owner") => quote! { Owner },
+        Some(_) => {
+            return syn::Error::new(
+                permission.unwrap().span(),
+                "permission must be one of member, group_admin, group_owner, admin, owner",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    let options_tokens = quote! {
+        crate::service::register::ServiceOptions {
+            priority: #priority,
+            fallback: #fallback,
+            permission: crate::model::permission::Role::#role,
+        }
+    };
     if regex.is_some() {
         let regex = regex.unwrap().value();
         let expanded = quote! {
@@ -58,7 +96,7 @@ pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
                 let service = std::sync::Arc::new(#struct_name::default());
                 let events = #events_tokens;
                 let name = String::from(#name);
-                crate::service::register::register_service(service, events, name);
+                crate::service::register::register_service(service, events, name, #options_tokens);
             }
         };
         return TokenStream::from(expanded);
@@ -74,7 +112,7 @@ pub fn service(args: TokenStream, input: TokenStream) -> TokenStream {
             let service = std::sync::Arc::new(#struct_name::default());
             let events = #events_tokens;
             let name = String::from(#name);
-            crate::service::register::register_service(service, events, name);
+            crate::service::register::register_service(service, events, name, #options_tokens);
         }
     };
 
@@ -2,6 +2,10 @@ use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use prost::Message;
+use prost_types::FileDescriptorSet;
+
 fn collect_proto_files<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
     let mut proto_files = Vec::new();
 
@@ -19,6 +23,62 @@ fn collect_proto_files<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
     proto_files
 }
 
+/// .kritor.core.GetVersionRequest -> GetVersionRequest，common包的类型在common模块下
+///
+/// 与prost一致转换为大驼峰，如GetCSRFTokenRequest -> GetCsrfTokenRequest
+fn rust_type(proto_type: &str) -> String {
+    let name = proto_type
+        .rsplit('.')
+        .next()
+        .unwrap_or(proto_type)
+        .to_upper_camel_case();
+    if proto_type.starts_with(".kritor.common.") {
+        format!("common::{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// 根据service定义为Bot生成调用方法，每个rpc对应一个 `服务名_方法名` 的方法
+fn generate_bot_api(descriptor_path: &Path, out_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let descriptor = FileDescriptorSet::decode(fs::read(descriptor_path)?.as_slice())?;
+    // 这两个服务由avocado实现，不需要生成调用
+    let skip_services = ["EventService", "ReverseService"];
+    let mut f = fs::File::create(out_path)?;
+    writeln!(f, "impl Bot {{")?;
+    for file in &descriptor.file {
+        for service in &file.service {
+            let service_name = service.name();
+            if skip_services.contains(&service_name) {
+                continue;
+            }
+            let prefix = service_name
+                .strip_suffix("Service")
+                .unwrap_or(service_name)
+                .to_snake_case();
+            for method in &service.method {
+                if method.client_streaming() || method.server_streaming() {
+                    continue;
+                }
+                let cmd = format!("{}.{}", service_name, method.name());
+                writeln!(f, "    /// `{}`", cmd)?;
+                writeln!(
+                    f,
+                    "    pub async fn {}_{}(&self, request: {}) -> Result<{}> {{",
+                    prefix,
+                    method.name().to_snake_case(),
+                    rust_type(method.input_type()),
+                    rust_type(method.output_type())
+                )?;
+                writeln!(f, "        self.call(\"{}\", request).await", cmd)?;
+                writeln!(f, "    }}")?;
+            }
+        }
+    }
+    writeln!(f, "}}")?;
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=kritor/protos");
     println!("cargo:rerun-if-changed=src/service/plugins");
@@ -177,11 +237,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
     });
 
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    let descriptor_path = out_dir.join("kritor_descriptor.bin");
     config
+        .file_descriptor_set_path(&descriptor_path)
         // .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .build_server(true)
         // .proto_path("kritor/protos")
         .compile(&proto_files_str, &["kritor/protos"])?;
+    generate_bot_api(&descriptor_path, &out_dir.join("kritor_api.rs"))?;
 
     // 加载插件
     let out_dirs = vec!["src/service/plugins", "src/service/plugins/default"];
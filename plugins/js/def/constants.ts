@@ -18,7 +18,19 @@ export interface Event {
     sender?: Sender;
     reply?: (msg: [MessageElement] | MessageElement | string, reply?: boolean) => Promise<void>;
     is_master: boolean,
+    // 触发者的角色：member、group_admin、group_owner、admin、owner
+    role: "member" | "group_admin" | "group_owner" | "admin" | "owner",
     contact?: Contact,
+    // 频道消息时为频道和子频道id
+    guild_id?: string,
+    channel_id?: string,
+    // 请求事件时可用
+    request_id?: string,
+    request_type?: 'FRIEND_APPLY' | 'GROUP_APPLY' | 'INVITED_GROUP',
+    approve?: () => Promise<void>,
+    reject?: (reason?: string) => Promise<void>,
+    // 处理了事件时置为true，reply、approve、reject会自动设置，为false时fallback插件仍会执行
+    handled: boolean,
     bot: AvocadoBot
 }
 export interface GroupInfo {
@@ -278,4 +290,5 @@ export interface AvocadoBot {
     uid: string,
     nickname?: string,
     sendMessage: (msg: [MessageElement] | MessageElement | string, contact: Contact, reply?: boolean) => Promise<void>,
+    sendChannelMessage: (guildId: string, channelId: string, msg: [MessageElement] | MessageElement | string) => Promise<void>,
 }
\ No newline at end of file
@@ -0,0 +1,325 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{info, warn};
+use prost::Message;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::adapter::onebot::message::segments_to_elements;
+use crate::adapter::{attach_bot, RequestHandler};
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::common::element::Data;
+use crate::kritor::server::kritor_proto::common::{
+    self, image_element, Contact, Element, PushMessageBody, Scene, Sender,
+};
+use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::kritor::server::kritor_proto::*;
+use crate::model::config::ConsoleConfig;
+use crate::model::error::Result;
+use crate::service::service::Elements;
+use crate::err;
+
+/// 控制台中当前发言的身份
+#[derive(Debug, Clone)]
+struct Identity {
+    scene: Scene,
+    sender_uin: u64,
+    sender_nick: String,
+}
+
+/// 控制台适配器，消息来自标准输入，发出的消息打印到终端
+pub struct ConsoleHandler {
+    config: ConsoleConfig,
+    identity: Mutex<Identity>,
+    message_id: AtomicU64,
+}
+
+/// 根据文件头判断图片格式，无法识别时不带扩展名
+pub fn image_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else if bytes.starts_with(b"BM") {
+        Some("bmp")
+    } else {
+        None
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl ConsoleHandler {
+    pub fn new(config: ConsoleConfig) -> Self {
+        let identity = Identity {
+            scene: Scene::Group,
+            sender_uin: config.sender_uin(),
+            sender_nick: config.sender_nick(),
+        };
+        Self {
+            config,
+            identity: Mutex::new(identity),
+            message_id: AtomicU64::new(1),
+        }
+    }
+
+    fn next_message_id(&self) -> u64 {
+        self.message_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn identity(&self) -> Identity {
+        self.identity.lock().unwrap().clone()
+    }
+
+    fn member(&self, uin: u64, nick: String) -> GroupMemberInfo {
+        GroupMemberInfo {
+            uid: uin.to_string(),
+            uin,
+            nick: nick.clone(),
+            card: nick,
+            ..Default::default()
+        }
+    }
+
+    fn group_info(&self) -> GroupInfo {
+        GroupInfo {
+            group_id: self.config.group_id(),
+            group_name: self.config.group_name(),
+            member_count: 2,
+            ..Default::default()
+        }
+    }
+
+    /// 处理控制台命令，返回true表示该行已被当作命令处理
+    fn command(&self, line: &str) -> bool {
+        let Some(command) = line.strip_prefix(':') else {
+            return false;
+        };
+        let mut args = command.split_whitespace();
+        let mut identity = self.identity.lock().unwrap();
+        match args.next() {
+            Some("group") => identity.scene = Scene::Group,
+            Some("private") => identity.scene = Scene::Friend,
+            Some("as") => {
+                match args.next().and_then(|uin| uin.parse().ok()) {
+                    Some(uin) => identity.sender_uin = uin,
+                    None => {
+                        println!("usage: :as <uin> [nick]");
+                        return true;
+                    }
+                }
+                identity.sender_nick = args.next().unwrap_or("tester").to_string();
+            }
+            _ => {
+                println!(":group 切换到群聊  :private 切换到私聊  :as <uin> [nick] 切换发送者");
+                return true;
+            }
+        }
+        println!(
+            "[Console] 当前身份: {}({}) @ {:?}",
+            identity.sender_nick, identity.sender_uin, identity.scene
+        );
+        true
+    }
+
+    /// 将一行输入转换为消息事件，支持CQ码
+    fn to_event(&self, line: &str) -> Event {
+        let identity = self.identity();
+        let peer = match identity.scene {
+            Scene::Group => self.config.group_id().to_string(),
+            _ => identity.sender_uin.to_string(),
+        };
+        let message_id = self.next_message_id();
+        Event::Message(PushMessageBody {
+            time: now(),
+            message_id: message_id.to_string(),
+            message_seq: message_id,
+            contact: Some(Contact {
+                scene: identity.scene.into(),
+                peer,
+                sub_peer: None,
+            }),
+            sender: Some(Sender {
+                uid: identity.sender_uin.to_string(),
+                uin: Some(identity.sender_uin),
+                nick: Some(identity.sender_nick),
+            }),
+            elements: segments_to_elements(&serde_json::Value::from(line)),
+            ..Default::default()
+        })
+    }
+
+    /// 图片保存到本地，并替换为本地路径以便打印
+    fn save_images(&self, elements: Vec<Element>) -> Vec<Element> {
+        let dir = PathBuf::from(self.config.image_dir());
+        elements
+            .into_iter()
+            .map(|mut element| {
+                if let Some(Data::Image(image)) = element.data.as_mut() {
+                    let bytes = match image.data.as_ref() {
+                        Some(image_element::Data::File(bytes)) => Some(bytes.clone()),
+                        Some(image_element::Data::FileBase64(data)) => {
+                            // 兼容带base64://前缀的写法
+                            let data = data.strip_prefix("base64://").unwrap_or(data);
+                            STANDARD
+                                .decode(data)
+                                .map_err(|e| warn!("Failed to decode image: {}", e))
+                                .ok()
+                        }
+                        _ => None,
+                    };
+                    if let Some(bytes) = bytes {
+                        let mut name = format!("{}-{}", now(), self.next_message_id());
+                        if let Some(ext) = image_extension(&bytes) {
+                            name = format!("{}.{}", name, ext);
+                        }
+                        let path = dir.join(name);
+                        let saved = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, &bytes));
+                        match saved {
+                            Ok(_) => {
+                                image.data = Some(image_element::Data::FileUrl(format!(
+                                    "file://{}",
+                                    path.display()
+                                )))
+                            }
+                            Err(e) => warn!("Failed to save image: {}", e),
+                        }
+                    }
+                }
+                element
+            })
+            .collect()
+    }
+
+    fn print_message(&self, contact: Option<Contact>, elements: Vec<Element>) {
+        let contact = contact.unwrap_or_default();
+        let target = match Scene::try_from(contact.scene) {
+            Ok(Scene::Group) => format!("Group: {}({})", self.config.group_name(), contact.peer),
+            _ => format!("Private: {}", contact.peer),
+        };
+        println!("[Console] -> [{}] {}", target, self.save_images(elements).get_raw_msg());
+    }
+}
+
+#[async_trait]
+impl RequestHandler for ConsoleHandler {
+    async fn handle(&self, request: &common::Request) -> Result<Vec<u8>> {
+        let Some((service, method)) = request.cmd.split_once('.') else {
+            return err!("invalid cmd: {}", request.cmd);
+        };
+        let buf = request.buf.as_slice();
+        let response = match (service, method) {
+            ("CoreService", "GetCurrentAccount") => GetCurrentAccountResponse {
+                account_uid: self.config.self_uin().to_string(),
+                account_uin: self.config.self_uin(),
+                account_name: self.config.nickname(),
+            }
+            .encode_to_vec(),
+            ("CoreService", "GetVersion") => GetVersionResponse {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                app_name: "console".to_string(),
+            }
+            .encode_to_vec(),
+            ("MessageService", "SendMessage") => {
+                let request = SendMessageRequest::decode(buf)?;
+                self.print_message(request.contact, request.elements);
+                SendMessageResponse {
+                    message_id: self.next_message_id().to_string(),
+                    ..Default::default()
+                }
+                .encode_to_vec()
+            }
+            ("FriendService", "GetFriendList") => {
+                let identity = self.identity();
+                GetFriendListResponse {
+                    friends_info: vec![FriendInfo {
+                        uid: identity.sender_uin.to_string(),
+                        uin: identity.sender_uin,
+                        nick: identity.sender_nick,
+                        ..Default::default()
+                    }],
+                }
+                .encode_to_vec()
+            }
+            ("GroupService", "GetGroupList") => GetGroupListResponse {
+                groups_info: vec![self.group_info()],
+            }
+            .encode_to_vec(),
+            ("GroupService", "GetGroupInfo") => GetGroupInfoResponse {
+                group_info: Some(self.group_info()),
+            }
+            .encode_to_vec(),
+            ("GroupService", "GetGroupMemberList") => {
+                let identity = self.identity();
+                GetGroupMemberListResponse {
+                    group_members_info: vec![
+                        self.member(self.config.self_uin(), self.config.nickname()),
+                        self.member(identity.sender_uin, identity.sender_nick),
+                    ],
+                }
+                .encode_to_vec()
+            }
+            ("GroupService", "GetGroupMemberInfo") => {
+                let identity = self.identity();
+                GetGroupMemberInfoResponse {
+                    group_member_info: Some(self.member(identity.sender_uin, identity.sender_nick)),
+                }
+                .encode_to_vec()
+            }
+            _ => {
+                // 其余接口只打印调用，空响应会被解码为默认值
+                println!("[Console] -> {}", request.cmd);
+                vec![]
+            }
+        };
+        Ok(response)
+    }
+}
+
+/// 启动控制台Bot，从标准输入读取消息
+pub async fn run_console(config: ConsoleConfig) {
+    let handler = Arc::new(ConsoleHandler::new(config.clone()));
+    let uin = config.self_uin();
+    let (bot, connection, forward) = attach_bot(
+        uin,
+        uin.to_string(),
+        Some("console".to_string()),
+        handler.clone(),
+    )
+    .await;
+    info!("Console bot started, type messages to send, :help for commands");
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Failed to read stdin: {}", e);
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() || handler.command(line) {
+            continue;
+        }
+        Bot::push_event(&bot, handler.to_event(line)).await;
+    }
+    info!("Console closed");
+    forward.abort();
+    Bot::disconnect(bot, connection).await;
+}
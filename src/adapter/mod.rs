@@ -0,0 +1,92 @@
+pub mod console;
+pub mod onebot;
+pub mod replay;
+pub mod satori;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::bot::bot::Bot;
+use crate::kritor::server::get_or_create_bot;
+use crate::kritor::server::kritor_proto::common;
+use crate::model::error::Result;
+
+/// 协议适配器，把Bot发出的kritor请求翻译为其他协议的调用
+///
+/// 请求与返回值都是编码后的kritor protobuf，因此Bot和插件不需要关心底层协议
+#[async_trait]
+pub trait RequestHandler: Send + Sync + 'static {
+    async fn handle(&self, request: &common::Request) -> Result<Vec<u8>>;
+
+    /// 为false时Bot不录制事件与请求，回放时使用
+    fn recordable(&self) -> bool {
+        true
+    }
+}
+
+fn to_response(request: &common::Request, result: Result<Vec<u8>>) -> common::Response {
+    match result {
+        Ok(buf) => common::Response {
+            cmd: request.cmd.clone(),
+            seq: request.seq,
+            code: 0,
+            msg: None,
+            buf,
+        },
+        Err(e) => {
+            warn!("request {} failed: {}", request.cmd, e);
+            // 非0即失败
+            common::Response {
+                cmd: request.cmd.clone(),
+                seq: request.seq,
+                code: 1,
+                msg: Some(e.error()),
+                buf: vec![],
+            }
+        }
+    }
+}
+
+/// 创建（或重连）由适配器驱动的Bot，返回Bot、连接编号和转发请求的任务
+///
+/// 连接断开时应当abort返回的任务并以连接编号调用 `Bot::disconnect`
+pub async fn attach_bot(
+    uin: u64,
+    uid: String,
+    version: Option<String>,
+    handler: Arc<dyn RequestHandler>,
+) -> (Arc<RwLock<Bot>>, u64, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::channel(4096);
+    let (bot, connection) = get_or_create_bot(uin, uid, tx, version).await;
+    if !handler.recordable() {
+        bot.read().await.disable_recording();
+    }
+    let bot_clone = bot.clone();
+    let forward = tokio::spawn(async move {
+        while let Some(request) = rx.recv().await {
+            let request = match request {
+                Ok(request) => request,
+                Err(status) => {
+                    debug!("skip request: {}", status);
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+            let bot = bot_clone.clone();
+            tokio::spawn(async move {
+                let result = handler.handle(&request).await;
+                let response = to_response(&request, result);
+                bot.read().await.handle_response(response);
+            });
+        }
+    });
+    let bot_clone = bot.clone();
+    tokio::spawn(async move {
+        Bot::init(bot_clone).await;
+    });
+    (bot, connection, forward)
+}
@@ -0,0 +1,365 @@
+use log::debug;
+use prost::Message;
+use serde_json::{json, Value};
+
+use crate::adapter::onebot::message::{elements_to_segments, value_to_string, value_to_u64};
+use crate::adapter::onebot::OneBotClient;
+use crate::kritor::server::kritor_proto::common::{self, Scene};
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+use crate::{client_err, err};
+
+/// 取出目标的uin，OneBot下uid就是uin的字符串形式
+macro_rules! target_uin {
+    ($target:expr, $module:ident) => {
+        match $target {
+            Some($module::Target::TargetUin(uin)) => uin,
+            Some($module::Target::TargetUid(uid)) => uid.parse().unwrap_or_default(),
+            None => return client_err!("target is required"),
+        }
+    };
+}
+
+fn to_friend_info(value: &Value) -> FriendInfo {
+    let user_id = value_to_u64(&value["user_id"]);
+    FriendInfo {
+        uid: user_id.to_string(),
+        uin: user_id,
+        nick: value_to_string(&value["nickname"]),
+        remark: value_to_string(&value["remark"]),
+        ..Default::default()
+    }
+}
+
+fn to_group_info(value: &Value) -> GroupInfo {
+    GroupInfo {
+        group_id: value_to_u64(&value["group_id"]),
+        group_name: value_to_string(&value["group_name"]),
+        member_count: value_to_u64(&value["member_count"]) as _,
+        max_member_count: value_to_u64(&value["max_member_count"]) as _,
+        ..Default::default()
+    }
+}
+
+/// OneBot的群信息不含群主与管理员，从成员列表的role中取出，失败时保持为空
+async fn fill_roles(client: &OneBotClient, info: &mut GroupInfo) {
+    let members = match client
+        .call("get_group_member_list", json!({ "group_id": info.group_id }))
+        .await
+    {
+        Ok(members) => members,
+        Err(e) => {
+            debug!("Failed to get roles of group {}: {}", info.group_id, e);
+            return;
+        }
+    };
+    for member in members.as_array().into_iter().flatten() {
+        let user_id = value_to_u64(&member["user_id"]);
+        match member["role"].as_str() {
+            Some("owner") => info.owner = user_id,
+            Some("admin") => info.admins.push(user_id),
+            _ => {}
+        }
+    }
+}
+
+fn to_group_member_info(value: &Value) -> GroupMemberInfo {
+    let user_id = value_to_u64(&value["user_id"]);
+    GroupMemberInfo {
+        uid: user_id.to_string(),
+        uin: user_id,
+        nick: value_to_string(&value["nickname"]),
+        card: value_to_string(&value["card"]),
+        unique_title: value_to_string(&value["title"]),
+        level: value_to_u64(&value["level"]) as _,
+        join_time: value_to_u64(&value["join_time"]),
+        last_active_time: value_to_u64(&value["last_sent_time"]),
+        ..Default::default()
+    }
+}
+
+fn message_target(contact: Option<common::Contact>) -> Result<(&'static str, Value)> {
+    let Some(contact) = contact else {
+        return client_err!("contact is required");
+    };
+    let peer: u64 = match contact.peer.parse() {
+        Ok(peer) => peer,
+        Err(_) => return client_err!("invalid peer: {}", contact.peer),
+    };
+    match Scene::try_from(contact.scene) {
+        Ok(Scene::Group) => Ok(("send_group_msg", json!({ "group_id": peer }))),
+        Ok(Scene::Friend) => Ok(("send_private_msg", json!({ "user_id": peer }))),
+        _ => client_err!("unsupported scene: {}", contact.scene),
+    }
+}
+
+/// OneBot的message_id是数字
+fn message_id(message_id: &str) -> Value {
+    message_id
+        .parse::<i64>()
+        .map(Value::from)
+        .unwrap_or_else(|_| Value::from(message_id))
+}
+
+/// 将kritor请求翻译为OneBot action，返回编码后的kritor响应
+pub async fn handle(client: &OneBotClient, request: &common::Request) -> Result<Vec<u8>> {
+    let Some((service, method)) = request.cmd.split_once('.') else {
+        return err!("invalid cmd: {}", request.cmd);
+    };
+    let buf = request.buf.as_slice();
+    let response = match (service, method) {
+        ("CoreService", "GetCurrentAccount") => {
+            let data = client.call("get_login_info", json!({})).await?;
+            GetCurrentAccountResponse {
+                account_uid: value_to_string(&data["user_id"]),
+                account_uin: value_to_u64(&data["user_id"]),
+                account_name: value_to_string(&data["nickname"]),
+            }
+            .encode_to_vec()
+        }
+        ("CoreService", "GetVersion") => {
+            let data = client.call("get_version_info", json!({})).await?;
+            GetVersionResponse {
+                version: value_to_string(&data["app_version"]),
+                app_name: value_to_string(&data["app_name"]),
+            }
+            .encode_to_vec()
+        }
+        ("MessageService", "SendMessage") => {
+            let request = SendMessageRequest::decode(buf)?;
+            let (action, mut params) = message_target(request.contact)?;
+            params["message"] = elements_to_segments(&request.elements);
+            let data = client.call(action, params).await?;
+            SendMessageResponse {
+                message_id: value_to_string(&data["message_id"]),
+                ..Default::default()
+            }
+            .encode_to_vec()
+        }
+        ("MessageService", "RecallMessage") => {
+            let request = RecallMessageRequest::decode(buf)?;
+            client
+                .call(
+                    "delete_msg",
+                    json!({ "message_id": message_id(&request.message_id) }),
+                )
+                .await?;
+            RecallMessageResponse::default().encode_to_vec()
+        }
+        ("FriendService", "GetFriendList") => {
+            let data = client.call("get_friend_list", json!({})).await?;
+            GetFriendListResponse {
+                friends_info: data
+                    .as_array()
+                    .map(|list| list.iter().map(to_friend_info).collect())
+                    .unwrap_or_default(),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupList") => {
+            // 群主与管理员只在GetGroupInfo中填充，避免每个群都请求一次成员列表
+            let data = client.call("get_group_list", json!({})).await?;
+            GetGroupListResponse {
+                groups_info: data
+                    .as_array()
+                    .map(|list| list.iter().map(to_group_info).collect())
+                    .unwrap_or_default(),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupInfo") => {
+            let request = GetGroupInfoRequest::decode(buf)?;
+            let data = client
+                .call("get_group_info", json!({ "group_id": request.group_id }))
+                .await?;
+            let mut info = to_group_info(&data);
+            fill_roles(client, &mut info).await;
+            GetGroupInfoResponse {
+                group_info: Some(info),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupMemberList") => {
+            let request = GetGroupMemberListRequest::decode(buf)?;
+            let data = client
+                .call(
+                    "get_group_member_list",
+                    json!({ "group_id": request.group_id }),
+                )
+                .await?;
+            GetGroupMemberListResponse {
+                group_members_info: data
+                    .as_array()
+                    .map(|list| list.iter().map(to_group_member_info).collect())
+                    .unwrap_or_default(),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupMemberInfo") => {
+            let request = GetGroupMemberInfoRequest::decode(buf)?;
+            let user_id = target_uin!(request.target, get_group_member_info_request);
+            let data = client
+                .call(
+                    "get_group_member_info",
+                    json!({
+                        "group_id": request.group_id,
+                        "user_id": user_id,
+                        "no_cache": request.refresh.unwrap_or(false),
+                    }),
+                )
+                .await?;
+            GetGroupMemberInfoResponse {
+                group_member_info: Some(to_group_member_info(&data)),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "BanMember") => {
+            let request = BanMemberRequest::decode(buf)?;
+            let user_id = target_uin!(request.target, ban_member_request);
+            client
+                .call(
+                    "set_group_ban",
+                    json!({
+                        "group_id": request.group_id,
+                        "user_id": user_id,
+                        "duration": request.duration,
+                    }),
+                )
+                .await?;
+            BanMemberResponse::default().encode_to_vec()
+        }
+        ("GroupService", "KickMember") => {
+            let request = KickMemberRequest::decode(buf)?;
+            let user_id = target_uin!(request.target, kick_member_request);
+            client
+                .call(
+                    "set_group_kick",
+                    json!({
+                        "group_id": request.group_id,
+                        "user_id": user_id,
+                        "reject_add_request": request.reject_add_request.unwrap_or(false),
+                    }),
+                )
+                .await?;
+            KickMemberResponse::default().encode_to_vec()
+        }
+        ("GroupService", "LeaveGroup") => {
+            let request = LeaveGroupRequest::decode(buf)?;
+            client
+                .call("set_group_leave", json!({ "group_id": request.group_id }))
+                .await?;
+            LeaveGroupResponse::default().encode_to_vec()
+        }
+        ("GroupService", "ModifyMemberCard") => {
+            let request = ModifyMemberCardRequest::decode(buf)?;
+            let user_id = target_uin!(request.target, modify_member_card_request);
+            client
+                .call(
+                    "set_group_card",
+                    json!({
+                        "group_id": request.group_id,
+                        "user_id": user_id,
+                        "card": request.card,
+                    }),
+                )
+                .await?;
+            ModifyMemberCardResponse::default().encode_to_vec()
+        }
+        ("GroupService", "ModifyGroupName") => {
+            let request = ModifyGroupNameRequest::decode(buf)?;
+            client
+                .call(
+                    "set_group_name",
+                    json!({ "group_id": request.group_id, "group_name": request.group_name }),
+                )
+                .await?;
+            ModifyGroupNameResponse::default().encode_to_vec()
+        }
+        ("GroupService", "SetGroupAdmin") => {
+            let request = SetGroupAdminRequest::decode(buf)?;
+            let user_id = target_uin!(request.target, set_group_admin_request);
+            client
+                .call(
+                    "set_group_admin",
+                    json!({
+                        "group_id": request.group_id,
+                        "user_id": user_id,
+                        "enable": request.is_admin,
+                    }),
+                )
+                .await?;
+            SetGroupAdminResponse::default().encode_to_vec()
+        }
+        ("GroupService", "SetGroupUniqueTitle") => {
+            let request = SetGroupUniqueTitleRequest::decode(buf)?;
+            let user_id = target_uin!(request.target, set_group_unique_title_request);
+            client
+                .call(
+                    "set_group_special_title",
+                    json!({
+                        "group_id": request.group_id,
+                        "user_id": user_id,
+                        "special_title": request.unique_title,
+                    }),
+                )
+                .await?;
+            SetGroupUniqueTitleResponse::default().encode_to_vec()
+        }
+        ("GroupService", "SetGroupWholeBan") => {
+            let request = SetGroupWholeBanRequest::decode(buf)?;
+            client
+                .call(
+                    "set_group_whole_ban",
+                    json!({ "group_id": request.group_id, "enable": request.is_ban }),
+                )
+                .await?;
+            SetGroupWholeBanResponse::default().encode_to_vec()
+        }
+        // OneBot事件的request_id即flag
+        ("ProcessService", "SetFriendApplyResult") => {
+            let request = SetFriendApplyResultRequest::decode(buf)?;
+            client
+                .call(
+                    "set_friend_add_request",
+                    json!({
+                        "flag": request.request_id,
+                        "approve": request.is_approve,
+                        "remark": request.remark.unwrap_or_default(),
+                    }),
+                )
+                .await?;
+            SetFriendApplyResultResponse::default().encode_to_vec()
+        }
+        ("ProcessService", "SetGroupApplyResult") => {
+            let request = SetGroupApplyResultRequest::decode(buf)?;
+            client
+                .call(
+                    "set_group_add_request",
+                    json!({
+                        "flag": request.request_id,
+                        "sub_type": "add",
+                        "approve": request.is_approve,
+                        "reason": request.deny_reason.unwrap_or_default(),
+                    }),
+                )
+                .await?;
+            SetGroupApplyResultResponse::default().encode_to_vec()
+        }
+        ("ProcessService", "SetInvitedJoinGroupResult") => {
+            let request = SetInvitedJoinGroupResultRequest::decode(buf)?;
+            client
+                .call(
+                    "set_group_add_request",
+                    json!({
+                        "flag": request.request_id,
+                        "sub_type": "invite",
+                        "approve": request.is_approve,
+                    }),
+                )
+                .await?;
+            SetInvitedJoinGroupResultResponse::default().encode_to_vec()
+        }
+        _ => return client_err!("{} is not supported by onebot", request.cmd),
+    };
+    Ok(response)
+}
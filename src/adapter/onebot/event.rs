@@ -0,0 +1,197 @@
+use log::debug;
+use serde_json::Value;
+
+use crate::adapter::onebot::message::{segments_to_elements, value_to_string, value_to_u64};
+use crate::kritor::server::kritor_proto::common::{Contact, PushMessageBody, Scene, Sender};
+use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::kritor::server::kritor_proto::notice_event::Notice;
+use crate::kritor::server::kritor_proto::request_event::Request;
+use crate::kritor::server::kritor_proto::{
+    FriendApplyRequest, FriendPokeNotice, FriendRecallNotice, GroupAdminChangedNotice,
+    GroupApplyRequest, GroupCardChangedNotice, GroupMemberBanNotice, GroupMemberDecreasedNotice,
+    GroupMemberIncreasedNotice, GroupPokeNotice, GroupRecallNotice, GroupWholeBanNotice,
+    InvitedJoinGroupRequest, NoticeEvent, RequestEvent,
+};
+
+/// OneBot事件转换为kritor事件，不支持的事件返回None
+pub fn to_event(value: &Value) -> Option<Event> {
+    match value["post_type"].as_str()? {
+        "message" => to_message(value).map(Event::Message),
+        "notice" => to_notice(value).map(Event::Notice),
+        "request" => to_request(value).map(Event::Request),
+        // 心跳、生命周期等元事件
+        _ => None,
+    }
+}
+
+fn to_message(value: &Value) -> Option<PushMessageBody> {
+    let user_id = value_to_u64(&value["user_id"]);
+    let contact = match value["message_type"].as_str()? {
+        "group" => Contact {
+            scene: Scene::Group.into(),
+            peer: value_to_string(&value["group_id"]),
+            sub_peer: None,
+        },
+        "private" => Contact {
+            scene: Scene::Friend.into(),
+            peer: user_id.to_string(),
+            sub_peer: None,
+        },
+        _ => return None,
+    };
+    let sender = &value["sender"];
+    let nick = sender["card"]
+        .as_str()
+        .filter(|card| !card.is_empty())
+        .or_else(|| sender["nickname"].as_str())
+        .map(|nick| nick.to_string());
+    Some(PushMessageBody {
+        time: value_to_u64(&value["time"]),
+        message_id: value_to_string(&value["message_id"]),
+        message_seq: value_to_u64(&value["message_id"]),
+        contact: Some(contact),
+        sender: Some(Sender {
+            uid: user_id.to_string(),
+            uin: Some(user_id),
+            nick,
+        }),
+        elements: segments_to_elements(&value["message"]),
+        ..Default::default()
+    })
+}
+
+fn to_notice(value: &Value) -> Option<NoticeEvent> {
+    let group_id = value_to_u64(&value["group_id"]);
+    let user_id = value_to_u64(&value["user_id"]);
+    let operator_id = value_to_u64(&value["operator_id"]);
+    let notice = match value["notice_type"].as_str()? {
+        "group_increase" => Notice::GroupMemberIncrease(GroupMemberIncreasedNotice {
+            group_id,
+            operator_uid: operator_id.to_string(),
+            operator_uin: operator_id,
+            target_uid: user_id.to_string(),
+            target_uin: user_id,
+            ..Default::default()
+        }),
+        "group_decrease" => Notice::GroupMemberDecrease(GroupMemberDecreasedNotice {
+            group_id,
+            operator_uid: Some(operator_id.to_string()),
+            operator_uin: Some(operator_id),
+            target_uid: Some(user_id.to_string()),
+            target_uin: Some(user_id),
+            ..Default::default()
+        }),
+        "group_admin" => Notice::GroupAdminChange(GroupAdminChangedNotice {
+            group_id,
+            target_uid: user_id.to_string(),
+            target_uin: user_id,
+            is_admin: value["sub_type"].as_str() == Some("set"),
+        }),
+        // user_id为0时是全员禁言
+        "group_ban" if user_id == 0 => Notice::GroupWholeBan(GroupWholeBanNotice {
+            group_id,
+            operator_uid: operator_id.to_string(),
+            operator_uin: operator_id,
+            is_ban: value["sub_type"].as_str() == Some("ban"),
+        }),
+        "group_ban" => Notice::GroupMemberBan(GroupMemberBanNotice {
+            group_id,
+            operator_uid: operator_id.to_string(),
+            operator_uin: operator_id,
+            target_uid: user_id.to_string(),
+            target_uin: user_id,
+            duration: value_to_u64(&value["duration"]) as _,
+            ..Default::default()
+        }),
+        "group_recall" => Notice::GroupRecall(GroupRecallNotice {
+            group_id,
+            message_id: value_to_string(&value["message_id"]),
+            operator_uid: operator_id.to_string(),
+            operator_uin: operator_id,
+            target_uid: user_id.to_string(),
+            target_uin: user_id,
+            ..Default::default()
+        }),
+        "friend_recall" => Notice::FriendRecall(FriendRecallNotice {
+            operator_uid: user_id.to_string(),
+            operator_uin: user_id,
+            message_id: value_to_string(&value["message_id"]),
+            ..Default::default()
+        }),
+        "group_card" => Notice::GroupCardChanged(GroupCardChangedNotice {
+            group_id,
+            operator_uid: user_id.to_string(),
+            operator_uin: user_id,
+            target_uid: user_id.to_string(),
+            target_uin: user_id,
+            new_card: value_to_string(&value["card_new"]),
+        }),
+        "notify" if value["sub_type"].as_str() == Some("poke") => {
+            let target_id = value_to_u64(&value["target_id"]);
+            if group_id == 0 {
+                Notice::FriendPoke(FriendPokeNotice {
+                    operator_uid: user_id.to_string(),
+                    operator_uin: user_id,
+                    ..Default::default()
+                })
+            } else {
+                Notice::GroupPoke(GroupPokeNotice {
+                    group_id,
+                    operator_uid: user_id.to_string(),
+                    operator_uin: user_id,
+                    target_uid: target_id.to_string(),
+                    target_uin: target_id,
+                    ..Default::default()
+                })
+            }
+        }
+        _ => {
+            debug!("unsupported onebot notice: {}", value);
+            return None;
+        }
+    };
+    Some(NoticeEvent {
+        time: value_to_u64(&value["time"]),
+        notice: Some(notice),
+        ..Default::default()
+    })
+}
+
+fn to_request(value: &Value) -> Option<RequestEvent> {
+    let user_id = value_to_u64(&value["user_id"]);
+    let group_id = value_to_u64(&value["group_id"]);
+    let flag = value_to_string(&value["flag"]);
+    let request = match (value["request_type"].as_str()?, value["sub_type"].as_str()) {
+        ("friend", _) => Request::FriendApply(FriendApplyRequest {
+            applier_uid: user_id.to_string(),
+            applier_uin: user_id,
+            flag: flag.clone(),
+            message: value_to_string(&value["comment"]),
+        }),
+        ("group", Some("invite")) => Request::InvitedGroup(InvitedJoinGroupRequest {
+            group_id,
+            inviter_uid: user_id.to_string(),
+            inviter_uin: user_id,
+            flag: flag.clone(),
+        }),
+        ("group", _) => Request::GroupApply(GroupApplyRequest {
+            group_id,
+            applier_uid: user_id.to_string(),
+            applier_uin: user_id,
+            reason: value_to_string(&value["comment"]),
+            flag: flag.clone(),
+            ..Default::default()
+        }),
+        _ => {
+            debug!("unsupported onebot request: {}", value);
+            return None;
+        }
+    };
+    // OneBot以flag处理请求
+    Some(RequestEvent {
+        time: value_to_u64(&value["time"]),
+        request_id: flag,
+        request: Some(request),
+        ..Default::default()
+    })
+}
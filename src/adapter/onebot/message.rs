@@ -0,0 +1,278 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{debug, warn};
+use serde_json::{json, Map, Value};
+
+use crate::kritor::server::kritor_proto::common::element::{Data, ElementType};
+use crate::kritor::server::kritor_proto::common::{
+    image_element, video_element, voice_element, AtElement, DiceElement, Element, FaceElement,
+    ImageElement, JsonElement, PokeElement, ReplyElement, RpsElement, ShareElement, TextElement,
+    VideoElement, VoiceElement, XmlElement,
+};
+
+fn element(r#type: ElementType, data: Data) -> Element {
+    Element {
+        r#type: r#type.into(),
+        data: Some(data),
+    }
+}
+
+/// OneBot的数字字段可能是数字也可能是字符串
+pub fn value_to_u64(value: &Value) -> u64 {
+    match value {
+        Value::Number(n) => n.as_u64().unwrap_or_default(),
+        Value::String(s) => s.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+pub fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+fn unescape_cq(text: &str, in_param: bool) -> String {
+    let text = text
+        .replace("&#91;", "[")
+        .replace("&#93;", "]");
+    let text = if in_param {
+        text.replace("&#44;", ",")
+    } else {
+        text
+    };
+    text.replace("&amp;", "&")
+}
+
+/// 解析CQ码格式的字符串消息为消息段数组
+pub fn parse_cq_code(message: &str) -> Vec<Value> {
+    let mut segments = Vec::new();
+    let mut rest = message;
+    while let Some(start) = rest.find("[CQ:") {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end,
+            None => break,
+        };
+        if start > 0 {
+            segments.push(json!({"type": "text", "data": {"text": unescape_cq(&rest[..start], false)}}));
+        }
+        let code = &rest[start + 4..end];
+        let mut parts = code.split(',');
+        let r#type = parts.next().unwrap_or_default();
+        let mut data = Map::new();
+        for part in parts {
+            if let Some((key, value)) = part.split_once('=') {
+                data.insert(key.to_string(), Value::String(unescape_cq(value, true)));
+            }
+        }
+        segments.push(json!({"type": r#type, "data": data}));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(json!({"type": "text", "data": {"text": unescape_cq(rest, false)}}));
+    }
+    segments
+}
+
+fn segment_to_element(segment: &Value) -> Option<Element> {
+    let data = &segment["data"];
+    let r#type = segment["type"].as_str().unwrap_or_default();
+    let element = match r#type {
+        "text" => element(
+            ElementType::Text,
+            Data::Text(TextElement {
+                text: value_to_string(&data["text"]),
+            }),
+        ),
+        "at" => {
+            let qq = value_to_string(&data["qq"]);
+            let uin = qq.parse().ok();
+            element(ElementType::At, Data::At(AtElement { uid: qq, uin }))
+        }
+        "face" => element(
+            ElementType::Face,
+            Data::Face(FaceElement {
+                id: value_to_u64(&data["id"]) as _,
+                ..Default::default()
+            }),
+        ),
+        "reply" => element(
+            ElementType::Reply,
+            Data::Reply(ReplyElement {
+                message_id: value_to_string(&data["id"]),
+            }),
+        ),
+        "image" => {
+            let url = data["url"].as_str().or_else(|| data["file"].as_str());
+            element(
+                ElementType::Image,
+                Data::Image(ImageElement {
+                    file_md5: None,
+                    sub_type: None,
+                    r#type: None,
+                    data: url.map(|url| image_element::Data::FileUrl(url.to_string())),
+                }),
+            )
+        }
+        "record" => {
+            let url = data["url"].as_str().or_else(|| data["file"].as_str());
+            element(
+                ElementType::Voice,
+                Data::Voice(VoiceElement {
+                    data: url.map(|url| voice_element::Data::FileUrl(url.to_string())),
+                    ..Default::default()
+                }),
+            )
+        }
+        "video" => {
+            let url = data["url"].as_str().or_else(|| data["file"].as_str());
+            element(
+                ElementType::Video,
+                Data::Video(VideoElement {
+                    data: url.map(|url| video_element::Data::FileUrl(url.to_string())),
+                    ..Default::default()
+                }),
+            )
+        }
+        "dice" => element(
+            ElementType::Dice,
+            Data::Dice(DiceElement {
+                id: value_to_u64(&data["result"]) as _,
+            }),
+        ),
+        "rps" => element(
+            ElementType::Rps,
+            Data::Rps(RpsElement {
+                id: value_to_u64(&data["result"]) as _,
+            }),
+        ),
+        "poke" => element(
+            ElementType::Poke,
+            Data::Poke(PokeElement {
+                id: value_to_u64(&data["id"]) as _,
+                r#type: value_to_u64(&data["type"]) as _,
+                ..Default::default()
+            }),
+        ),
+        "share" => element(
+            ElementType::Share,
+            Data::Share(ShareElement {
+                url: value_to_string(&data["url"]),
+                title: value_to_string(&data["title"]),
+                content: value_to_string(&data["content"]),
+                image: value_to_string(&data["image"]),
+            }),
+        ),
+        "json" => element(
+            ElementType::Json,
+            Data::Json(JsonElement {
+                json: value_to_string(&data["data"]),
+            }),
+        ),
+        "xml" => element(
+            ElementType::Xml,
+            Data::Xml(XmlElement {
+                xml: value_to_string(&data["data"]),
+            }),
+        ),
+        _ => {
+            debug!("unsupported onebot segment: {}", segment);
+            return None;
+        }
+    };
+    Some(element)
+}
+
+/// OneBot消息（消息段数组或CQ码字符串）转换为kritor的Element
+pub fn segments_to_elements(message: &Value) -> Vec<Element> {
+    match message {
+        Value::Array(segments) => segments.iter().filter_map(segment_to_element).collect(),
+        Value::String(message) => parse_cq_code(message)
+            .iter()
+            .filter_map(segment_to_element)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn file_to_onebot(file: Option<String>, bytes: Option<Vec<u8>>) -> Option<String> {
+    bytes
+        .map(|bytes| format!("base64://{}", STANDARD.encode(bytes)))
+        .or(file)
+}
+
+fn image_file(data: Option<image_element::Data>) -> Option<String> {
+    match data? {
+        image_element::Data::File(bytes) => file_to_onebot(None, Some(bytes)),
+        image_element::Data::FileUrl(url) => Some(url),
+        image_element::Data::FilePath(path) => Some(format!("file:///{}", path.trim_start_matches('/'))),
+        image_element::Data::FileName(name) => Some(name),
+    }
+}
+
+fn voice_file(data: Option<voice_element::Data>) -> Option<String> {
+    match data? {
+        voice_element::Data::File(bytes) => file_to_onebot(None, Some(bytes)),
+        voice_element::Data::FileUrl(url) => Some(url),
+        voice_element::Data::FilePath(path) => Some(format!("file:///{}", path.trim_start_matches('/'))),
+        voice_element::Data::FileName(name) => Some(name),
+    }
+}
+
+fn video_file(data: Option<video_element::Data>) -> Option<String> {
+    match data? {
+        video_element::Data::File(bytes) => file_to_onebot(None, Some(bytes)),
+        video_element::Data::FileUrl(url) => Some(url),
+        video_element::Data::FilePath(path) => Some(format!("file:///{}", path.trim_start_matches('/'))),
+        video_element::Data::FileName(name) => Some(name),
+    }
+}
+
+fn element_to_segment(element: &Element) -> Option<Value> {
+    let segment = match element.data.clone()? {
+        Data::Text(text) => json!({"type": "text", "data": {"text": text.text}}),
+        Data::At(at) => {
+            let qq = at.uin.map(|uin| uin.to_string()).unwrap_or(at.uid);
+            json!({"type": "at", "data": {"qq": qq}})
+        }
+        Data::Face(face) => json!({"type": "face", "data": {"id": face.id.to_string()}}),
+        Data::Reply(reply) => json!({"type": "reply", "data": {"id": reply.message_id}}),
+        Data::Image(image) => {
+            json!({"type": "image", "data": {"file": image_file(image.data)?}})
+        }
+        Data::Voice(voice) => {
+            json!({"type": "record", "data": {"file": voice_file(voice.data)?}})
+        }
+        Data::Video(video) => {
+            json!({"type": "video", "data": {"file": video_file(video.data)?}})
+        }
+        Data::Dice(_) => json!({"type": "dice", "data": {}}),
+        Data::Rps(_) => json!({"type": "rps", "data": {}}),
+        Data::Poke(poke) => {
+            json!({"type": "poke", "data": {"type": poke.r#type.to_string(), "id": poke.id.to_string()}})
+        }
+        Data::Share(share) => json!({"type": "share", "data": {
+            "url": share.url,
+            "title": share.title,
+            "content": share.content,
+            "image": share.image,
+        }}),
+        Data::Json(json_element) => json!({"type": "json", "data": {"data": json_element.json}}),
+        Data::Xml(xml) => json!({"type": "xml", "data": {"data": xml.xml}}),
+        _ => {
+            warn!(
+                "element {:?} is not supported by onebot, skipped",
+                ElementType::try_from(element.r#type)
+            );
+            return None;
+        }
+    };
+    Some(segment)
+}
+
+/// kritor的Element转换为OneBot消息段数组
+pub fn elements_to_segments(elements: &[Element]) -> Value {
+    Value::Array(elements.iter().filter_map(element_to_segment).collect())
+}
@@ -0,0 +1,272 @@
+pub mod action;
+pub mod event;
+pub mod message;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::{SinkExt, StreamExt};
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::{accept_hdr_async, connect_async, WebSocketStream};
+
+use crate::adapter::onebot::message::{value_to_string, value_to_u64};
+use crate::adapter::{attach_bot, RequestHandler};
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::common;
+use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::model::config::{OneBotConfig, OneBotMode};
+use crate::model::error::Result;
+use crate::{client_err, network_err};
+
+/// 单个action等待响应的时间
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 一条OneBot WebSocket连接，通过echo匹配action的响应
+pub struct OneBotClient {
+    sender: mpsc::Sender<WsMessage>,
+    pending: DashMap<String, oneshot::Sender<Value>>,
+    echo: AtomicU64,
+}
+
+impl OneBotClient {
+    pub fn new(sender: mpsc::Sender<WsMessage>) -> Self {
+        Self {
+            sender,
+            pending: DashMap::new(),
+            echo: AtomicU64::new(0),
+        }
+    }
+
+    /// 调用OneBot action，返回响应中的data
+    pub async fn call(&self, action: &str, params: Value) -> Result<Value> {
+        let echo = self.echo.fetch_add(1, Ordering::Relaxed).to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(echo.clone(), tx);
+        let frame = json!({ "action": action, "params": params, "echo": echo });
+        debug!("OneBot action: {}", frame);
+        if self.sender.send(WsMessage::Text(frame.to_string())).await.is_err() {
+            self.pending.remove(&echo);
+            return network_err!("Connection closed");
+        }
+        let response = match tokio::time::timeout(ACTION_TIMEOUT, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return network_err!("Connection closed"),
+            Err(_) => {
+                self.pending.remove(&echo);
+                return network_err!("action {} timed out", action);
+            }
+        };
+        if response["status"].as_str() == Some("failed") {
+            let msg = response["wording"]
+                .as_str()
+                .or_else(|| response["msg"].as_str())
+                .unwrap_or_default();
+            return client_err!(
+                "action {} failed, retcode {}: {}",
+                action,
+                response["retcode"],
+                msg
+            );
+        }
+        Ok(response["data"].clone())
+    }
+
+    /// 处理收到的一帧，action响应交给等待方，事件转换后返回
+    pub fn handle_frame(&self, frame: Value) -> Option<Event> {
+        if let Some(echo) = frame.get("echo") {
+            if let Some((_, tx)) = self.pending.remove(&value_to_string(echo)) {
+                let _ = tx.send(frame);
+            } else {
+                debug!("unknown onebot response: {}", frame);
+            }
+            return None;
+        }
+        event::to_event(&frame)
+    }
+}
+
+#[async_trait]
+impl RequestHandler for OneBotClient {
+    async fn handle(&self, request: &common::Request) -> Result<Vec<u8>> {
+        action::handle(self, request).await
+    }
+}
+
+/// 在已建立的WebSocket连接上运行，直到连接断开才返回
+pub async fn run_session<S>(ws: WebSocketStream<S>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sink, mut stream) = ws.split();
+    let (tx, mut rx) = mpsc::channel(1024);
+    let writer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if let Err(e) = sink.send(message).await {
+                warn!("Failed to send onebot frame: {}", e);
+                break;
+            }
+        }
+    });
+
+    let client = Arc::new(OneBotClient::new(tx));
+    // Bot创建前收到的事件先缓存
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let reader_client = client.clone();
+    let reader = tokio::spawn(async move {
+        while let Some(message) = stream.next().await {
+            let text = match message {
+                Ok(WsMessage::Text(text)) => text,
+                Ok(WsMessage::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("OneBot connection error: {}", e);
+                    break;
+                }
+            };
+            let frame = match serde_json::from_str::<Value>(&text) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    warn!("Invalid onebot frame: {}", e);
+                    continue;
+                }
+            };
+            if let Some(event) = reader_client.handle_frame(frame) {
+                if event_tx.send(event).is_err() {
+                    break;
+                }
+            }
+        }
+        // 让还在等待的action立即返回
+        reader_client.pending.clear();
+    });
+
+    let result = serve_bot(client, &mut event_rx).await;
+    reader.abort();
+    writer.abort();
+    result
+}
+
+async fn serve_bot(
+    client: Arc<OneBotClient>,
+    events: &mut mpsc::UnboundedReceiver<Event>,
+) -> Result<()> {
+    let login = client.call("get_login_info", json!({})).await?;
+    let uin = value_to_u64(&login["user_id"]);
+    let version = client
+        .call("get_version_info", json!({}))
+        .await
+        .ok()
+        .map(|data| {
+            format!(
+                "{} {}",
+                value_to_string(&data["app_name"]),
+                value_to_string(&data["app_version"])
+            )
+        });
+    info!("OneBot account connected: {}", uin);
+    // OneBot没有uid，以uin的字符串形式代替
+    let (bot, connection, forward) = attach_bot(uin, uin.to_string(), version, client).await;
+    while let Some(event) = events.recv().await {
+        Bot::push_event(&bot, event).await;
+    }
+    forward.abort();
+    Bot::disconnect(bot, connection).await;
+    network_err!("OneBot connection {} closed", uin)
+}
+
+/// 校验反向连接携带的access_token，支持请求头和query参数
+pub fn authorized(request: &Request, access_token: Option<&str>) -> bool {
+    let Some(access_token) = access_token else {
+        return true;
+    };
+    let header = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .trim_start_matches("Bearer ")
+                .trim_start_matches("Token ")
+                .to_string()
+        });
+    let query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("access_token="))
+            .map(|token| token.to_string())
+    });
+    header.or(query).as_deref() == Some(access_token)
+}
+
+async fn connect_forward(config: &OneBotConfig) -> Result<()> {
+    let mut request = config.address.as_str().into_client_request()?;
+    if let Some(token) = config.access_token.as_ref() {
+        if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+    }
+    let (ws, _) = connect_async(request).await?;
+    info!("Connected to onebot: {}", config.address);
+    run_session(ws).await
+}
+
+async fn listen_reverse(config: &OneBotConfig) -> Result<()> {
+    let listener = TcpListener::bind(&config.address).await?;
+    info!("OneBot reverse websocket listening on {}", config.address);
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let access_token = config.access_token.clone();
+        tokio::spawn(async move {
+            let callback = |request: &Request, response: Response| {
+                if authorized(request, access_token.as_deref()) {
+                    Ok(response)
+                } else {
+                    let mut response = ErrorResponse::new(Some("Unauthorized".to_string()));
+                    *response.status_mut() = StatusCode::UNAUTHORIZED;
+                    Err(response)
+                }
+            };
+            match accept_hdr_async(stream, callback).await {
+                Ok(ws) => {
+                    info!("OneBot connected from {}", addr);
+                    if let Err(e) = run_session(ws).await {
+                        warn!("OneBot session from {} ended: {}", addr, e);
+                    }
+                }
+                Err(e) => warn!("OneBot handshake from {} failed: {}", addr, e),
+            }
+        });
+    }
+}
+
+/// 按配置启动OneBot连接，正向连接断开后按间隔重连
+pub async fn connect_onebot(config: OneBotConfig) {
+    match config.mode {
+        OneBotMode::Forward => {
+            let interval = Duration::from_secs(config.reconnect_interval.unwrap_or(5));
+            loop {
+                if let Err(e) = connect_forward(&config).await {
+                    error!("OneBot connection to {} lost: {}", config.address, e);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        }
+        OneBotMode::Reverse => {
+            if let Err(e) = listen_reverse(&config).await {
+                error!("OneBot listener on {} stopped: {}", config.address, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use prost::Message;
+use tokio::sync::RwLock;
+
+use crate::adapter::{attach_bot, RequestHandler};
+use crate::bot::bot::{Bot, BotState};
+use crate::kritor::record::{read_records, record, Record};
+use crate::kritor::server::kritor_proto::{common, SendMessageRequest};
+use crate::model::config::ReplayConfig;
+use crate::model::error::Result;
+use crate::service::service::Elements;
+
+/// 回放时用录制的响应应答Bot的请求，同一cmd的响应按录制顺序依次返回，最后一个会被重复使用
+pub struct ReplayHandler {
+    responses: DashMap<String, VecDeque<Vec<u8>>>,
+}
+
+impl ReplayHandler {
+    pub fn new(records: &[Record], uid: &str) -> Self {
+        let responses: DashMap<String, VecDeque<Vec<u8>>> = DashMap::new();
+        for record in records.iter().filter(|record| record.uid == uid) {
+            if let Some(record::Data::Response(response)) = record.data.as_ref() {
+                if response.code == 0 {
+                    responses
+                        .entry(response.cmd.clone())
+                        .or_default()
+                        .push_back(response.buf.clone());
+                }
+            }
+        }
+        Self { responses }
+    }
+}
+
+#[async_trait]
+impl RequestHandler for ReplayHandler {
+    async fn handle(&self, request: &common::Request) -> Result<Vec<u8>> {
+        if request.cmd == "MessageService.SendMessage" {
+            if let Ok(message) = SendMessageRequest::decode(request.buf.as_slice()) {
+                info!(
+                    "[Replay] -> {:?}: {}",
+                    message.contact,
+                    message.elements.get_raw_msg()
+                );
+            }
+        } else {
+            info!("[Replay] -> {}", request.cmd);
+        }
+        let Some(mut responses) = self.responses.get_mut(&request.cmd) else {
+            debug!("no recorded response for {}", request.cmd);
+            return Ok(vec![]);
+        };
+        let buf = if responses.len() > 1 {
+            responses.pop_front().unwrap_or_default()
+        } else {
+            responses.front().cloned().unwrap_or_default()
+        };
+        Ok(buf)
+    }
+
+    /// 回放的事件和请求本身来自录制文件，不再重复录制
+    fn recordable(&self) -> bool {
+        false
+    }
+}
+
+async fn wait_online(bot: &Arc<RwLock<Bot>>) {
+    let online = tokio::time::timeout(Duration::from_secs(10), async {
+        while bot.read().await.get_state().await != BotState::Online {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    if online.is_err() {
+        warn!("Replay bot is not online yet, start replaying anyway");
+    }
+}
+
+/// 将录制文件中的事件重新推送给Bot
+///
+/// speed为1时按原始间隔回放，大于1时加速，为0时不等待
+pub async fn replay(config: ReplayConfig) -> Result<()> {
+    let records = read_records(&config.path)?;
+    let speed = config.speed.unwrap_or(1.0);
+    info!(
+        "Replaying {} records from {} at {}x",
+        records.len(),
+        config.path,
+        speed
+    );
+
+    let mut bots = HashMap::new();
+    for record in &records {
+        if bots.contains_key(&record.uid) {
+            continue;
+        }
+        let handler = Arc::new(ReplayHandler::new(&records, &record.uid));
+        let (bot, _connection, forward) = attach_bot(
+            record.uin,
+            record.uid.clone(),
+            Some("replay".to_string()),
+            handler,
+        )
+        .await;
+        wait_online(&bot).await;
+        bots.insert(record.uid.clone(), (bot, forward));
+    }
+
+    let mut previous = None;
+    let mut count = 0;
+    for record in records {
+        let Some(record::Data::Event(event)) = record.data else {
+            continue;
+        };
+        let Some(event) = event.event else {
+            continue;
+        };
+        if speed > 0.0 {
+            if let Some(previous) = previous {
+                let delay = record.time.saturating_sub(previous) as f64 / speed;
+                tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+            }
+        }
+        previous = Some(record.time);
+        if let Some((bot, _)) = bots.get(&record.uid) {
+            Bot::push_event(bot, event).await;
+            count += 1;
+        }
+    }
+    info!("Replay finished, {} events pushed", count);
+    Ok(())
+}
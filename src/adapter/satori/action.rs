@@ -0,0 +1,298 @@
+use log::debug;
+use prost::Message;
+use serde_json::{json, Value};
+
+use crate::adapter::satori::event::id_of;
+use crate::adapter::satori::message::elements_to_content;
+use crate::adapter::satori::SatoriClient;
+use crate::kritor::server::kritor_proto::common::{self, Scene};
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+use crate::{client_err, err};
+
+/// 取出目标的用户id，Satori的uid就是平台的用户id
+macro_rules! target_id {
+    ($target:expr, $module:ident) => {
+        match $target {
+            Some($module::Target::TargetUid(uid)) => uid,
+            Some($module::Target::TargetUin(uin)) => uin.to_string(),
+            None => return client_err!("target is required"),
+        }
+    };
+}
+
+fn str_of(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+fn user_name(user: &Value) -> String {
+    user["nick"]
+        .as_str()
+        .or_else(|| user["name"].as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// id无法解析为数字的好友、群、成员被跳过，见id_of
+fn to_friend_info(user: &Value) -> Option<FriendInfo> {
+    Some(FriendInfo {
+        uid: str_of(&user["id"]),
+        uin: id_of(&user["id"])?,
+        nick: user_name(user),
+        ..Default::default()
+    })
+}
+
+fn to_group_info(guild: &Value) -> Option<GroupInfo> {
+    Some(GroupInfo {
+        group_id: id_of(&guild["id"])?,
+        group_name: str_of(&guild["name"]),
+        ..Default::default()
+    })
+}
+
+/// 成员的角色，QQ等平台的roles中有owner、admin
+fn role_of(member: &Value) -> Option<&str> {
+    member["roles"]
+        .as_array()?
+        .iter()
+        .filter_map(|role| role.as_str().or_else(|| role["id"].as_str()))
+        .find(|role| matches!(*role, "owner" | "admin"))
+}
+
+/// Satori的guild不含群主与管理员，从成员列表的roles中取出，失败时保持为空
+async fn fill_roles(client: &SatoriClient, guild_id: &str, info: &mut GroupInfo) {
+    let members = match client
+        .list("guild.member.list", json!({ "guild_id": guild_id }))
+        .await
+    {
+        Ok(members) => members,
+        Err(e) => {
+            debug!("Failed to get roles of guild {}: {}", guild_id, e);
+            return;
+        }
+    };
+    for member in members.iter() {
+        let Some(user_id) = id_of(&member["user"]["id"]) else {
+            continue;
+        };
+        match role_of(member) {
+            Some("owner") => info.owner = user_id,
+            Some(_) => info.admins.push(user_id),
+            None => {}
+        }
+    }
+}
+
+fn to_group_member_info(member: &Value) -> Option<GroupMemberInfo> {
+    let user = &member["user"];
+    Some(GroupMemberInfo {
+        uid: str_of(&user["id"]),
+        uin: id_of(&user["id"])?,
+        nick: user_name(user),
+        card: str_of(&member["nick"]),
+        join_time: member["joined_at"].as_u64().unwrap_or_default() / 1000,
+        ..Default::default()
+    })
+}
+
+/// 群聊消息发往的频道，sub_peer为频道id，没有时guild id即频道id
+pub fn group_channel(contact: common::Contact) -> String {
+    contact.sub_peer.unwrap_or(contact.peer)
+}
+
+/// 消息发往的频道，私聊需要先取得私聊频道
+async fn channel_id(client: &SatoriClient, contact: Option<common::Contact>) -> Result<String> {
+    let Some(contact) = contact else {
+        return client_err!("contact is required");
+    };
+    match Scene::try_from(contact.scene) {
+        Ok(Scene::Group) => Ok(group_channel(contact)),
+        Ok(Scene::Friend) => client.direct_channel(&contact.peer).await,
+        _ => client_err!("unsupported scene: {}", contact.scene),
+    }
+}
+
+/// 处理好友申请、加群申请与入群邀请
+async fn approve(
+    client: &SatoriClient,
+    method: &str,
+    message_id: String,
+    approve: bool,
+    comment: Option<String>,
+) -> Result<()> {
+    let mut body = json!({ "message_id": message_id, "approve": approve });
+    if let Some(comment) = comment {
+        body["comment"] = Value::from(comment);
+    }
+    client.call(method, body).await?;
+    Ok(())
+}
+
+/// 将kritor请求翻译为Satori API调用，返回编码后的kritor响应
+///
+/// kritor的群号是数字，无法解析为数字的guild id会被置为0
+pub async fn handle(client: &SatoriClient, request: &common::Request) -> Result<Vec<u8>> {
+    let Some((service, method)) = request.cmd.split_once('.') else {
+        return err!("invalid cmd: {}", request.cmd);
+    };
+    let buf = request.buf.as_slice();
+    let response = match (service, method) {
+        ("CoreService", "GetCurrentAccount") => {
+            let login = client.call("login.get", json!({})).await?;
+            GetCurrentAccountResponse {
+                account_uid: client.self_id.clone(),
+                account_uin: client.self_id.parse().unwrap_or_default(),
+                account_name: user_name(&login["user"]),
+            }
+            .encode_to_vec()
+        }
+        ("CoreService", "GetVersion") => GetVersionResponse {
+            version: "satori".to_string(),
+            app_name: client.platform.clone(),
+        }
+        .encode_to_vec(),
+        ("MessageService", "SendMessage") => {
+            let request = SendMessageRequest::decode(buf)?;
+            let channel_id = channel_id(client, request.contact).await?;
+            let messages = client
+                .call(
+                    "message.create",
+                    json!({
+                        "channel_id": channel_id,
+                        "content": elements_to_content(&request.elements),
+                    }),
+                )
+                .await?;
+            SendMessageResponse {
+                message_id: str_of(&messages[0]["id"]),
+                ..Default::default()
+            }
+            .encode_to_vec()
+        }
+        ("MessageService", "RecallMessage") => {
+            let request = RecallMessageRequest::decode(buf)?;
+            let channel_id = channel_id(client, request.contact).await?;
+            client
+                .call(
+                    "message.delete",
+                    json!({ "channel_id": channel_id, "message_id": request.message_id }),
+                )
+                .await?;
+            RecallMessageResponse::default().encode_to_vec()
+        }
+        ("FriendService", "GetFriendList") => {
+            let friends = client.list("friend.list", json!({})).await?;
+            GetFriendListResponse {
+                friends_info: friends.iter().filter_map(to_friend_info).collect(),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupList") => {
+            // 群主与管理员只在GetGroupInfo中填充，避免每个群都请求一次成员列表
+            let guilds = client.list("guild.list", json!({})).await?;
+            GetGroupListResponse {
+                groups_info: guilds.iter().filter_map(to_group_info).collect(),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupInfo") => {
+            let request = GetGroupInfoRequest::decode(buf)?;
+            let guild_id = request.group_id.to_string();
+            let guild = client
+                .call("guild.get", json!({ "guild_id": guild_id }))
+                .await?;
+            let mut info = to_group_info(&guild);
+            if let Some(info) = info.as_mut() {
+                fill_roles(client, &guild_id, info).await;
+            }
+            GetGroupInfoResponse { group_info: info }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupMemberList") => {
+            let request = GetGroupMemberListRequest::decode(buf)?;
+            let members = client
+                .list(
+                    "guild.member.list",
+                    json!({ "guild_id": request.group_id.to_string() }),
+                )
+                .await?;
+            GetGroupMemberListResponse {
+                group_members_info: members.iter().filter_map(to_group_member_info).collect(),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "GetGroupMemberInfo") => {
+            let request = GetGroupMemberInfoRequest::decode(buf)?;
+            let user_id = target_id!(request.target, get_group_member_info_request);
+            let member = client
+                .call(
+                    "guild.member.get",
+                    json!({ "guild_id": request.group_id.to_string(), "user_id": user_id }),
+                )
+                .await?;
+            GetGroupMemberInfoResponse {
+                group_member_info: to_group_member_info(&member),
+            }
+            .encode_to_vec()
+        }
+        ("GroupService", "KickMember") => {
+            let request = KickMemberRequest::decode(buf)?;
+            let user_id = target_id!(request.target, kick_member_request);
+            client
+                .call(
+                    "guild.member.kick",
+                    json!({
+                        "guild_id": request.group_id.to_string(),
+                        "user_id": user_id,
+                        "permanent": request.reject_add_request.unwrap_or(false),
+                    }),
+                )
+                .await?;
+            KickMemberResponse::default().encode_to_vec()
+        }
+        ("GroupService", "BanMember") => {
+            let request = BanMemberRequest::decode(buf)?;
+            let user_id = target_id!(request.target, ban_member_request);
+            // Satori的时长单位为毫秒
+            client
+                .call(
+                    "guild.member.mute",
+                    json!({
+                        "guild_id": request.group_id.to_string(),
+                        "user_id": user_id,
+                        "duration": request.duration as u64 * 1000,
+                    }),
+                )
+                .await?;
+            BanMemberResponse::default().encode_to_vec()
+        }
+        // Satori事件的request_id即请求消息的id
+        ("ProcessService", "SetFriendApplyResult") => {
+            let request = SetFriendApplyResultRequest::decode(buf)?;
+            approve(client, "friend.approve", request.request_id, request.is_approve, None)
+                .await?;
+            SetFriendApplyResultResponse::default().encode_to_vec()
+        }
+        ("ProcessService", "SetGroupApplyResult") => {
+            let request = SetGroupApplyResultRequest::decode(buf)?;
+            approve(
+                client,
+                "guild.member.approve",
+                request.request_id,
+                request.is_approve,
+                request.deny_reason,
+            )
+            .await?;
+            SetGroupApplyResultResponse::default().encode_to_vec()
+        }
+        ("ProcessService", "SetInvitedJoinGroupResult") => {
+            let request = SetInvitedJoinGroupResultRequest::decode(buf)?;
+            approve(client, "guild.approve", request.request_id, request.is_approve, None)
+                .await?;
+            SetInvitedJoinGroupResultResponse::default().encode_to_vec()
+        }
+        _ => return client_err!("{} is not supported by satori", request.cmd),
+    };
+    Ok(response)
+}
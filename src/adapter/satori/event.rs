@@ -0,0 +1,198 @@
+use log::{debug, warn};
+use serde_json::Value;
+
+use crate::adapter::satori::message::content_to_elements;
+use crate::kritor::server::kritor_proto::common::{Contact, PushMessageBody, Scene, Sender};
+use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::kritor::server::kritor_proto::notice_event::Notice;
+use crate::kritor::server::kritor_proto::request_event::Request;
+use crate::kritor::server::kritor_proto::{
+    FriendApplyRequest, FriendRecallNotice, GroupApplyRequest, GroupMemberDecreasedNotice,
+    GroupMemberIncreasedNotice, GroupRecallNotice, InvitedJoinGroupRequest, NoticeEvent,
+    RequestEvent,
+};
+
+/// Satori的频道类型，1为私聊
+const DIRECT_CHANNEL: u64 = 1;
+
+fn str_of(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+/// kritor中只能为数字的uin、群号，没有id时为0
+///
+/// Satori的id是字符串，Matrix、Telegram等平台的id无法解析为数字，此时返回None，
+/// 由调用方跳过，避免不同的群或用户共用0作为缓存的键
+pub fn id_of(value: &Value) -> Option<u64> {
+    match value.as_str() {
+        None | Some("") => Some(0),
+        Some(id) => match id.parse() {
+            Ok(id) => Some(id),
+            Err(_) => {
+                warn!("satori id {} is not numeric, skipped", id);
+                None
+            }
+        },
+    }
+}
+
+fn is_direct(body: &Value) -> bool {
+    body["channel"]["type"].as_u64() == Some(DIRECT_CHANNEL)
+}
+
+/// Satori的timestamp为毫秒
+fn time_of(body: &Value) -> u64 {
+    body["timestamp"].as_u64().unwrap_or_default() / 1000
+}
+
+/// 群聊所在的群，QQ等平台的群号即guild id，没有guild时使用频道id
+fn group_of(body: &Value) -> &Value {
+    if body["guild"]["id"].is_string() {
+        &body["guild"]["id"]
+    } else {
+        &body["channel"]["id"]
+    }
+}
+
+/// Satori事件转换为kritor事件，不支持的事件返回None
+pub fn to_event(body: &Value) -> Option<Event> {
+    match body["type"].as_str()? {
+        "message-created" => to_message(body).map(Event::Message),
+        "message-deleted" | "guild-member-added" | "guild-member-removed" => {
+            to_notice(body).map(Event::Notice)
+        }
+        "friend-request" | "guild-member-request" | "guild-request" => {
+            to_request(body).map(Event::Request)
+        }
+        r#type => {
+            debug!("unsupported satori event: {}", r#type);
+            None
+        }
+    }
+}
+
+fn to_message(body: &Value) -> Option<PushMessageBody> {
+    let user = &body["user"];
+    let user_id = str_of(&user["id"]);
+    // 自己发出的消息也会推送
+    if user_id == str_of(&body["self_id"]) {
+        return None;
+    }
+    let contact = if is_direct(body) {
+        Contact {
+            scene: Scene::Friend.into(),
+            peer: user_id.clone(),
+            sub_peer: None,
+        }
+    } else {
+        // guild与频道不同时（Discord、KOOK等），频道放在sub_peer中，回复时发往该频道
+        let peer = str_of(group_of(body));
+        let channel = str_of(&body["channel"]["id"]);
+        Contact {
+            scene: Scene::Group.into(),
+            sub_peer: (!channel.is_empty() && channel != peer).then_some(channel),
+            peer,
+        }
+    };
+    let nick = body["member"]["nick"]
+        .as_str()
+        .filter(|nick| !nick.is_empty())
+        .or_else(|| user["nick"].as_str())
+        .or_else(|| user["name"].as_str())
+        .map(|nick| nick.to_string());
+    let message = &body["message"];
+    Some(PushMessageBody {
+        time: time_of(body),
+        message_id: str_of(&message["id"]),
+        contact: Some(contact),
+        sender: Some(Sender {
+            uin: user_id.parse().ok(),
+            uid: user_id,
+            nick,
+        }),
+        elements: content_to_elements(message["content"].as_str().unwrap_or_default()),
+        ..Default::default()
+    })
+}
+
+fn to_notice(body: &Value) -> Option<NoticeEvent> {
+    // 私聊撤回没有群号，只在群通知中解析
+    let group_id = || id_of(group_of(body));
+    let user = &body["user"]["id"];
+    let operator = &body["operator"]["id"];
+    let notice = match body["type"].as_str()? {
+        "message-deleted" if is_direct(body) => Notice::FriendRecall(FriendRecallNotice {
+            operator_uid: str_of(user),
+            operator_uin: id_of(user)?,
+            message_id: str_of(&body["message"]["id"]),
+            ..Default::default()
+        }),
+        "message-deleted" => Notice::GroupRecall(GroupRecallNotice {
+            group_id: group_id()?,
+            message_id: str_of(&body["message"]["id"]),
+            operator_uid: str_of(operator),
+            operator_uin: id_of(operator)?,
+            target_uid: str_of(user),
+            target_uin: id_of(user)?,
+            ..Default::default()
+        }),
+        "guild-member-added" => Notice::GroupMemberIncrease(GroupMemberIncreasedNotice {
+            group_id: group_id()?,
+            operator_uid: str_of(operator),
+            operator_uin: id_of(operator)?,
+            target_uid: str_of(user),
+            target_uin: id_of(user)?,
+            ..Default::default()
+        }),
+        "guild-member-removed" => Notice::GroupMemberDecrease(GroupMemberDecreasedNotice {
+            group_id: group_id()?,
+            operator_uid: Some(str_of(operator)),
+            operator_uin: Some(id_of(operator)?),
+            target_uid: Some(str_of(user)),
+            target_uin: Some(id_of(user)?),
+            ..Default::default()
+        }),
+        _ => return None,
+    };
+    Some(NoticeEvent {
+        time: time_of(body),
+        notice: Some(notice),
+        ..Default::default()
+    })
+}
+
+fn to_request(body: &Value) -> Option<RequestEvent> {
+    let user = &body["user"]["id"];
+    // Satori以请求事件中的message id处理请求
+    let flag = str_of(&body["message"]["id"]);
+    let reason = str_of(&body["message"]["content"]);
+    let request = match body["type"].as_str()? {
+        "friend-request" => Request::FriendApply(FriendApplyRequest {
+            applier_uid: str_of(user),
+            applier_uin: id_of(user)?,
+            flag: flag.clone(),
+            message: reason,
+        }),
+        "guild-member-request" => Request::GroupApply(GroupApplyRequest {
+            group_id: id_of(&body["guild"]["id"])?,
+            applier_uid: str_of(user),
+            applier_uin: id_of(user)?,
+            reason,
+            flag: flag.clone(),
+            ..Default::default()
+        }),
+        "guild-request" => Request::InvitedGroup(InvitedJoinGroupRequest {
+            group_id: id_of(&body["guild"]["id"])?,
+            inviter_uid: str_of(user),
+            inviter_uin: id_of(user)?,
+            flag: flag.clone(),
+        }),
+        _ => return None,
+    };
+    Some(RequestEvent {
+        time: time_of(body),
+        request_id: flag,
+        request: Some(request),
+        ..Default::default()
+    })
+}
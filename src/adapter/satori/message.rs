@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{debug, warn};
+
+use crate::kritor::server::kritor_proto::common::element::{Data, ElementType};
+use crate::kritor::server::kritor_proto::common::{
+    image_element, video_element, voice_element, AtElement, Element, FaceElement, ImageElement,
+    ReplyElement, TextElement, VideoElement, VoiceElement,
+};
+
+/// Satori消息元素树的节点
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Text(String),
+    Element {
+        name: String,
+        attrs: HashMap<String, String>,
+        children: Vec<Node>,
+    },
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn read_name(&mut self) -> String {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=')
+            .unwrap_or(rest.len());
+        self.pos += end;
+        rest[..end].to_string()
+    }
+
+    fn read_attrs(&mut self) -> HashMap<String, String> {
+        let mut attrs = HashMap::new();
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.is_empty() || rest.starts_with('/') || rest.starts_with('>') {
+                return attrs;
+            }
+            let key = self.read_name();
+            if key.is_empty() {
+                // 非法字符，跳过
+                self.pos += rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+                continue;
+            }
+            if !self.rest().starts_with('=') {
+                // 布尔属性
+                attrs.insert(key, "true".to_string());
+                continue;
+            }
+            self.pos += 1;
+            let rest = self.rest();
+            let value = match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = rest[1..].find(quote).map(|i| i + 1).unwrap_or(rest.len());
+                    self.pos += (end + 1).min(rest.len());
+                    unescape(&rest[1..end])
+                }
+                _ => unescape(&self.read_name()),
+            };
+            attrs.insert(key, value);
+        }
+    }
+
+    /// 解析到结束标签closing（或输入末尾）为止
+    fn parse_nodes(&mut self, closing: Option<&str>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        while !self.rest().is_empty() {
+            let rest = self.rest();
+            let Some(start) = rest.find('<') else {
+                nodes.push(Node::Text(unescape(rest)));
+                self.pos = self.input.len();
+                break;
+            };
+            if start > 0 {
+                nodes.push(Node::Text(unescape(&rest[..start])));
+                self.pos += start;
+                continue;
+            }
+            if let Some(tail) = rest.strip_prefix("</") {
+                let end = tail.find('>').map(|i| i + 3).unwrap_or(rest.len());
+                let name = tail[..end.saturating_sub(3)].trim();
+                self.pos += end;
+                if closing == Some(name) {
+                    return nodes;
+                }
+                // 不匹配的结束标签直接忽略
+                continue;
+            }
+            self.pos += 1;
+            let name = self.read_name();
+            if name.is_empty() {
+                nodes.push(Node::Text("<".to_string()));
+                continue;
+            }
+            let attrs = self.read_attrs();
+            let self_closing = self.rest().starts_with('/');
+            if let Some(end) = self.rest().find('>') {
+                self.pos += end + 1;
+            } else {
+                self.pos = self.input.len();
+            }
+            let children = if self_closing {
+                vec![]
+            } else {
+                self.parse_nodes(Some(&name))
+            };
+            nodes.push(Node::Element {
+                name,
+                attrs,
+                children,
+            });
+        }
+        nodes
+    }
+}
+
+/// 解析Satori消息内容为元素树
+pub fn parse(content: &str) -> Vec<Node> {
+    Parser {
+        input: content,
+        pos: 0,
+    }
+    .parse_nodes(None)
+}
+
+fn element(r#type: ElementType, data: Data) -> Element {
+    Element {
+        r#type: r#type.into(),
+        data: Some(data),
+    }
+}
+
+fn push_text(elements: &mut Vec<Element>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    // 合并相邻的文本
+    if let Some(Element {
+        data: Some(Data::Text(last)),
+        ..
+    }) = elements.last_mut()
+    {
+        last.text.push_str(text);
+        return;
+    }
+    elements.push(element(
+        ElementType::Text,
+        Data::Text(TextElement {
+            text: text.to_string(),
+        }),
+    ));
+}
+
+fn collect(nodes: &[Node], elements: &mut Vec<Element>) {
+    for node in nodes {
+        let (name, attrs, children) = match node {
+            Node::Text(text) => {
+                push_text(elements, text);
+                continue;
+            }
+            Node::Element {
+                name,
+                attrs,
+                children,
+            } => (name.as_str(), attrs, children),
+        };
+        let attr = |key: &str| attrs.get(key).cloned().unwrap_or_default();
+        match name {
+            "at" => {
+                let id = if attrs.get("type").map(|t| t.as_str()) == Some("all") {
+                    "all".to_string()
+                } else {
+                    attr("id")
+                };
+                elements.push(element(
+                    ElementType::At,
+                    Data::At(AtElement {
+                        uin: id.parse().ok(),
+                        uid: id,
+                    }),
+                ));
+            }
+            "sharp" => push_text(elements, &format!("#{}", attr("name"))),
+            "a" => {
+                collect(children, elements);
+                if children.is_empty() {
+                    push_text(elements, &attr("href"));
+                }
+            }
+            "img" | "image" => elements.push(element(
+                ElementType::Image,
+                Data::Image(ImageElement {
+                    file_md5: None,
+                    sub_type: None,
+                    r#type: None,
+                    data: Some(image_element::Data::FileUrl(attr("src"))),
+                }),
+            )),
+            "audio" => elements.push(element(
+                ElementType::Voice,
+                Data::Voice(VoiceElement {
+                    data: Some(voice_element::Data::FileUrl(attr("src"))),
+                    ..Default::default()
+                }),
+            )),
+            "video" => elements.push(element(
+                ElementType::Video,
+                Data::Video(VideoElement {
+                    data: Some(video_element::Data::FileUrl(attr("src"))),
+                    ..Default::default()
+                }),
+            )),
+            // 带id的quote是引用回复，内容只是被引用消息的预览
+            "quote" => elements.push(element(
+                ElementType::Reply,
+                Data::Reply(ReplyElement {
+                    message_id: attr("id"),
+                }),
+            )),
+            "face" => elements.push(element(
+                ElementType::Face,
+                Data::Face(FaceElement {
+                    id: attr("id").parse().unwrap_or_default(),
+                    ..Default::default()
+                }),
+            )),
+            "br" => push_text(elements, "\n"),
+            "p" => {
+                collect(children, elements);
+                push_text(elements, "\n");
+            }
+            // 修饰元素与message等容器只保留内容
+            "b" | "strong" | "i" | "em" | "u" | "ins" | "s" | "del" | "spl" | "code" | "sup"
+            | "sub" | "message" | "author" | "template" => collect(children, elements),
+            _ => {
+                debug!("unsupported satori element: {}", name);
+                collect(children, elements);
+            }
+        }
+    }
+}
+
+/// Satori消息内容转换为kritor的Element
+pub fn content_to_elements(content: &str) -> Vec<Element> {
+    let mut elements = Vec::new();
+    collect(&parse(content), &mut elements);
+    elements
+}
+
+fn data_url(bytes: &[u8], mime: &str) -> String {
+    format!("data:{};base64,{}", mime, STANDARD.encode(bytes))
+}
+
+fn file_url(path: &str) -> String {
+    format!("file:///{}", path.trim_start_matches('/'))
+}
+
+fn media(name: &str, src: Option<String>) -> Option<String> {
+    src.map(|src| format!("<{} src=\"{}\"/>", name, escape(&src)))
+}
+
+/// kritor的Element转换为Satori消息内容
+pub fn elements_to_content(elements: &[Element]) -> String {
+    let mut content = String::new();
+    for element in elements {
+        let Some(data) = element.data.clone() else {
+            continue;
+        };
+        let part = match data {
+            Data::Text(text) => Some(escape(&text.text)),
+            Data::At(at) => {
+                if at.uid == "all" || at.uin == Some(0) {
+                    Some("<at type=\"all\"/>".to_string())
+                } else {
+                    let id = if at.uid.is_empty() {
+                        at.uin.unwrap_or_default().to_string()
+                    } else {
+                        at.uid
+                    };
+                    Some(format!("<at id=\"{}\"/>", escape(&id)))
+                }
+            }
+            Data::Reply(reply) => Some(format!("<quote id=\"{}\"/>", escape(&reply.message_id))),
+            Data::Face(face) => Some(format!("<face id=\"{}\"/>", face.id)),
+            Data::Image(image) => media(
+                "img",
+                image.data.and_then(|data| match data {
+                    image_element::Data::File(bytes) => Some(data_url(&bytes, "image/png")),
+                    image_element::Data::FileUrl(url) => Some(url),
+                    image_element::Data::FilePath(path) => Some(file_url(&path)),
+                    image_element::Data::FileName(_) => None,
+                }),
+            ),
+            Data::Voice(voice) => media(
+                "audio",
+                voice.data.and_then(|data| match data {
+                    voice_element::Data::File(bytes) => Some(data_url(&bytes, "audio/amr")),
+                    voice_element::Data::FileUrl(url) => Some(url),
+                    voice_element::Data::FilePath(path) => Some(file_url(&path)),
+                    voice_element::Data::FileName(_) => None,
+                }),
+            ),
+            Data::Video(video) => media(
+                "video",
+                video.data.and_then(|data| match data {
+                    video_element::Data::File(bytes) => Some(data_url(&bytes, "video/mp4")),
+                    video_element::Data::FileUrl(url) => Some(url),
+                    video_element::Data::FilePath(path) => Some(file_url(&path)),
+                    video_element::Data::FileName(_) => None,
+                }),
+            ),
+            _ => None,
+        };
+        match part {
+            Some(part) => content.push_str(&part),
+            None => warn!(
+                "element {:?} is not supported by satori, skipped",
+                ElementType::try_from(element.r#type)
+            ),
+        }
+    }
+    content
+}
@@ -0,0 +1,282 @@
+pub mod action;
+pub mod event;
+pub mod message;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::{SinkExt, StreamExt};
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use crate::adapter::{attach_bot, RequestHandler};
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::common;
+use crate::model::config::SatoriConfig;
+use crate::model::error::Result;
+use crate::{client_err, err, network_err};
+
+/// Satori信令
+const OP_EVENT: u64 = 0;
+const OP_PING: u64 = 1;
+const OP_IDENTIFY: u64 = 3;
+const OP_READY: u64 = 4;
+
+/// 心跳间隔，协议要求每10秒发送一次PING
+const PING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Satori中的一个登录账号，通过HTTP API调用
+pub struct SatoriClient {
+    http: reqwest::Client,
+    address: String,
+    token: Option<String>,
+    pub platform: String,
+    pub self_id: String,
+    /// 私聊用户id到私聊频道id
+    channels: DashMap<String, String>,
+}
+
+impl SatoriClient {
+    pub fn new(address: String, token: Option<String>, platform: String, self_id: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            address,
+            token,
+            platform,
+            self_id,
+            channels: DashMap::new(),
+        }
+    }
+
+    /// 调用Satori API，如 message.create
+    pub async fn call(&self, method: &str, body: Value) -> Result<Value> {
+        let url = format!("{}/{}", self.address.trim_end_matches('/'), method);
+        debug!("Satori api: {} {}", url, body);
+        let mut request = self
+            .http
+            .post(url)
+            .header("Satori-Platform", &self.platform)
+            .header("Satori-User-ID", &self.self_id)
+            // 兼容旧版本
+            .header("X-Platform", &self.platform)
+            .header("X-Self-ID", &self.self_id)
+            .json(&body);
+        if let Some(token) = self.token.as_ref() {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return client_err!("api {} failed with {}: {}", method, status, text);
+        }
+        if text.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// 调用分页API并合并全部数据
+    pub async fn list(&self, method: &str, mut body: Value) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        loop {
+            let page = self.call(method, body.clone()).await?;
+            if let Some(data) = page["data"].as_array() {
+                items.extend(data.iter().cloned());
+            }
+            match page["next"].as_str() {
+                Some(next) => body["next"] = Value::from(next),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    /// 取得与用户的私聊频道
+    pub async fn direct_channel(&self, user_id: &str) -> Result<String> {
+        if let Some(channel_id) = self.channels.get(user_id) {
+            return Ok(channel_id.clone());
+        }
+        let channel = self
+            .call("user.channel.create", json!({ "user_id": user_id }))
+            .await?;
+        let Some(channel_id) = channel["id"].as_str() else {
+            return client_err!("failed to create direct channel for {}", user_id);
+        };
+        self.channels
+            .insert(user_id.to_string(), channel_id.to_string());
+        Ok(channel_id.to_string())
+    }
+
+    /// 记录私聊消息所在的频道
+    fn remember_channel(&self, body: &Value) {
+        if body["channel"]["type"].as_u64() != Some(1) {
+            return;
+        }
+        if let (Some(user_id), Some(channel_id)) =
+            (body["user"]["id"].as_str(), body["channel"]["id"].as_str())
+        {
+            self.channels
+                .insert(user_id.to_string(), channel_id.to_string());
+        }
+    }
+}
+
+#[async_trait]
+impl RequestHandler for SatoriClient {
+    async fn handle(&self, request: &common::Request) -> Result<Vec<u8>> {
+        action::handle(self, request).await
+    }
+}
+
+/// 由API地址得到事件推送地址
+fn events_url(address: &str) -> Result<String> {
+    let address = address.trim_end_matches('/');
+    if let Some(rest) = address.strip_prefix("https://") {
+        Ok(format!("wss://{}/events", rest))
+    } else if let Some(rest) = address.strip_prefix("http://") {
+        Ok(format!("ws://{}/events", rest))
+    } else {
+        err!("invalid satori address: {}", address)
+    }
+}
+
+struct Login {
+    client: Arc<SatoriClient>,
+    bot: Arc<RwLock<Bot>>,
+    connection: u64,
+    forward: JoinHandle<()>,
+}
+
+/// 连接一次Satori，直到连接断开才返回，sequence用于断线后补发事件
+async fn run_session(config: &SatoriConfig, sequence: &mut Option<u64>) -> Result<()> {
+    let (ws, _) = connect_async(events_url(&config.address)?).await?;
+    info!("Connected to satori: {}", config.address);
+    let (mut sink, mut stream) = ws.split();
+    let identify = json!({
+        "op": OP_IDENTIFY,
+        "body": { "token": config.token, "sequence": sequence },
+    });
+    sink.send(WsMessage::Text(identify.to_string())).await?;
+
+    let ready = loop {
+        match stream.next().await {
+            Some(Ok(WsMessage::Text(text))) => {
+                let frame: Value = serde_json::from_str(&text)?;
+                if frame["op"].as_u64() == Some(OP_READY) {
+                    break frame;
+                }
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => return network_err!("satori closed before ready"),
+        }
+    };
+
+    // 一个Satori连接可以包含多个账号，每个账号对应一个Bot
+    let mut logins = HashMap::new();
+    for login in ready["body"]["logins"].as_array().cloned().unwrap_or_default() {
+        let self_id = login["self_id"]
+            .as_str()
+            .or_else(|| login["user"]["id"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let platform = login["platform"].as_str().unwrap_or_default().to_string();
+        if self_id.is_empty() {
+            continue;
+        }
+        info!("Satori account connected: {} ({})", self_id, platform);
+        let client = Arc::new(SatoriClient::new(
+            config.address.clone(),
+            config.token.clone(),
+            platform.clone(),
+            self_id.clone(),
+        ));
+        // 无法解析为数字的账号没有uin，Bot以uid区分
+        let uin = self_id.parse().unwrap_or_else(|_| {
+            warn!("Satori account {} is not numeric, uin is left as 0", self_id);
+            0
+        });
+        let (bot, connection, forward) = attach_bot(
+            uin,
+            self_id.clone(),
+            Some(format!("satori {}", platform)),
+            client.clone(),
+        )
+        .await;
+        logins.insert(
+            self_id,
+            Login {
+                client,
+                bot,
+                connection,
+                forward,
+            },
+        );
+    }
+
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            _ = ping.tick() => {
+                let frame = json!({ "op": OP_PING });
+                if let Err(e) = sink.send(WsMessage::Text(frame.to_string())).await {
+                    break Err(e.into());
+                }
+            }
+            message = stream.next() => {
+                let text = match message {
+                    Some(Ok(WsMessage::Text(text))) => text,
+                    Some(Ok(WsMessage::Close(_))) | None => break network_err!("satori connection closed"),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => break Err(e.into()),
+                };
+                let frame = match serde_json::from_str::<Value>(&text) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        warn!("Invalid satori frame: {}", e);
+                        continue;
+                    }
+                };
+                if frame["op"].as_u64() != Some(OP_EVENT) {
+                    continue;
+                }
+                let body = &frame["body"];
+                if let Some(id) = body["id"].as_u64() {
+                    sequence.replace(id);
+                }
+                let Some(login) = body["self_id"].as_str().and_then(|id| logins.get(id)) else {
+                    debug!("satori event for unknown login: {}", body);
+                    continue;
+                };
+                login.client.remember_channel(body);
+                if let Some(event) = event::to_event(body) {
+                    Bot::push_event(&login.bot, event).await;
+                }
+            }
+        }
+    };
+    for (_, login) in logins {
+        login.forward.abort();
+        Bot::disconnect(login.bot, login.connection).await;
+    }
+    result
+}
+
+/// 连接配置中的Satori，断开后按间隔重连
+pub async fn connect_satori(config: SatoriConfig) {
+    let interval = Duration::from_secs(config.reconnect_interval.unwrap_or(5));
+    let mut sequence = None;
+    loop {
+        if let Err(e) = run_session(&config, &mut sequence).await {
+            error!("Satori connection to {} lost: {}", config.address, e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,8 @@
+//! 由build.rs根据kritor的service定义生成，proto中的每个rpc都对应Bot上的一个方法
+//!
+//! 如 `CoreService.GetVersion` 对应 `bot.core_get_version(GetVersionRequest {})`
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+
+include!(concat!(env!("OUT_DIR"), "/kritor_api.rs"));
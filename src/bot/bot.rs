@@ -1,34 +1,119 @@
+use crate::bot::cache::spawn_resync;
+use crate::bot::channel::{EventChannel, EventReceiver};
 use crate::bot::core::CoreAPITrait;
-use crate::bot::friend::{Friend, FriendAPITrait};
-use crate::bot::group::{Group, GroupAPITrait};
+use crate::bot::friend::Friend;
+use crate::bot::group::Group;
+use crate::bot::limiter::SendScheduler;
+use crate::bot::web::WebCache;
+use crate::kritor::record::{record_event, record_request, record_response};
 use crate::kritor::server::kritor_proto::common::{Contact, Element};
+use crate::kritor::server::kritor_proto::event_structure::Event;
 use crate::kritor::server::kritor_proto::*;
-use crate::service::service::KritorContext;
+use crate::service::register::dispatch_lifecycle;
+use crate::service::service::{KritorContext, LifecycleEvent};
 use crate::utils::kritor::same_contact_and_sender;
-use crate::{err, kritor_err};
-use bytes::Bytes;
+use crate::model::config::{get_config, EventConfig};
+use crate::model::error::Error;
+use crate::{err, network_err};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use futures::channel::oneshot;
-use futures::stream::FuturesUnordered;
-use futures::StreamExt;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use prost::Message;
-use rand::Rng;
 use std::collections::HashMap;
-use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc::Sender;
-use tokio::sync::{broadcast, RwLock, Semaphore};
+use tokio::sync::RwLock;
 use tonic::Status;
 
+/// Bot与Kritor端的连接状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotState {
+    /// 连接已建立，正在初始化
+    Connecting,
+    Online,
+    Offline,
+}
+
+/// 等待响应的请求
+#[derive(Debug)]
+pub struct PendingRequest {
+    pub cmd: String,
+    pub sent_at: Instant,
+    // 发送时的连接编号
+    connection: u64,
+    tx: oneshot::Sender<common::Response>,
+}
+
+/// 每个cmd的请求统计
+#[derive(Debug, Clone, Default)]
+pub struct RequestStats {
+    /// 正在等待响应的数量
+    pub pending: u64,
+    pub total: u64,
+    pub failed: u64,
+    pub timeout: u64,
+    /// 连接断开或调用方放弃而没有等到响应的数量
+    pub cancelled: u64,
+    /// 收到响应的数量
+    pub responded: u64,
+    /// 找不到对应请求的响应数量，通常是已超时或已取消的请求的迟到响应
+    pub unmatched: u64,
+    /// 收到响应的请求的总耗时与最大耗时，单位毫秒
+    pub total_latency: u64,
+    pub max_latency: u64,
+}
+
+impl RequestStats {
+    /// 平均耗时，单位毫秒
+    pub fn avg_latency(&self) -> u64 {
+        if self.responded == 0 {
+            0
+        } else {
+            self.total_latency / self.responded
+        }
+    }
+}
+
+/// 请求结束（响应、超时或被取消）时从队列移除，仍在队列中说明请求被取消
+struct PendingGuard<'a> {
+    bot: &'a Bot,
+    seq: u32,
+    cmd: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        let cancelled = self.bot.request_queue.remove(&self.seq).is_some();
+        if let Some(mut stats) = self.bot.request_stats.get_mut(&self.cmd) {
+            stats.pending = stats.pending.saturating_sub(1);
+            if cancelled {
+                stats.cancelled += 1;
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Bot {
-    message_sender: broadcast::Sender<common::PushMessageBody>,
-    notice_sender: broadcast::Sender<NoticeEvent>,
-    request_sender: broadcast::Sender<RequestEvent>,
-    request_queue: Arc<DashMap<u32, oneshot::Sender<common::Response>>>,
-    response_listener: Arc<Option<Sender<Result<common::Request, Status>>>>,
+    // 推送事件时先取出队列再等待消费者，不持有Bot的锁
+    message_channel: Arc<EventChannel<common::PushMessageBody>>,
+    notice_channel: Arc<EventChannel<NoticeEvent>>,
+    request_channel: Arc<EventChannel<RequestEvent>>,
+    request_queue: Arc<DashMap<u32, PendingRequest>>,
+    // 单调递增的请求序号
+    seq: AtomicU32,
+    request_stats: Arc<DashMap<String, RequestStats>>,
+    response_listener: Arc<RwLock<Option<Sender<Result<common::Request, Status>>>>>,
+    // 当前连接的编号，每次重连加一，与response_listener一起修改
+    connection: AtomicU64,
+    state: Arc<RwLock<BotState>>,
+    // 群、好友列表是否已经加载过，重连时不再重复加载
+    initialized: AtomicBool,
+    // 是否录制事件与请求，回放的Bot不录制
+    recording: AtomicBool,
     uin: Option<u64>,
     uid: Option<String>,
     nickname: Arc<RwLock<Option<String>>>,
@@ -45,25 +130,46 @@ pub struct Bot {
     // context transaction lock, when it exists for a contact, message won't be sent to handlers for the contact
     // 对于每个contact是唯一的，也就是每个人同时最多只能进行一个trans
     transaction_contexts: Arc<RwLock<Vec<(KritorContext, Contact, common::Sender)>>>,
+    // 网页接口的cookie、bkn缓存
+    web_cache: Arc<WebCache>,
+    // 发送消息的限速队列
+    send_scheduler: Arc<SendScheduler>,
 }
 
 impl Bot {
     pub fn new(
         uin: u64,
         uid: String,
-        tx_mutex: Arc<Option<Sender<Result<common::Request, Status>>>>,
+        tx: Option<Sender<Result<common::Request, Status>>>,
         version: Option<String>,
+        event: &EventConfig,
     ) -> Self {
         info!("Bot is created: uin: {}, uid: {}", uin, uid);
-        let (msender, _mreceiver) = broadcast::channel(100);
-        let (nsender, _nreceiver) = broadcast::channel(100);
-        let (rsender, _rreceiver) = broadcast::channel(100);
+        let backpressure = event.backpressure();
         Self {
-            message_sender: msender,
-            notice_sender: nsender,
-            request_sender: rsender,
+            message_channel: Arc::new(EventChannel::new(
+                "message",
+                event.message_capacity(),
+                backpressure,
+            )),
+            notice_channel: Arc::new(EventChannel::new(
+                "notice",
+                event.notice_capacity(),
+                backpressure,
+            )),
+            request_channel: Arc::new(EventChannel::new(
+                "request",
+                event.request_capacity(),
+                backpressure,
+            )),
             request_queue: Arc::new(DashMap::new()),
-            response_listener: tx_mutex,
+            seq: AtomicU32::new(1),
+            request_stats: Arc::new(DashMap::new()),
+            response_listener: Arc::new(RwLock::new(tx)),
+            connection: AtomicU64::new(1),
+            state: Arc::new(RwLock::new(BotState::Connecting)),
+            initialized: AtomicBool::new(false),
+            recording: AtomicBool::new(true),
             uid: Some(uid),
             nickname: Arc::new(RwLock::new(None)),
             groups: Arc::new(RwLock::new(Some(HashMap::new()))),
@@ -81,13 +187,188 @@ impl Bot {
             receive: AtomicI32::new(0),
 
             transaction_contexts: Arc::new(RwLock::new(vec![])),
+            web_cache: Arc::new(WebCache::default()),
+            send_scheduler: Arc::new(SendScheduler::default()),
         }
     }
 
-    pub fn get_request_queue(&self) -> &Arc<DashMap<u32, oneshot::Sender<common::Response>>> {
+    /// 关闭该Bot的录制，需要在Bot初始化之前调用
+    pub fn disable_recording(&self) {
+        self.recording.store(false, Ordering::SeqCst);
+    }
+
+    fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::SeqCst)
+    }
+
+    pub fn get_web_cache(&self) -> &Arc<WebCache> {
+        &self.web_cache
+    }
+
+    pub fn get_request_queue(&self) -> &Arc<DashMap<u32, PendingRequest>> {
         &self.request_queue
     }
 
+    /// 各cmd的请求统计，按cmd排序
+    pub fn get_request_stats(&self) -> Vec<(String, RequestStats)> {
+        let mut stats: Vec<(String, RequestStats)> = self
+            .request_stats
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+
+    /// 下一个请求序号，跳过0和仍在等待响应的序号
+    pub fn next_seq(&self) -> u32 {
+        loop {
+            let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+            if seq != 0 && !self.request_queue.contains_key(&seq) {
+                return seq;
+            }
+        }
+    }
+
+    pub async fn get_state(&self) -> BotState {
+        *self.state.read().await
+    }
+
+    /// 当前连接的编号，断开时用于判断是否仍是当前连接
+    pub fn current_connection(&self) -> u64 {
+        self.connection.load(Ordering::SeqCst)
+    }
+
+    /// 同一账号重新连接时替换发送端，群、好友缓存与插件状态保持不变。返回新连接的编号
+    pub async fn reconnect(&self, tx: Sender<Result<common::Request, Status>>) -> u64 {
+        let connection = {
+            let mut listener = self.response_listener.write().await;
+            listener.replace(tx);
+            *self.state.write().await = BotState::Connecting;
+            self.connection.fetch_add(1, Ordering::SeqCst) + 1
+        };
+        info!(
+            "Bot reconnected: uin: {}, uid: {}",
+            self.uin.unwrap_or_default(),
+            self.uid.clone().unwrap_or_default()
+        );
+        connection
+    }
+
+    /// 连接断开，让该连接上等待中的请求立即失败
+    ///
+    /// 旧连接在重连之后才结束时不影响新连接，只有当前连接断开时才标记为离线
+    pub async fn disconnect(self_arc: Arc<RwLock<Self>>, connection: u64) {
+        {
+            let self_guard = self_arc.read().await;
+            let current = {
+                let mut listener = self_guard.response_listener.write().await;
+                let current = self_guard.current_connection() == connection;
+                if current {
+                    listener.take();
+                    *self_guard.state.write().await = BotState::Offline;
+                }
+                current
+            };
+            let pending = self_guard.request_queue.len();
+            // 丢弃oneshot的发送端，等待中的请求会立即收到错误
+            self_guard
+                .request_queue
+                .retain(|_, request| request.connection != connection);
+            let failed = pending - self_guard.request_queue.len();
+            if !current {
+                debug!(
+                    "Stale connection {} of bot {} ended, {} pending requests failed",
+                    connection,
+                    self_guard.uid.clone().unwrap_or_default(),
+                    failed
+                );
+                return;
+            }
+            info!(
+                "Bot is offline: uid: {}, {} pending requests failed",
+                self_guard.uid.clone().unwrap_or_default(),
+                failed
+            );
+        }
+        dispatch_lifecycle(self_arc, LifecycleEvent::Disconnected).await;
+    }
+
+    async fn set_online(self_arc: Arc<RwLock<Self>>) {
+        {
+            let self_guard = self_arc.read().await;
+            *self_guard.state.write().await = BotState::Online;
+        }
+        dispatch_lifecycle(self_arc, LifecycleEvent::Connected).await;
+    }
+
+    /// 将收到的事件推送到对应的队列中，与事件的来源（被动、主动或其他协议）无关
+    ///
+    /// 开启backpressure时会等待消费者，等待期间不持有Bot的锁
+    pub async fn push_event(self_arc: &Arc<RwLock<Self>>, event: Event) {
+        let (message_channel, notice_channel, request_channel) = {
+            let self_guard = self_arc.read().await;
+            if self_guard.is_recording() {
+                record_event(
+                    self_guard.uin.unwrap_or_default(),
+                    self_guard.uid.as_deref().unwrap_or_default(),
+                    &event,
+                );
+            }
+            (
+                self_guard.message_channel.clone(),
+                self_guard.notice_channel.clone(),
+                self_guard.request_channel.clone(),
+            )
+        };
+        match event {
+            Event::Message(message) => {
+                message_channel.send(message).await;
+            }
+            Event::Notice(notice) => {
+                notice_channel.send(notice).await;
+            }
+            Event::Request(request) => {
+                request_channel.send(request).await;
+            }
+        }
+    }
+
+    /// 将响应交给等待中的请求
+    pub fn handle_response(&self, response: common::Response) {
+        if self.is_recording() {
+            record_response(
+                self.uin.unwrap_or_default(),
+                self.uid.as_deref().unwrap_or_default(),
+                &response,
+            );
+        }
+        if let Some((_, pending)) = self.request_queue.remove(&response.seq) {
+            let latency = pending.sent_at.elapsed().as_millis() as u64;
+            if let Some(mut stats) = self.request_stats.get_mut(&pending.cmd) {
+                stats.responded += 1;
+                stats.total_latency += latency;
+                stats.max_latency = stats.max_latency.max(latency);
+                if response.code != 0 {
+                    stats.failed += 1;
+                }
+            }
+            if pending.tx.send(response).is_err() {
+                debug!("request already dropped");
+            }
+        } else {
+            // 已超时或被取消的请求也会走到这里
+            warn!(
+                "unknown or expired response: cmd: {}, seq: {}",
+                response.cmd, response.seq
+            );
+            self.request_stats
+                .entry(response.cmd.clone())
+                .or_default()
+                .unmatched += 1;
+        }
+    }
+
     pub async fn init(self_arc: Arc<RwLock<Self>>) {
         {
             let self_guard = self_arc.read().await;
@@ -99,127 +380,75 @@ impl Bot {
                 .write()
                 .await
                 .replace("Android QQ".to_string());
-            let current = self_guard
-                .get_current_account()
-                .await
-                .expect("Failed to get nickname");
-            self_guard
-                .nickname
-                .write()
-                .await
-                .replace(current.account_name.clone());
-            info!("Welcome Nickname: {}", current.account_name);
+            match self_guard.get_current_account().await {
+                Ok(current) => {
+                    self_guard
+                        .nickname
+                        .write()
+                        .await
+                        .replace(current.account_name.clone());
+                    info!("Welcome Nickname: {}", current.account_name);
+                }
+                Err(err) => error!("Failed to get nickname: {:?}", err.error()),
+            }
         }
 
-        let self_guard = self_arc.read().await;
-        info!("Start to get friends list");
-        let result = self_guard.get_friend_list(true).await;
-        drop(self_guard);
-        match result {
-            Ok(friends_response) => {
-                let info = friends_response.friends_info.clone();
-                let friend_num = info.len();
-                info!("Friends count: {}", friend_num);
-                let friends = info
-                    .into_iter()
-                    .map(|info| {
-                        let friend = Friend::new(info);
-                        (friend.inner.uin, friend)
-                    })
-                    .collect::<HashMap<u64, Friend>>();
-                let self_guard = self_arc.write().await;
-                let mut final_friends = self_guard.friends.write().await;
-                final_friends
-                    .get_or_insert_with(HashMap::new)
-                    .extend(friends);
-            }
-            Err(err) => {
-                error!("Failed to initialize friends: {:?}", err.error());
-            }
+        if self_arc.read().await.initialized.swap(true, Ordering::SeqCst) {
+            info!("Bot reconnected, cached groups and friends are kept");
+            // 断线期间可能漏掉了通知，在后台重新同步
+            let bot = self_arc.clone();
+            tokio::spawn(async move {
+                bot.read().await.resync().await;
+            });
+            Bot::set_online(self_arc).await;
+            return;
         }
 
-        let self_guard = self_arc.read().await;
-        let max_concurrent = 20;
-        info!("Start to get group list");
-        let result = self_guard.get_group_list(true).await;
-        drop(self_guard);
-        match result {
-            Ok(groups) => {
-                debug!("group list: {:?}", groups);
-                let semaphore = Arc::new(Semaphore::new(max_concurrent));
-                debug!("Start to get group member list");
-                let tasks = groups
-                    .groups_info
-                    .clone()
-                    .into_iter()
-                    .map(|group_info| {
-                        let semaphore_clone = semaphore.clone();
-                        let bot_clone = self_arc.clone();
-                        async move {
-                            let _permit = semaphore_clone
-                                .acquire_owned()
-                                .await
-                                .expect("Failed to acquire semaphore");
-                            let self_guard = bot_clone.read().await;
-                            let group_members_info = &self_guard
-                                .get_group_member_list(group_info.group_id, true)
-                                .await
-                                .expect("Failed to get group member list")
-                                .group_members_info;
-                            debug!("group member list: {:?}", group_info.group_id);
-                            (group_info.group_id, group_members_info.clone())
-                        }
-                    })
-                    .collect::<FuturesUnordered<_>>(); // 使用 FuturesUnordered 来处理并发
-                let results: HashMap<u64, Vec<GroupMemberInfo>> = tasks.collect().await;
-
-                debug!("prepare to update bot");
-                let self_guard = self_arc.write().await;
-                let mut final_groups = self_guard.groups.write().await;
-                for group_info in &groups.groups_info {
-                    let group_id = group_info.group_id;
-                    let group_members_info = results
-                        .get(&group_id)
-                        .expect("Failed to get group members info");
-                    let group_members_map = group_members_info
-                        .iter()
-                        .map(|member_info| (member_info.uin, member_info.clone()))
-                        .collect::<HashMap<u64, GroupMemberInfo>>();
-                    let group = Group::new(group_info.clone(), group_members_map);
-                    final_groups
-                        .get_or_insert_with(HashMap::new)
-                        .insert(group_id, group);
-                }
+        {
+            // 先使用本地缓存，只请求群、好友列表，成员列表在后台加载
+            let self_guard = self_arc.read().await;
+            self_guard.load_cache().await;
+            info!("Start to get friends list");
+            if let Err(err) = self_guard.refresh_friends().await {
+                error!("Failed to initialize friends: {:?}", err.error());
             }
-            Err(err) => {
+            info!("Start to get group list");
+            if let Err(err) = self_guard.refresh_groups().await {
                 error!("Failed to initialize groups: {:?}", err.error());
             }
-        };
+        }
 
         info!("Bot initialized");
+        let bot = self_arc.clone();
+        tokio::spawn(async move {
+            bot.read().await.load_stale_members().await;
+        });
+        spawn_resync(self_arc.clone());
+        Bot::set_online(self_arc).await;
     }
-    pub fn get_message_sender(&self) -> &broadcast::Sender<common::PushMessageBody> {
-        &self.message_sender
+    pub fn subscribe_message(&self) -> EventReceiver<common::PushMessageBody> {
+        self.message_channel.subscribe()
     }
 
-    pub fn subscribe_message(&self) -> broadcast::Receiver<common::PushMessageBody> {
-        self.message_sender.subscribe()
+    pub fn subscribe_notice(&self) -> EventReceiver<NoticeEvent> {
+        self.notice_channel.subscribe()
     }
 
-    pub fn get_notice_sender(&self) -> &broadcast::Sender<NoticeEvent> {
-        &self.notice_sender
+    pub fn subscribe_request(&self) -> EventReceiver<RequestEvent> {
+        self.request_channel.subscribe()
     }
 
-    pub fn subscribe_notice(&self) -> broadcast::Receiver<NoticeEvent> {
-        self.notice_sender.subscribe()
+    /// 外部订阅者使用，跟不上时丢弃事件而不是阻塞事件流
+    pub fn subscribe_message_lossy(&self) -> EventReceiver<common::PushMessageBody> {
+        self.message_channel.subscribe_lossy()
     }
 
-    pub fn get_request_sender(&self) -> &broadcast::Sender<RequestEvent> {
-        &self.request_sender
+    pub fn subscribe_notice_lossy(&self) -> EventReceiver<NoticeEvent> {
+        self.notice_channel.subscribe_lossy()
     }
 
-    pub fn subscribe_request(&self) -> broadcast::Receiver<RequestEvent> {
-        self.request_sender.subscribe()
+    pub fn subscribe_request_lossy(&self) -> EventReceiver<RequestEvent> {
+        self.request_channel.subscribe_lossy()
     }
 
     pub fn get_kritor_version(&self) -> Option<String> {
@@ -250,7 +479,7 @@ impl Bot {
     }
 
     pub fn get_sent(&self) -> i32 {
-        self.sent.load(std::sync::atomic::Ordering::Relaxed)
+        self.sent.load(Ordering::Relaxed)
     }
 
     pub fn plus_one_sent(&self) {
@@ -258,11 +487,10 @@ impl Bot {
     }
 
     pub fn plus_sent(&self, delta: i32) {
-        self.sent
-            .fetch_add(delta, std::sync::atomic::Ordering::Relaxed);
+        self.sent.fetch_add(delta, Ordering::Relaxed);
     }
     pub fn get_receive(&self) -> i32 {
-        self.receive.load(std::sync::atomic::Ordering::Relaxed)
+        self.receive.load(Ordering::Relaxed)
     }
 
     pub fn plus_one_receive(&self) {
@@ -270,8 +498,7 @@ impl Bot {
     }
 
     pub fn plus_receive(&self, delta: i32) {
-        self.receive
-            .fetch_add(delta, std::sync::atomic::Ordering::Relaxed);
+        self.receive.fetch_add(delta, Ordering::Relaxed);
     }
 
     pub fn get_groups_arc(&self) -> Arc<RwLock<Option<HashMap<u64, Group>>>> {
@@ -337,43 +564,118 @@ impl Bot {
         self.send_request_with_timeout(request, None).await
     }
 
+    /// 发送请求并等待响应，seq为0或与等待中的请求冲突时会重新分配
     pub async fn send_request_with_timeout(
         &self,
-        request: common::Request,
+        mut request: common::Request,
         timeout_duration: Option<Duration>,
     ) -> crate::model::error::Result<common::Response> {
         let timeout_duration = timeout_duration.unwrap_or(Duration::from_secs(10));
+        let (tx, connection) = {
+            let listener = self.response_listener.read().await;
+            let Some(tx) = listener.clone() else {
+                return err!("Connection not established");
+            };
+            (tx, self.current_connection())
+        };
         let (resp_tx, resp_rx) = oneshot::channel();
-        let tx_guard = self.response_listener.clone();
+        let pending = PendingRequest {
+            cmd: request.cmd.clone(),
+            sent_at: Instant::now(),
+            connection,
+            tx: resp_tx,
+        };
+        // 占用seq，冲突时换下一个
+        loop {
+            if request.seq != 0 {
+                if let Entry::Vacant(entry) = self.request_queue.entry(request.seq) {
+                    entry.insert(pending);
+                    break;
+                }
+                warn!(
+                    "seq collision: cmd: {}, seq: {}",
+                    request.cmd, request.seq
+                );
+            }
+            request.seq = self.next_seq();
+        }
+        {
+            let mut stats = self.request_stats.entry(request.cmd.clone()).or_default();
+            stats.total += 1;
+            stats.pending += 1;
+        }
+        let _guard = PendingGuard {
+            bot: self,
+            seq: request.seq,
+            cmd: request.cmd.clone(),
+        };
 
-        if let Some(tx) = tx_guard.as_ref() {
-            let request_queue = self.request_queue.clone();
-            request_queue.insert(request.seq, resp_tx);
-            tx.send(Ok(request.clone()))
-                .await
-                .expect("Failed to send request");
-        } else {
-            return err!("Connection not established");
+        if self.is_recording() {
+            record_request(
+                self.uin.unwrap_or_default(),
+                self.uid.as_deref().unwrap_or_default(),
+                &request,
+            );
+        }
+        if tx.send(Ok(request.clone())).await.is_err() {
+            return network_err!("Connection closed");
         }
 
         debug!("Request sent: {:?}", request);
-        let timeout_future = tokio::time::timeout(timeout_duration, resp_rx);
-        match timeout_future.await {
-            Ok(result) => match result {
-                Ok(response) => {
-                    debug!(
-                        "Response received, cmd: {}, seq: {}",
-                        response.cmd, response.seq
-                    );
-                    Ok(response)
+        match tokio::time::timeout(timeout_duration, resp_rx).await {
+            Ok(Ok(response)) => {
+                debug!(
+                    "Response received, cmd: {}, seq: {}",
+                    response.cmd, response.seq
+                );
+                Ok(response)
+            }
+            // 连接断开时请求被移出队列
+            Ok(Err(e)) => {
+                if let Some(mut stats) = self.request_stats.get_mut(&request.cmd) {
+                    stats.cancelled += 1;
                 }
-                Err(e) => kritor_err!(format!("Failed to receive response: {}", e)),
-            },
-            Err(_) => err!(
-                "Timeout occurred while waiting for response: {}",
-                request.cmd
-            ),
+                network_err!("Failed to receive response: {}", e)
+            }
+            Err(_) => {
+                // 先移出队列，避免被当作取消
+                self.request_queue.remove(&request.seq);
+                if let Some(mut stats) = self.request_stats.get_mut(&request.cmd) {
+                    stats.timeout += 1;
+                }
+                Err(Error::timeout(format!(
+                    "Timeout occurred while waiting for response: {}",
+                    request.cmd
+                )))
+            }
+        }
+    }
+
+    /// 发送请求并解码响应，code不为0时返回错误
+    ///
+    /// cmd为proto中的 Service.Method，如 `MessageService.SendMessage`，一般通过生成的方法调用
+    pub async fn call<Req, Resp>(&self, cmd: &str, request: Req) -> crate::model::error::Result<Resp>
+    where
+        Req: Message,
+        Resp: Message + Default,
+    {
+        let response = self
+            .send_request(common::Request {
+                cmd: cmd.to_string(),
+                seq: self.next_seq(),
+                buf: request.encode_to_vec(),
+                no_response: false,
+            })
+            .await?;
+        if response.code != 0 {
+            return Err(Error::response(
+                cmd.to_string(),
+                response.code,
+                response.msg.unwrap_or_default(),
+            ));
         }
+        // 空的buf即所有字段都为默认值
+        Ok(Resp::decode(response.buf.as_slice())?)
     }
 
     pub async fn send_msg(
@@ -381,31 +683,32 @@ impl Bot {
         segments: Vec<Element>,
         contact: Contact,
     ) -> crate::model::error::Result<SendMessageResponse> {
+        self.send_msg_with_priority(segments, contact, false).await
+    }
+
+    /// 经过限速队列发送消息，priority为true时走优先通道
+    pub async fn send_msg_with_priority(
+        &self,
+        segments: Vec<Element>,
+        contact: Contact,
+        priority: bool,
+    ) -> crate::model::error::Result<SendMessageResponse> {
+        let config = get_config().await.send.unwrap_or_default();
+        self.send_scheduler
+            .acquire(&contact, priority, &config)
+            .await;
+        // 发送失败时由Kritor端按retry_count重试
         let msg = SendMessageRequest {
             contact: Some(contact),
             elements: segments,
-            retry_count: None,
+            retry_count: Some(config.retry_count()),
             message_id: None,
             notice_id: None,
             request_id: None,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.SendMessage".to_string(),
-                seq: get_seq(),
-                buf: msg.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = SendMessageResponse::decode(buf).unwrap();
+        let response = self.message_send_message(msg).await?;
+        // 只统计成功发出的消息
         self.plus_one_sent();
         Ok(response)
     }
 }
-
-pub fn get_seq() -> u32 {
-    let mut rng = rand::thread_rng();
-    rng.gen()
-}
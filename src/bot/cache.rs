@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use log::{debug, info, warn};
+use tokio::sync::{RwLock, Semaphore};
+
+use crate::bot::bot::{Bot, BotState};
+use crate::bot::friend::{Friend, FriendAPITrait};
+use crate::bot::group::{Group, GroupAPITrait};
+use crate::kritor::server::kritor_proto::notice_event::Notice;
+use crate::kritor::server::kritor_proto::*;
+use crate::kritor_err;
+use crate::model::cache::{now, CacheStore, CACHE_STORE};
+use crate::model::config::get_config;
+use crate::model::error::Result;
+
+/// 后台同时获取群成员列表的最大请求数
+const MAX_CONCURRENT: usize = 5;
+
+/// 获取成员列表失败时的重试次数
+const MAX_RETRY: u32 = 3;
+
+/// 未开启定期同步时，重新检查配置的间隔
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+async fn ttl() -> u64 {
+    get_config().await.cache.unwrap_or_default().ttl()
+}
+
+impl Bot {
+    /// 写入本地缓存，失败只记录日志
+    fn persist<F: FnOnce(&CacheStore, &str) -> Result<()>>(&self, f: F) {
+        let Some(store) = CACHE_STORE.as_ref() else {
+            return;
+        };
+        if let Err(e) = f(store, self.get_uid().unwrap_or_default().as_str()) {
+            warn!("Failed to persist cache: {}", e);
+        }
+    }
+
+    /// 从本地缓存恢复群、好友，启动时不必等待全部成员列表
+    pub async fn load_cache(&self) {
+        let Some(store) = CACHE_STORE.as_ref() else {
+            return;
+        };
+        let uid = self.get_uid().unwrap_or_default();
+        match store.load_friends(&uid) {
+            Ok(friends) => {
+                let friends_arc = self.get_friends_arc();
+                friends_arc
+                    .write()
+                    .await
+                    .get_or_insert_with(HashMap::new)
+                    .extend(friends.into_iter().map(|info| (info.uin, Friend::new(info))));
+            }
+            Err(e) => warn!("Failed to load cached friends: {}", e),
+        }
+        match store.load_groups(&uid) {
+            Ok(cached) => {
+                info!("Loaded {} groups from cache", cached.len());
+                let groups = cached.into_iter().map(|cached| {
+                    let group = Group {
+                        roles_loaded: cached.info.owner != 0,
+                        members: cached
+                            .members
+                            .into_iter()
+                            .map(|member| (member.uin, member))
+                            .collect(),
+                        inner: cached.info,
+                        updated_at: cached.updated_at,
+                        members_updated_at: cached.members_updated_at,
+                    };
+                    (group.inner.group_id, group)
+                });
+                let groups_arc = self.get_groups_arc();
+                groups_arc
+                    .write()
+                    .await
+                    .get_or_insert_with(HashMap::new)
+                    .extend(groups);
+            }
+            Err(e) => warn!("Failed to load cached groups: {}", e),
+        }
+    }
+
+    /// 重新获取好友列表，替换缓存
+    pub async fn refresh_friends(&self) -> Result<()> {
+        let response = self.get_friend_list(true).await?;
+        info!("Friends count: {}", response.friends_info.len());
+        let friends = response
+            .friends_info
+            .iter()
+            .map(|info| (info.uin, Friend::new(info.clone())))
+            .collect::<HashMap<u64, Friend>>();
+        self.get_friends_arc().write().await.replace(friends);
+        self.persist(|store, uid| store.save_friends(uid, &response.friends_info));
+        Ok(())
+    }
+
+    /// 重新获取群列表，已加载的成员保留，新加入的群稍后加载成员
+    pub async fn refresh_groups(&self) -> Result<()> {
+        let response = self.get_group_list(true).await?;
+        info!("Groups count: {}", response.groups_info.len());
+        {
+            let groups_arc = self.get_groups_arc();
+            let mut groups = groups_arc.write().await;
+            let mut old = groups.take().unwrap_or_default();
+            let new = response
+                .groups_info
+                .iter()
+                .map(|info| {
+                    let group = match old.remove(&info.group_id) {
+                        Some(mut group) => {
+                            let mut info = info.clone();
+                            // 群列表中没有群主时保留已获取的群主与管理员
+                            if info.owner == 0 && group.roles_loaded {
+                                info.owner = group.inner.owner;
+                                info.admins = std::mem::take(&mut group.inner.admins);
+                            } else {
+                                group.roles_loaded = info.owner != 0;
+                            }
+                            group.inner = info;
+                            group.updated_at = now();
+                            group
+                        }
+                        None => Group::lazy(info.clone()),
+                    };
+                    (info.group_id, group)
+                })
+                .collect::<HashMap<u64, Group>>();
+            groups.replace(new);
+        }
+        self.persist(|store, uid| store.save_groups(uid, &response.groups_info));
+        Ok(())
+    }
+
+    /// 获取单个群的信息，用于新加入的群或缓存未命中
+    async fn refresh_group(&self, group_id: u64) -> Result<()> {
+        let Some(info) = self.get_group_info(group_id).await?.group_info else {
+            return kritor_err!("group {} not found", group_id);
+        };
+        {
+            let groups_arc = self.get_groups_arc();
+            let mut groups = groups_arc.write().await;
+            let groups = groups.get_or_insert_with(HashMap::new);
+            // GetGroupInfo的结果即使没有群主也不再重复获取
+            match groups.get_mut(&group_id) {
+                Some(group) => {
+                    group.inner = info.clone();
+                    group.updated_at = now();
+                    group.roles_loaded = true;
+                }
+                None => {
+                    let mut group = Group::lazy(info.clone());
+                    group.roles_loaded = true;
+                    groups.insert(group_id, group);
+                }
+            }
+        }
+        self.persist(|store, uid| store.save_group(uid, &info));
+        Ok(())
+    }
+
+    /// 获取群成员列表并写入缓存
+    async fn load_members(&self, group_id: u64) -> Result<HashMap<u64, GroupMemberInfo>> {
+        let response = self.get_group_member_list(group_id, true).await?;
+        let members = response
+            .group_members_info
+            .iter()
+            .map(|member| (member.uin, member.clone()))
+            .collect::<HashMap<u64, GroupMemberInfo>>();
+        {
+            let groups_arc = self.get_groups_arc();
+            let mut groups = groups_arc.write().await;
+            if let Some(group) = groups.as_mut().and_then(|groups| groups.get_mut(&group_id)) {
+                group.members = members.clone();
+                group.members_updated_at = Some(now());
+            }
+        }
+        self.persist(|store, uid| store.save_members(uid, group_id, &response.group_members_info));
+        Ok(members)
+    }
+
+    async fn load_members_with_retry(&self, group_id: u64) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.load_members(group_id).await {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt + 1 >= MAX_RETRY => return Err(e),
+                Err(e) => {
+                    attempt += 1;
+                    debug!(
+                        "Failed to load members of group {}, retry {}: {}",
+                        group_id, attempt, e
+                    );
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                }
+            }
+        }
+    }
+
+    /// 加载所有未加载或已过期的成员列表，失败的群会重试
+    pub async fn load_stale_members(&self) {
+        let ttl = ttl().await;
+        let stale = {
+            let groups_arc = self.get_groups_arc();
+            let groups = groups_arc.read().await;
+            groups
+                .as_ref()
+                .map(|groups| {
+                    groups
+                        .values()
+                        .filter(|group| !group.members_fresh(ttl))
+                        .map(|group| group.inner.group_id)
+                        .collect::<Vec<u64>>()
+                })
+                .unwrap_or_default()
+        };
+        if stale.is_empty() {
+            return;
+        }
+        info!("Start to load members of {} groups", stale.len());
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT));
+        let failed = stale
+            .into_iter()
+            .map(|group_id| {
+                let semaphore = semaphore.clone();
+                async move {
+                    let _permit = semaphore.acquire_owned().await.ok();
+                    match self.load_members_with_retry(group_id).await {
+                        Ok(_) => 0,
+                        Err(e) => {
+                            warn!("Failed to load members of group {}: {}", group_id, e);
+                            1
+                        }
+                    }
+                }
+            })
+            .collect::<FuturesUnordered<_>>()
+            .collect::<Vec<usize>>()
+            .await
+            .into_iter()
+            .sum::<usize>();
+        info!("Group members loaded, {} failed", failed);
+    }
+
+    /// 重新获取群、好友列表，再加载过期的成员列表
+    pub async fn resync(&self) {
+        if let Err(e) = self.refresh_friends().await {
+            warn!("Failed to resync friends: {}", e);
+        }
+        if let Err(e) = self.refresh_groups().await {
+            warn!("Failed to resync groups: {}", e);
+        }
+        self.load_stale_members().await;
+    }
+
+    /// 获取群信息与成员，缓存未命中或成员列表过期时请求Kritor端
+    pub async fn get_group(&self, group_id: u64) -> Result<Group> {
+        let cached = self
+            .get_groups_arc()
+            .read()
+            .await
+            .as_ref()
+            .and_then(|groups| groups.get(&group_id).cloned());
+        let ttl = ttl().await;
+        if let Some(group) = cached.as_ref().filter(|group| group.members_fresh(ttl)) {
+            return Ok(group.clone());
+        }
+        if cached.is_none() {
+            self.refresh_group(group_id).await?;
+        }
+        self.load_members(group_id).await?;
+        match self.get_groups_arc().read().await.as_ref() {
+            Some(groups) if groups.contains_key(&group_id) => Ok(groups[&group_id].clone()),
+            _ => kritor_err!("group {} not found", group_id),
+        }
+    }
+
+    /// 获取包含群主与管理员的群信息，未知时通过GetGroupInfo获取
+    pub async fn get_group_roles(&self, group_id: u64) -> Result<GroupInfo> {
+        let cached = self
+            .get_groups_arc()
+            .read()
+            .await
+            .as_ref()
+            .and_then(|groups| groups.get(&group_id))
+            .filter(|group| group.roles_loaded)
+            .map(|group| group.inner.clone());
+        if let Some(info) = cached {
+            return Ok(info);
+        }
+        self.refresh_group(group_id).await?;
+        match self.get_groups_arc().read().await.as_ref() {
+            Some(groups) if groups.contains_key(&group_id) => Ok(groups[&group_id].inner.clone()),
+            _ => kritor_err!("group {} not found", group_id),
+        }
+    }
+
+    /// 获取群成员列表，未加载或已过期时请求Kritor端
+    pub async fn get_group_members(&self, group_id: u64) -> Result<HashMap<u64, GroupMemberInfo>> {
+        self.get_group(group_id).await.map(|group| group.members)
+    }
+
+    /// 获取单个群成员，缓存未命中时请求Kritor端
+    pub async fn get_group_member(&self, group_id: u64, uin: u64) -> Result<GroupMemberInfo> {
+        let cached = self
+            .get_groups_arc()
+            .read()
+            .await
+            .as_ref()
+            .and_then(|groups| groups.get(&group_id))
+            .and_then(|group| group.members.get(&uin).cloned());
+        match cached {
+            Some(member) => Ok(member),
+            None => self.fetch_member(group_id, uin, false).await,
+        }
+    }
+
+    /// 请求单个群成员的信息并写入缓存
+    async fn fetch_member(&self, group_id: u64, uin: u64, refresh: bool) -> Result<GroupMemberInfo> {
+        let Some(member) = self
+            .get_group_member_info_by_uin(group_id, uin, refresh)
+            .await?
+            .group_member_info
+        else {
+            return kritor_err!("member {} of group {} not found", uin, group_id);
+        };
+        if let Some(group) = self
+            .get_groups_arc()
+            .write()
+            .await
+            .as_mut()
+            .and_then(|groups| groups.get_mut(&group_id))
+        {
+            group.members.insert(uin, member.clone());
+        }
+        self.persist(|store, uid| store.save_member(uid, group_id, &member));
+        Ok(member)
+    }
+
+    /// 获取好友，缓存未命中时重新获取好友列表
+    pub async fn get_friend(&self, uin: u64) -> Result<Friend> {
+        let cached = self
+            .get_friends_arc()
+            .read()
+            .await
+            .as_ref()
+            .and_then(|friends| friends.get(&uin).cloned());
+        if let Some(friend) = cached {
+            return Ok(friend);
+        }
+        self.refresh_friends().await?;
+        match self.get_friends_arc().read().await.as_ref() {
+            Some(friends) if friends.contains_key(&uin) => Ok(friends[&uin].clone()),
+            _ => kritor_err!("friend {} not found", uin),
+        }
+    }
+
+    /// 根据通知更新群、好友缓存，需要请求Kritor端的更新在后台进行
+    ///
+    /// 持有群列表的写锁时只修改内存，释放后再写入本地缓存
+    pub async fn apply_notice(self_arc: &Arc<RwLock<Self>>, notice: &NoticeEvent) {
+        let Some(notice) = notice.notice.as_ref() else {
+            return;
+        };
+        let bot = self_arc.read().await;
+        let self_uin = bot.get_uin().unwrap_or_default();
+        let groups_arc = bot.get_groups_arc();
+        let change: Option<CacheChange> = {
+            let mut groups = groups_arc.write().await;
+            let groups = groups.get_or_insert_with(HashMap::new);
+            match notice {
+                Notice::GroupMemberIncrease(n) => {
+                    let bot_arc = self_arc.clone();
+                    let (group_id, uin) = (n.group_id, n.target_uin);
+                    if uin == self_uin {
+                        tokio::spawn(async move {
+                            let bot = bot_arc.read().await;
+                            if let Err(e) = bot.refresh_group(group_id).await {
+                                warn!("Failed to load joined group {}: {}", group_id, e);
+                                return;
+                            }
+                            if let Err(e) = bot.load_members_with_retry(group_id).await {
+                                warn!("Failed to load members of group {}: {}", group_id, e);
+                            }
+                        });
+                        return;
+                    }
+                    let Some(group) = groups.get_mut(&group_id) else {
+                        return;
+                    };
+                    let member = GroupMemberInfo {
+                        uid: n.target_uid.clone(),
+                        uin,
+                        ..Default::default()
+                    };
+                    group.members.insert(uin, member.clone());
+                    group.inner.member_count = group.inner.member_count.saturating_add(1);
+                    // 获取完整的成员信息
+                    tokio::spawn(async move {
+                        let bot = bot_arc.read().await;
+                        if let Err(e) = bot.fetch_member(group_id, uin, true).await {
+                            debug!("Failed to get member {} of group {}: {}", uin, group_id, e);
+                        }
+                    });
+                    Some(CacheChange::SaveMember(group_id, member))
+                }
+                Notice::GroupMemberDecrease(n) => {
+                    let group_id = n.group_id;
+                    let target_uid = n.target_uid.as_deref().unwrap_or_default();
+                    let Some(group) = groups.get_mut(&group_id) else {
+                        return;
+                    };
+                    let uin = n.target_uin.or_else(|| {
+                        group
+                            .members
+                            .values()
+                            .find(|member| member.uid == target_uid)
+                            .map(|member| member.uin)
+                    });
+                    match uin {
+                        Some(uin) if uin == self_uin => {
+                            groups.remove(&group_id);
+                            Some(CacheChange::RemoveGroup(group_id))
+                        }
+                        Some(uin) => {
+                            group.members.remove(&uin);
+                            group.inner.admins.retain(|admin| *admin != uin);
+                            group.inner.member_count = group.inner.member_count.saturating_sub(1);
+                            Some(CacheChange::RemoveMember(group_id, uin))
+                        }
+                        None => None,
+                    }
+                }
+                Notice::GroupCardChanged(n) => groups
+                    .get_mut(&n.group_id)
+                    .and_then(|group| group.members.get_mut(&n.target_uin))
+                    .map(|member| {
+                        member.card = n.new_card.clone();
+                        CacheChange::SaveMember(n.group_id, member.clone())
+                    }),
+                Notice::GroupAdminChange(n) => groups.get_mut(&n.group_id).map(|group| {
+                    let admins = &mut group.inner.admins;
+                    admins.retain(|admin| *admin != n.target_uin);
+                    if n.is_admin {
+                        admins.push(n.target_uin);
+                    }
+                    CacheChange::SaveGroup(group.inner.clone())
+                }),
+                Notice::GroupMemberBan(n) => groups
+                    .get_mut(&n.group_id)
+                    .and_then(|group| group.members.get_mut(&n.target_uin))
+                    .map(|member| {
+                        // duration为0表示解除禁言
+                        member.shut_up_timestamp = if n.duration > 0 {
+                            now() + n.duration as u64
+                        } else {
+                            0
+                        };
+                        CacheChange::SaveMember(n.group_id, member.clone())
+                    }),
+                _ => None,
+            }
+        };
+        match change {
+            Some(CacheChange::SaveMember(group_id, member)) => {
+                bot.persist(|store, uid| store.save_member(uid, group_id, &member))
+            }
+            Some(CacheChange::RemoveMember(group_id, uin)) => {
+                bot.persist(|store, uid| store.remove_member(uid, group_id, uin))
+            }
+            Some(CacheChange::RemoveGroup(group_id)) => {
+                bot.persist(|store, uid| store.remove_group(uid, group_id))
+            }
+            Some(CacheChange::SaveGroup(info)) => {
+                bot.persist(|store, uid| store.save_group(uid, &info))
+            }
+            None => {}
+        }
+    }
+}
+
+/// 通知引起的本地缓存修改，在释放群列表的锁之后写入
+enum CacheChange {
+    SaveMember(u64, GroupMemberInfo),
+    RemoveMember(u64, u64),
+    RemoveGroup(u64),
+    SaveGroup(GroupInfo),
+}
+
+/// 定期重新同步群、好友列表，并重新加载过期的成员列表，弥补遗漏的通知
+pub fn spawn_resync(bot: Arc<RwLock<Bot>>) {
+    tokio::spawn(async move {
+        loop {
+            let interval = get_config().await.cache.unwrap_or_default().resync_interval();
+            if interval == 0 {
+                tokio::time::sleep(CONFIG_CHECK_INTERVAL).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let bot = bot.read().await;
+            if bot.get_state().await != BotState::Online {
+                continue;
+            }
+            debug!("Start to resync groups and friends");
+            bot.resync().await;
+        }
+    });
+}
@@ -0,0 +1,103 @@
+use std::sync::Mutex;
+
+use log::warn;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+
+/// 事件队列，默认使用broadcast，消费者跟不上时丢弃最旧的事件
+///
+/// 开启backpressure后每个消费者有独立的有界队列，队列满时发送方等待，
+/// 从而暂停读取Kritor端的事件流，不会丢失事件。外部订阅者总是使用broadcast，
+/// 不会拖慢事件流
+#[derive(Debug)]
+pub struct EventChannel<T> {
+    name: &'static str,
+    capacity: usize,
+    broadcast: broadcast::Sender<T>,
+    // 为None时使用broadcast
+    bounded: Option<Mutex<Vec<mpsc::Sender<T>>>>,
+}
+
+/// 事件队列的消费端
+#[derive(Debug)]
+pub enum EventReceiver<T> {
+    Broadcast {
+        name: &'static str,
+        receiver: broadcast::Receiver<T>,
+    },
+    Bounded(mpsc::Receiver<T>),
+}
+
+impl<T: Clone + Send + 'static> EventChannel<T> {
+    pub fn new(name: &'static str, capacity: usize, backpressure: bool) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _receiver) = broadcast::channel(capacity);
+        Self {
+            name,
+            capacity,
+            broadcast: sender,
+            bounded: backpressure.then(|| Mutex::new(vec![])),
+        }
+    }
+
+    pub fn subscribe(&self) -> EventReceiver<T> {
+        match self.bounded.as_ref() {
+            Some(senders) => {
+                let (tx, rx) = mpsc::channel(self.capacity);
+                senders.lock().unwrap().push(tx);
+                EventReceiver::Bounded(rx)
+            }
+            None => EventReceiver::Broadcast {
+                name: self.name,
+                receiver: self.broadcast.subscribe(),
+            },
+        }
+    }
+
+    /// 不受backpressure影响的订阅，跟不上时丢弃最旧的事件
+    pub fn subscribe_lossy(&self) -> EventReceiver<T> {
+        EventReceiver::Broadcast {
+            name: self.name,
+            receiver: self.broadcast.subscribe(),
+        }
+    }
+
+    /// 推送事件，开启backpressure时等待所有有界消费者都有空位
+    pub async fn send(&self, event: T) {
+        // 没有订阅者时返回错误，忽略即可
+        let _ = self.broadcast.send(event.clone());
+        let Some(senders) = self.bounded.as_ref() else {
+            return;
+        };
+        let targets = senders.lock().unwrap().clone();
+        let mut closed = false;
+        for tx in targets {
+            if tx.capacity() == 0 {
+                warn!("{} queue is full, waiting for consumer", self.name);
+            }
+            closed |= tx.send(event.clone()).await.is_err();
+        }
+        // 移除已经断开的消费者
+        if closed {
+            senders.lock().unwrap().retain(|tx| !tx.is_closed());
+        }
+    }
+}
+
+impl<T: Clone> EventReceiver<T> {
+    /// 接收下一个事件，队列关闭时返回None。积压时跳过丢失的事件并继续接收
+    pub async fn recv(&mut self) -> Option<T> {
+        match self {
+            EventReceiver::Broadcast { name, receiver } => loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some(event),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("{} receiver lagged, {} events skipped", name, skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+            EventReceiver::Bounded(receiver) => receiver.recv().await,
+        }
+    }
+}
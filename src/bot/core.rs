@@ -2,11 +2,7 @@ use crate::model::error::Result;
 
 use crate::bot::bot::Bot;
 use crate::kritor::server::kritor_proto::*;
-use crate::kritor_err;
 use async_trait::async_trait;
-use bytes::Bytes;
-use log::error;
-use prost::Message;
 
 #[async_trait]
 pub trait CoreAPITrait {
@@ -26,25 +22,7 @@ pub trait CoreAPITrait {
 impl CoreAPITrait for Bot {
     async fn get_version(&self) -> Result<GetVersionResponse> {
         let request = GetVersionRequest {};
-        let response = self
-            .send_request(common::Request {
-                cmd: "CoreService.GetVersion".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        if buf.is_empty() {
-            error!(
-                "request error: {}",
-                response.msg.as_ref().cloned().unwrap_or("".to_string())
-            );
-            return kritor_err!("empty response");
-        }
-        let response = GetVersionResponse::decode(buf).unwrap();
-        Ok(response)
+        self.core_get_version(request).await
     }
 
     async fn download_file(&self, url: String) -> Result<DownloadFileResponse> {
@@ -56,48 +34,12 @@ impl CoreAPITrait for Bot {
             thread_cnt: None,
             headers: None,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "CoreService.DownloadFile".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        if buf.is_empty() {
-            error!(
-                "request error: {}",
-                response.msg.as_ref().cloned().unwrap_or("".to_string())
-            );
-            return kritor_err!("empty response");
-        }
-        let response = DownloadFileResponse::decode(buf).unwrap();
-        Ok(response)
+        self.core_download_file(request).await
     }
 
     async fn get_current_account(&self) -> Result<GetCurrentAccountResponse> {
         let request = GetCurrentAccountRequest {};
-        let response = self
-            .send_request(common::Request {
-                cmd: "CoreService.GetCurrentAccount".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        if buf.is_empty() {
-            error!(
-                "request error: {}",
-                response.msg.as_ref().cloned().unwrap_or("".to_string())
-            );
-            return kritor_err!("empty response");
-        }
-        let response = GetCurrentAccountResponse::decode(buf).unwrap();
-        Ok(response)
+        self.core_get_current_account(request).await
     }
 
     async fn switch_account(
@@ -109,24 +51,6 @@ impl CoreAPITrait for Bot {
             account: Some(switch_account_request::Account::AccountUid(account_uid)),
             super_ticket,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "CoreService.SwitchAccount".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        if buf.is_empty() {
-            error!(
-                "request error: {}",
-                response.msg.as_ref().cloned().unwrap_or("".to_string())
-            );
-            return kritor_err!("empty response");
-        }
-        let response = SwitchAccountResponse::decode(buf).unwrap();
-        Ok(response)
+        self.core_switch_account(request).await
     }
 }
@@ -0,0 +1,59 @@
+use crate::bot::bot::Bot;
+use crate::bot::core::CoreAPITrait;
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+use async_trait::async_trait;
+
+/// Kritor端的开发者接口，可以在Kritor端执行命令，只应开放给主人
+#[async_trait]
+pub trait DeveloperAPITrait {
+    async fn shell(&self, command: Vec<String>, directory: String) -> Result<ShellResponse>;
+
+    /// 获取Kritor端日志，recent为true时只返回最近的日志
+    async fn get_log(&self, start: u32, recent: bool) -> Result<GetLogResponse>;
+
+    async fn clear_cache(&self) -> Result<ClearCacheResponse>;
+
+    async fn get_device_battery(&self) -> Result<GetDeviceBatteryResponse>;
+
+    /// Kritor端的实现、版本、账号与电量信息
+    async fn get_developer_info(&self) -> Result<String>;
+}
+
+#[async_trait]
+impl DeveloperAPITrait for Bot {
+    async fn shell(&self, command: Vec<String>, directory: String) -> Result<ShellResponse> {
+        let request = ShellRequest { command, directory };
+        self.developer_shell(request).await
+    }
+
+    async fn get_log(&self, start: u32, recent: bool) -> Result<GetLogResponse> {
+        let request = GetLogRequest { start, recent };
+        self.developer_get_log(request).await
+    }
+
+    async fn clear_cache(&self) -> Result<ClearCacheResponse> {
+        self.developer_clear_cache(ClearCacheRequest {}).await
+    }
+
+    async fn get_device_battery(&self) -> Result<GetDeviceBatteryResponse> {
+        self.developer_get_device_battery(GetDeviceBatteryRequest {}).await
+    }
+
+    async fn get_developer_info(&self) -> Result<String> {
+        let version = self.get_version().await?;
+        let account = self.get_current_account().await?;
+        let mut info = format!(
+            "实现：{}\n版本：{}\n账号：{}({})",
+            version.app_name, version.version, account.account_name, account.account_uin
+        );
+        // 部分实现不支持获取电量
+        if let Ok(battery) = self.get_device_battery().await {
+            info.push_str(&format!(
+                "\n电量：{}/{}",
+                battery.battery, battery.scale
+            ));
+        }
+        Ok(info)
+    }
+}
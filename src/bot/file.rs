@@ -0,0 +1,156 @@
+use crate::bot::bot::Bot;
+use crate::client_err;
+use crate::kritor::server::kritor_proto::common::FileElement;
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+/// 群文件管理
+///
+/// kritor的GroupFileService没有移动文件的接口，因此不提供move_file
+#[async_trait]
+pub trait FileAPITrait {
+    async fn create_folder(&self, group_id: u64, name: String) -> Result<CreateFolderResponse>;
+
+    async fn rename_folder(
+        &self,
+        group_id: u64,
+        folder_id: String,
+        name: String,
+    ) -> Result<RenameFolderResponse>;
+
+    async fn delete_folder(&self, group_id: u64, folder_id: String)
+        -> Result<DeleteFolderResponse>;
+
+    async fn upload_file(
+        &self,
+        group_id: u64,
+        data: upload_file_request::Data,
+    ) -> Result<UploadFileResponse>;
+
+    async fn delete_file(
+        &self,
+        group_id: u64,
+        file_id: String,
+        bus_id: u32,
+    ) -> Result<DeleteFileResponse>;
+
+    async fn get_file_system_info(&self, group_id: u64) -> Result<GetFileSystemInfoResponse>;
+
+    /// 获取群文件和文件夹列表，folder_id为空时为根目录
+    async fn get_file_list(
+        &self,
+        group_id: u64,
+        folder_id: Option<String>,
+    ) -> Result<GetFileListResponse>;
+
+    /// 下载消息中的文件
+    async fn download_file_element(&self, element: &FileElement) -> Result<Vec<u8>>;
+
+    /// 下载消息中的文件并保存到dir下，返回保存的路径
+    async fn save_file_element(&self, element: &FileElement, dir: &Path) -> Result<PathBuf>;
+}
+
+#[async_trait]
+impl FileAPITrait for Bot {
+    async fn create_folder(&self, group_id: u64, name: String) -> Result<CreateFolderResponse> {
+        let request = CreateFolderRequest { group_id, name };
+        self.group_file_create_folder(request).await
+    }
+
+    async fn rename_folder(
+        &self,
+        group_id: u64,
+        folder_id: String,
+        name: String,
+    ) -> Result<RenameFolderResponse> {
+        let request = RenameFolderRequest {
+            group_id,
+            folder_id,
+            name,
+        };
+        self.group_file_rename_folder(request).await
+    }
+
+    async fn delete_folder(
+        &self,
+        group_id: u64,
+        folder_id: String,
+    ) -> Result<DeleteFolderResponse> {
+        let request = DeleteFolderRequest {
+            group_id,
+            folder_id,
+        };
+        self.group_file_delete_folder(request).await
+    }
+
+    async fn upload_file(
+        &self,
+        group_id: u64,
+        data: upload_file_request::Data,
+    ) -> Result<UploadFileResponse> {
+        let request = UploadFileRequest {
+            group_id,
+            data: Some(data),
+        };
+        self.group_file_upload_file(request).await
+    }
+
+    async fn delete_file(
+        &self,
+        group_id: u64,
+        file_id: String,
+        bus_id: u32,
+    ) -> Result<DeleteFileResponse> {
+        let request = DeleteFileRequest {
+            group_id,
+            file_id,
+            bus_id,
+        };
+        self.group_file_delete_file(request).await
+    }
+
+    async fn get_file_system_info(&self, group_id: u64) -> Result<GetFileSystemInfoResponse> {
+        let request = GetFileSystemInfoRequest { group_id };
+        self.group_file_get_file_system_info(request).await
+    }
+
+    async fn get_file_list(
+        &self,
+        group_id: u64,
+        folder_id: Option<String>,
+    ) -> Result<GetFileListResponse> {
+        let request = GetFileListRequest {
+            group_id,
+            folder_id,
+        };
+        self.group_file_get_file_list(request).await
+    }
+
+    async fn download_file_element(&self, element: &FileElement) -> Result<Vec<u8>> {
+        let Some(url) = element.url.clone() else {
+            return client_err!("file element has no url");
+        };
+        let response = reqwest::get(url).await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn save_file_element(&self, element: &FileElement, dir: &Path) -> Result<PathBuf> {
+        let name = element
+            .name
+            .clone()
+            .or(element.id.clone())
+            .unwrap_or("unnamed".to_string());
+        // 文件名来自消息，去掉路径部分
+        let name = Path::new(&name)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or("unnamed".to_string());
+        let bytes = self.download_file_element(element).await?;
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(name);
+        std::fs::write(&path, bytes)?;
+        Ok(path)
+    }
+}
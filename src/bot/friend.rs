@@ -2,8 +2,6 @@ use crate::bot::bot::Bot;
 use crate::kritor::server::kritor_proto::*;
 use crate::model::error::Result;
 use async_trait::async_trait;
-use bytes::Bytes;
-use prost::Message;
 #[derive(Debug, Clone)]
 pub struct Friend {
     pub inner: FriendInfo,
@@ -63,18 +61,7 @@ impl FriendAPITrait for Bot {
         let request = GetFriendListRequest {
             refresh: Some(refresh),
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.GetFriendListRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetFriendListResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_get_friend_list(request).await
     }
 
     async fn get_friend_profile_card(
@@ -86,18 +73,7 @@ impl FriendAPITrait for Bot {
             target_uins: uins,
             target_uids: uids,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.GetFriendProfileCardRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetFriendProfileCardResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_get_friend_profile_card(request).await
     }
 
     async fn get_stranger_profile_card(
@@ -109,18 +85,7 @@ impl FriendAPITrait for Bot {
             target_uins: uins,
             target_uids: uids,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.GetStrangerProfileCardRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetStrangerProfileCardResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_get_stranger_profile_card(request).await
     }
 
     async fn set_profile_card(
@@ -142,18 +107,7 @@ impl FriendAPITrait for Bot {
             birthday,
             age,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.SetProfileCardRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = SetProfileCardResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_set_profile_card(request).await
     }
 
     async fn is_black_list_user(
@@ -163,18 +117,7 @@ impl FriendAPITrait for Bot {
         let request = IsBlackListUserRequest {
             target: Some(target),
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.IsBlackListUserRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = IsBlackListUserResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_is_black_list_user(request).await
     }
 
     async fn vote_user(
@@ -186,49 +129,16 @@ impl FriendAPITrait for Bot {
             vote_count,
             target: Some(target),
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.VoteUserRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = VoteUserResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_vote_user(request).await
     }
 
     async fn get_uid_by_uin(&self, uins: Vec<u64>) -> Result<GetUidByUinResponse> {
         let request = GetUidByUinRequest { target_uins: uins };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.GetUidByUinRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetUidByUinResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_get_uid_by_uin(request).await
     }
 
     async fn get_uin_by_uid(&self, uids: Vec<String>) -> Result<GetUinByUidResponse> {
         let request = GetUinByUidRequest { target_uids: uids };
-        let response = self
-            .send_request(common::Request {
-                cmd: "FriendService.GetUinByUidRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetUinByUidResponse::decode(buf).unwrap();
-        Ok(response)
+        self.friend_get_uin_by_uid(request).await
     }
 }
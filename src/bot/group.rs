@@ -1,17 +1,20 @@
 use crate::bot::bot::Bot;
 use crate::kritor::server::kritor_proto::get_group_member_info_request::Target;
 use crate::kritor::server::kritor_proto::*;
-use crate::kritor_err;
+use crate::model::cache::now;
 use async_trait::async_trait;
-use bytes::Bytes;
-use log::error;
-use prost::Message;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct Group {
     pub inner: GroupInfo,
     pub members: HashMap<u64, GroupMemberInfo>,
+    // 群信息的更新时间戳，单位秒
+    pub updated_at: u64,
+    // 成员列表的更新时间戳，None表示尚未加载
+    pub members_updated_at: Option<u64>,
+    // 群主与管理员是否已知，适配器的群列表中没有这些信息，需要通过GetGroupInfo获取
+    pub roles_loaded: bool,
 }
 
 impl Default for Group {
@@ -19,13 +22,40 @@ impl Default for Group {
         Self {
             inner: GroupInfo::default(),
             members: HashMap::new(),
+            updated_at: 0,
+            members_updated_at: None,
+            roles_loaded: false,
         }
     }
 }
 
 impl Group {
     pub fn new(inner: GroupInfo, members: HashMap<u64, GroupMemberInfo>) -> Self {
-        Self { inner, members }
+        let now = now();
+        Self {
+            roles_loaded: inner.owner != 0,
+            inner,
+            members,
+            updated_at: now,
+            members_updated_at: Some(now),
+        }
+    }
+
+    /// 成员列表稍后加载的群
+    pub fn lazy(inner: GroupInfo) -> Self {
+        Self {
+            roles_loaded: inner.owner != 0,
+            inner,
+            updated_at: now(),
+            ..Default::default()
+        }
+    }
+
+    /// 成员列表是否已加载且未超过ttl秒
+    pub fn members_fresh(&self, ttl: u64) -> bool {
+        self.members_updated_at
+            .map(|t| now().saturating_sub(t) < ttl)
+            .unwrap_or(false)
     }
 }
 
@@ -154,25 +184,7 @@ impl GroupAPITrait for Bot {
         let request = GetGroupListRequest {
             refresh: Some(refresh),
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "GroupService.GetGroupList".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        if buf.is_empty() {
-            error!(
-                "request error: {}",
-                response.msg.as_ref().cloned().unwrap_or("".to_string())
-            );
-            return kritor_err!("empty response");
-        }
-        let response = GetGroupListResponse::decode(buf).unwrap();
-        Ok(response)
+        self.group_get_group_list(request).await
     }
 
     async fn get_group_info(
@@ -180,16 +192,7 @@ impl GroupAPITrait for Bot {
         group_id: u64,
     ) -> crate::model::error::Result<GetGroupInfoResponse> {
         let request = GetGroupInfoRequest { group_id };
-        let request = common::Request {
-            cmd: "GroupService.GetGroupInfo".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let group_info = GetGroupInfoResponse::decode(buf).unwrap();
-        Ok(group_info)
+        self.group_get_group_info(request).await
     }
 
     async fn get_group_member_info_by_uin(
@@ -203,16 +206,7 @@ impl GroupAPITrait for Bot {
             refresh: Some(refresh),
             target: Some(Target::TargetUin(member_uin)),
         };
-        let request = common::Request {
-            cmd: "GroupService.GetGroupMemberInfo".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let group_member_info = GetGroupMemberInfoResponse::decode(buf).unwrap();
-        Ok(group_member_info)
+        self.group_get_group_member_info(request).await
     }
 
     async fn get_group_member_info_by_uid(
@@ -226,16 +220,7 @@ impl GroupAPITrait for Bot {
             refresh: Some(refresh),
             target: Some(Target::TargetUid(member_uid)),
         };
-        let request = common::Request {
-            cmd: "GroupService.GetGroupMemberInfo".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let group_member_info = GetGroupMemberInfoResponse::decode(buf).unwrap();
-        Ok(group_member_info)
+        self.group_get_group_member_info(request).await
     }
 
     async fn get_group_member_list(
@@ -247,16 +232,7 @@ impl GroupAPITrait for Bot {
             group_id,
             refresh: Some(refresh),
         };
-        let request = common::Request {
-            cmd: "GroupService.GetGroupMemberList".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let group_member_list = GetGroupMemberListResponse::decode(buf).unwrap();
-        Ok(group_member_list)
+        self.group_get_group_member_list(request).await
     }
 
     async fn ban_member(
@@ -270,16 +246,7 @@ impl GroupAPITrait for Bot {
             target: Some(target),
             duration,
         };
-        let request = common::Request {
-            cmd: "GroupService.BanMember".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let ban_member_response = BanMemberResponse::decode(buf).unwrap();
-        Ok(ban_member_response)
+        self.group_ban_member(request).await
     }
 
     async fn poke_member(
@@ -291,16 +258,7 @@ impl GroupAPITrait for Bot {
             group_id,
             target: Some(target),
         };
-        let request = common::Request {
-            cmd: "GroupService.PokeMember".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let poke_member_response = PokeMemberResponse::decode(buf).unwrap();
-        Ok(poke_member_response)
+        self.group_poke_member(request).await
     }
 
     async fn kick_member(
@@ -316,30 +274,12 @@ impl GroupAPITrait for Bot {
             reject_add_request: Some(reject_add_request),
             kick_reason,
         };
-        let request = common::Request {
-            cmd: "GroupService.KickMember".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let kick_member_response = KickMemberResponse::decode(buf).unwrap();
-        Ok(kick_member_response)
+        self.group_kick_member(request).await
     }
 
     async fn leave_group(&self, group_id: u64) -> crate::model::error::Result<LeaveGroupResponse> {
         let request = LeaveGroupRequest { group_id };
-        let request = common::Request {
-            cmd: "GroupService.LeaveGroup".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let leave_group_response = LeaveGroupResponse::decode(buf).unwrap();
-        Ok(leave_group_response)
+        self.group_leave_group(request).await
     }
 
     async fn modify_member_card(
@@ -353,16 +293,7 @@ impl GroupAPITrait for Bot {
             target: Some(target),
             card,
         };
-        let request = common::Request {
-            cmd: "GroupService.ModifyMemberCard".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let modify_member_card_response = ModifyMemberCardResponse::decode(buf).unwrap();
-        Ok(modify_member_card_response)
+        self.group_modify_member_card(request).await
     }
 
     async fn modify_group_name(
@@ -374,16 +305,7 @@ impl GroupAPITrait for Bot {
             group_id,
             group_name,
         };
-        let request = common::Request {
-            cmd: "GroupService.ModifyGroupName".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let modify_group_name_response = ModifyGroupNameResponse::decode(buf).unwrap();
-        Ok(modify_group_name_response)
+        self.group_modify_group_name(request).await
     }
 
     async fn modify_group_remark(
@@ -392,16 +314,7 @@ impl GroupAPITrait for Bot {
         remark: String,
     ) -> crate::model::error::Result<ModifyGroupRemarkResponse> {
         let request = ModifyGroupRemarkRequest { group_id, remark };
-        let request = common::Request {
-            cmd: "GroupService.ModifyGroupRemark".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let modify_group_remark_response = ModifyGroupRemarkResponse::decode(buf).unwrap();
-        Ok(modify_group_remark_response)
+        self.group_modify_group_remark(request).await
     }
 
     async fn set_group_admin(
@@ -415,16 +328,7 @@ impl GroupAPITrait for Bot {
             target: Some(target),
             is_admin,
         };
-        let request = common::Request {
-            cmd: "GroupService.SetGroupAdmin".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let set_group_admin_response = SetGroupAdminResponse::decode(buf).unwrap();
-        Ok(set_group_admin_response)
+        self.group_set_group_admin(request).await
     }
 
     async fn set_group_unique_title(
@@ -438,16 +342,7 @@ impl GroupAPITrait for Bot {
             target: Some(target),
             unique_title,
         };
-        let request = common::Request {
-            cmd: "GroupService.SetGroupUniqueTitle".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let set_group_unique_title_response = SetGroupUniqueTitleResponse::decode(buf).unwrap();
-        Ok(set_group_unique_title_response)
+        self.group_set_group_unique_title(request).await
     }
 
     async fn set_group_whole_ban(
@@ -456,16 +351,7 @@ impl GroupAPITrait for Bot {
         is_ban: bool,
     ) -> crate::model::error::Result<SetGroupWholeBanResponse> {
         let request = SetGroupWholeBanRequest { group_id, is_ban };
-        let request = common::Request {
-            cmd: "GroupService.SetGroupWholeBan".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let set_group_whole_ban_response = SetGroupWholeBanResponse::decode(buf).unwrap();
-        Ok(set_group_whole_ban_response)
+        self.group_set_group_whole_ban(request).await
     }
 
     async fn get_prohibited_user_list(
@@ -473,16 +359,7 @@ impl GroupAPITrait for Bot {
         group_id: u64,
     ) -> crate::model::error::Result<GetProhibitedUserListResponse> {
         let request = GetProhibitedUserListRequest { group_id };
-        let request = common::Request {
-            cmd: "GroupService.GetProhibitedUserList".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let get_prohibited_user_list_response = GetProhibitedUserListResponse::decode(buf).unwrap();
-        Ok(get_prohibited_user_list_response)
+        self.group_get_prohibited_user_list(request).await
     }
 
     async fn get_remain_count_at_all(
@@ -490,16 +367,7 @@ impl GroupAPITrait for Bot {
         group_id: u64,
     ) -> crate::model::error::Result<GetRemainCountAtAllResponse> {
         let request = GetRemainCountAtAllRequest { group_id };
-        let request = common::Request {
-            cmd: "GroupService.GetRemainCountAtAll".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let get_remain_count_at_all_response = GetRemainCountAtAllResponse::decode(buf).unwrap();
-        Ok(get_remain_count_at_all_response)
+        self.group_get_remain_count_at_all(request).await
     }
 
     async fn get_not_joined_group_info(
@@ -507,17 +375,7 @@ impl GroupAPITrait for Bot {
         group_id: u64,
     ) -> crate::model::error::Result<GetNotJoinedGroupInfoResponse> {
         let request = GetNotJoinedGroupInfoRequest { group_id };
-        let request = common::Request {
-            cmd: "GroupService.GetNotJoinedGroupInfo".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let get_not_joined_group_info_response =
-            GetNotJoinedGroupInfoResponse::decode(buf).unwrap();
-        Ok(get_not_joined_group_info_response)
+        self.group_get_not_joined_group_info(request).await
     }
 
     async fn get_group_honor_info(
@@ -529,15 +387,6 @@ impl GroupAPITrait for Bot {
             group_id,
             refresh: Some(refresh),
         };
-        let request = common::Request {
-            cmd: "GroupService.GetGroupHonorInfo".to_string(),
-            seq: crate::bot::bot::get_seq(),
-            buf: request.encode_to_vec(),
-            no_response: false,
-        };
-        let response = self.send_request(request).await.expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let get_group_honor_response = GetGroupHonorResponse::decode(buf).unwrap();
-        Ok(get_group_honor_response)
+        self.group_get_group_honor(request).await
     }
 }
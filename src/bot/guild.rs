@@ -0,0 +1,142 @@
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::common::{Contact, Element, Scene};
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+use async_trait::async_trait;
+
+/// 频道的contact，peer为频道id，sub_peer为子频道id
+pub fn guild_contact(guild_id: u64, channel_id: u64) -> Contact {
+    Contact {
+        scene: Scene::Guild.into(),
+        peer: guild_id.to_string(),
+        sub_peer: Some(channel_id.to_string()),
+    }
+}
+
+#[async_trait]
+pub trait GuildAPITrait {
+    async fn get_bot_info(&self) -> Result<GetBotInfoResponse>;
+
+    /// 获取已加入的频道列表
+    async fn get_guild_list(&self) -> Result<GetChannelListResponse>;
+
+    async fn get_guild_channel_list(
+        &self,
+        guild_id: u64,
+        refresh: bool,
+    ) -> Result<GetGuildChannelListResponse>;
+
+    /// 分页获取频道成员，next_token为空时从第一页开始
+    async fn get_guild_member_list(
+        &self,
+        guild_id: u64,
+        next_token: String,
+        all: bool,
+        refresh: bool,
+    ) -> Result<GetGuildMemberListResponse>;
+
+    async fn get_guild_member(
+        &self,
+        guild_id: u64,
+        tiny_id: u64,
+        refresh: bool,
+    ) -> Result<GetGuildMemberResponse>;
+
+    async fn get_guild_role_list(&self, guild_id: u64) -> Result<GetGuildRoleListResponse>;
+
+    async fn set_guild_member_role(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+        set: bool,
+        tiny_ids: Vec<String>,
+    ) -> Result<SetGuildMemberRoleResponse>;
+
+    async fn send_channel_msg(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        segments: Vec<Element>,
+    ) -> Result<SendMessageResponse>;
+}
+
+#[async_trait]
+impl GuildAPITrait for Bot {
+    async fn get_bot_info(&self) -> Result<GetBotInfoResponse> {
+        self.guild_get_bot_info(GetBotInfoRequest {}).await
+    }
+
+    async fn get_guild_list(&self) -> Result<GetChannelListResponse> {
+        self.guild_get_channel_list(GetChannelListRequest {}).await
+    }
+
+    async fn get_guild_channel_list(
+        &self,
+        guild_id: u64,
+        refresh: bool,
+    ) -> Result<GetGuildChannelListResponse> {
+        let request = GetGuildChannelListRequest { guild_id, refresh };
+        self.guild_get_guild_channel_list(request).await
+    }
+
+    async fn get_guild_member_list(
+        &self,
+        guild_id: u64,
+        next_token: String,
+        all: bool,
+        refresh: bool,
+    ) -> Result<GetGuildMemberListResponse> {
+        let request = GetGuildMemberListRequest {
+            guild_id,
+            next_token,
+            all,
+            refresh,
+        };
+        self.guild_get_guild_member_list(request).await
+    }
+
+    async fn get_guild_member(
+        &self,
+        guild_id: u64,
+        tiny_id: u64,
+        refresh: bool,
+    ) -> Result<GetGuildMemberResponse> {
+        let request = GetGuildMemberRequest {
+            guild_id,
+            tiny_id,
+            refresh,
+        };
+        self.guild_get_guild_member(request).await
+    }
+
+    async fn get_guild_role_list(&self, guild_id: u64) -> Result<GetGuildRoleListResponse> {
+        let request = GetGuildRoleListRequest { guild_id };
+        self.guild_get_guild_role_list(request).await
+    }
+
+    async fn set_guild_member_role(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+        set: bool,
+        tiny_ids: Vec<String>,
+    ) -> Result<SetGuildMemberRoleResponse> {
+        let request = SetGuildMemberRoleRequest {
+            guild_id,
+            role_id,
+            set,
+            tiny_ids,
+        };
+        self.guild_set_guild_member_role(request).await
+    }
+
+    async fn send_channel_msg(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        segments: Vec<Element>,
+    ) -> Result<SendMessageResponse> {
+        self.send_msg(segments, guild_contact(guild_id, channel_id))
+            .await
+    }
+}
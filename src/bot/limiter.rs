@@ -0,0 +1,204 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+use crate::kritor::server::kritor_proto::common::Contact;
+use crate::model::config::SendConfig;
+
+/// 有优先消息等待时，普通消息重新检查的间隔
+const YIELD_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 会话超过该时间没有发送消息时，清理其令牌桶与排队锁
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// 清理空闲会话的间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 令牌桶，rate为每秒补充的令牌数，rate为0时不限速
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            capacity: burst.max(1) as f64,
+            tokens: burst.max(1) as f64,
+            last: Instant::now(),
+        }
+    }
+
+    /// 配置热更新时调整速率，已有的令牌不超过新容量
+    fn configure(&mut self, rate: f64, burst: u32) {
+        self.rate = rate;
+        self.capacity = burst.max(1) as f64;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+    }
+
+    /// 距离有一个可用令牌还需等待的时间
+    pub fn wait_time(&mut self) -> Duration {
+        if self.rate <= 0.0 {
+            return Duration::ZERO;
+        }
+        self.refill();
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+
+    /// 取走一个令牌，令牌不足时只取到0，不透支之后的额度
+    pub fn take(&mut self) {
+        if self.rate > 0.0 {
+            self.refill();
+            self.tokens = (self.tokens - 1.0).max(0.0);
+        }
+    }
+
+    /// 令牌已补满且超过idle没有使用，此时重新创建与保留没有区别
+    fn is_idle(&self, idle: Duration) -> bool {
+        self.last.elapsed() >= idle
+            && (self.rate <= 0.0
+                || self.tokens + self.last.elapsed().as_secs_f64() * self.rate >= self.capacity)
+    }
+}
+
+/// 发送调度，按Bot和会话两级令牌桶限速
+///
+/// 同一会话的消息按顺序排队，主人与管理员的回复走优先通道，只受Bot级限速
+#[derive(Debug)]
+pub struct SendScheduler {
+    bot_bucket: Mutex<TokenBucket>,
+    contact_buckets: DashMap<String, TokenBucket>,
+    contact_lanes: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    priority_waiting: AtomicUsize,
+    last_sweep: Mutex<Instant>,
+}
+
+struct PriorityGuard<'a>(&'a AtomicUsize);
+
+impl Drop for PriorityGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn contact_key(contact: &Contact) -> String {
+    format!(
+        "{}:{}:{}",
+        contact.scene,
+        contact.peer,
+        contact.sub_peer.as_deref().unwrap_or_default()
+    )
+}
+
+impl Default for SendScheduler {
+    fn default() -> Self {
+        let config = SendConfig::default();
+        Self {
+            bot_bucket: Mutex::new(TokenBucket::new(config.bot_rate(), config.bot_burst())),
+            contact_buckets: DashMap::new(),
+            contact_lanes: DashMap::new(),
+            priority_waiting: AtomicUsize::new(0),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl SendScheduler {
+    /// 等待直到可以向contact发送一条消息
+    pub async fn acquire(&self, contact: &Contact, priority: bool, config: &SendConfig) {
+        self.sweep_if_due();
+        let key = contact_key(contact);
+        if priority {
+            self.priority_waiting.fetch_add(1, Ordering::SeqCst);
+            let _guard = PriorityGuard(&self.priority_waiting);
+            self.wait_tokens(&key, true, config).await;
+            return;
+        }
+        let lane = self
+            .contact_lanes
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let _lane = lane.lock().await;
+        self.wait_tokens(&key, false, config).await;
+    }
+
+    /// 距离上次清理超过SWEEP_INTERVAL时清理空闲会话
+    fn sweep_if_due(&self) {
+        {
+            let mut last_sweep = self.last_sweep.lock().unwrap();
+            if last_sweep.elapsed() < SWEEP_INTERVAL {
+                return;
+            }
+            *last_sweep = Instant::now();
+        }
+        self.sweep(IDLE_TIMEOUT);
+    }
+
+    /// 清理超过idle没有发送的会话，正在排队的会话不会被清理
+    pub fn sweep(&self, idle: Duration) {
+        self.contact_buckets
+            .retain(|_, bucket| !bucket.is_idle(idle));
+        // 只有这里和调度持有锁时引用计数大于1
+        self.contact_lanes.retain(|key, lane| {
+            Arc::strong_count(lane) > 1 || self.contact_buckets.contains_key(key)
+        });
+    }
+
+    /// 当前缓存的会话数，用于检查清理是否生效
+    pub fn contact_count(&self) -> usize {
+        self.contact_lanes.len().max(self.contact_buckets.len())
+    }
+
+    async fn wait_tokens(&self, key: &str, priority: bool, config: &SendConfig) {
+        loop {
+            if !priority && self.priority_waiting.load(Ordering::SeqCst) > 0 {
+                tokio::time::sleep(YIELD_INTERVAL).await;
+                continue;
+            }
+            let wait = {
+                let mut bot_bucket = self.bot_bucket.lock().unwrap();
+                bot_bucket.configure(config.bot_rate(), config.bot_burst());
+                let mut contact_bucket = self
+                    .contact_buckets
+                    .entry(key.to_string())
+                    .or_insert_with(|| {
+                        TokenBucket::new(config.contact_rate(), config.contact_burst())
+                    });
+                contact_bucket.configure(config.contact_rate(), config.contact_burst());
+                let wait = if priority {
+                    bot_bucket.wait_time()
+                } else {
+                    bot_bucket.wait_time().max(contact_bucket.wait_time())
+                };
+                if wait.is_zero() {
+                    bot_bucket.take();
+                    // 优先消息同样计入会话的用量，但不因此等待，令牌最多用到0
+                    contact_bucket.take();
+                }
+                wait
+            };
+            if wait.is_zero() {
+                return;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
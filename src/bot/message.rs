@@ -3,8 +3,6 @@ use crate::kritor::server::kritor_proto::common::{Contact, Element, ForwardMessa
 use crate::kritor::server::kritor_proto::*;
 use crate::model::error::Result;
 use async_trait::async_trait;
-use bytes::Bytes;
-use prost::Message;
 
 #[async_trait]
 pub trait MessageAPITrait {
@@ -97,26 +95,8 @@ impl MessageAPITrait for Bot {
         segments: Vec<Element>,
         contact: Contact,
     ) -> Result<SendMessageResponse> {
-        let request = SendMessageRequest {
-            contact: Some(contact),
-            elements: segments,
-            retry_count: None,
-            message_id: None,
-            notice_id: None,
-            request_id: None,
-        };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.SendMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = SendMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        // 与Bot::send_msg一致，经过限速队列
+        Bot::send_msg(self, segments, contact).await
     }
 
     async fn send_msg_by_res_id(
@@ -129,36 +109,14 @@ impl MessageAPITrait for Bot {
             res_id: res_id,
             retry_count: None,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.SendMessageByResIdRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = SendMessageByResIdResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_send_message_by_res_id(request).await
     }
 
     async fn set_message_readed(&self, contact: Contact) -> Result<SetMessageReadResponse> {
         let request = SetMessageReadRequest {
             contact: Some(contact),
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.SetMessageReadRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = SetMessageReadResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_set_message_read(request).await
     }
 
     async fn recall_message(
@@ -170,18 +128,7 @@ impl MessageAPITrait for Bot {
             contact: Some(contact),
             message_id: message_id,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.RecallMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = RecallMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_recall_message(request).await
     }
 
     async fn react_message_with_emoji(
@@ -197,18 +144,7 @@ impl MessageAPITrait for Bot {
             face_id: face_id,
             is_set: is_set,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.ReactMessageWithEmojiRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = ReactMessageWithEmojiResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_react_message_with_emoji(request).await
     }
 
     async fn get_message(
@@ -220,18 +156,7 @@ impl MessageAPITrait for Bot {
             contact: Some(contact),
             message_id: message_id,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.GetMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_get_message(request).await
     }
 
     async fn get_message_by_seq(
@@ -243,18 +168,7 @@ impl MessageAPITrait for Bot {
             contact: Some(contact),
             message_seq: message_seq,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.GetMessageBySeqRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetMessageBySeqResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_get_message_by_seq(request).await
     }
 
     async fn get_history_message(
@@ -268,18 +182,7 @@ impl MessageAPITrait for Bot {
             start_message_id: start_message_id,
             count: count,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.GetHistoryMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetHistoryMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_get_history_message(request).await
     }
 
     async fn get_history_message_by_seq(
@@ -293,18 +196,7 @@ impl MessageAPITrait for Bot {
             start_message_seq: start_message_seq,
             count: count,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.GetHistoryMessageBySeqRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetHistoryMessageBySeqResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_get_history_message_by_seq(request).await
     }
 
     async fn upload_forward_message(
@@ -317,18 +209,7 @@ impl MessageAPITrait for Bot {
             messages: messages,
             retry_count: None,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.UploadForwardMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = UploadForwardMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_upload_forward_message(request).await
     }
 
     async fn download_forward_message(
@@ -336,18 +217,7 @@ impl MessageAPITrait for Bot {
         res_id: String,
     ) -> Result<DownloadForwardMessageResponse> {
         let request = DownloadForwardMessageRequest { res_id: res_id };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.DownloadForwardMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = DownloadForwardMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_download_forward_message(request).await
     }
 
     async fn get_essence_message_list(
@@ -361,18 +231,7 @@ impl MessageAPITrait for Bot {
             page: page,
             page_size: page_size,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.GetEssenceMessageListRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = GetEssenceMessageListResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_get_essence_message_list(request).await
     }
 
     async fn set_essence_message(
@@ -384,18 +243,7 @@ impl MessageAPITrait for Bot {
             group_id: group_id,
             message_id: message_id,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.SetEssenceMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = SetEssenceMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_set_essence_message(request).await
     }
 
     async fn delete_essence_message(
@@ -407,17 +255,6 @@ impl MessageAPITrait for Bot {
             group_id: group_id,
             message_id: message_id,
         };
-        let response = self
-            .send_request(common::Request {
-                cmd: "MessageService.DeleteEssenceMessageRequest".to_string(),
-                seq: crate::bot::bot::get_seq(),
-                buf: request.encode_to_vec(),
-                no_response: false,
-            })
-            .await
-            .expect("send error");
-        let buf: Bytes = response.buf.clone().into();
-        let response = DeleteEssenceMessageResponse::decode(buf).unwrap();
-        Ok(response)
+        self.message_delete_essence_message(request).await
     }
 }
@@ -1,5 +1,14 @@
+pub mod api;
 pub mod bot;
+pub mod cache;
+pub mod channel;
 pub mod core;
+pub mod developer;
+pub mod file;
 pub mod friend;
 pub mod group;
+pub mod limiter;
+pub mod guild;
 pub mod message;
+pub mod process;
+pub mod web;
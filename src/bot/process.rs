@@ -0,0 +1,171 @@
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::request_event::Request;
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+use async_trait::async_trait;
+use log::warn;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// 请求事件的种类，决定使用哪个接口处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    FriendApply,
+    GroupApply,
+    InvitedGroup,
+}
+
+impl RequestKind {
+    pub fn of(event: &RequestEvent) -> Option<Self> {
+        match event.request.as_ref()? {
+            Request::FriendApply(_) => Some(RequestKind::FriendApply),
+            Request::GroupApply(_) => Some(RequestKind::GroupApply),
+            Request::InvitedGroup(_) => Some(RequestKind::InvitedGroup),
+        }
+    }
+
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            RequestKind::FriendApply => "FRIEND_APPLY",
+            RequestKind::GroupApply => "GROUP_APPLY",
+            RequestKind::InvitedGroup => "INVITED_GROUP",
+        }
+    }
+
+    pub fn from_str_name(name: &str) -> Option<Self> {
+        match name {
+            "FRIEND_APPLY" => Some(RequestKind::FriendApply),
+            "GROUP_APPLY" => Some(RequestKind::GroupApply),
+            "INVITED_GROUP" => Some(RequestKind::InvitedGroup),
+            _ => None,
+        }
+    }
+}
+
+#[async_trait]
+pub trait ProcessAPITrait {
+    async fn set_friend_apply_result(
+        &self,
+        request_id: String,
+        is_approve: bool,
+        remark: Option<String>,
+    ) -> Result<SetFriendApplyResultResponse>;
+
+    async fn set_group_apply_result(
+        &self,
+        request_id: String,
+        is_approve: bool,
+        deny_reason: Option<String>,
+    ) -> Result<SetGroupApplyResultResponse>;
+
+    async fn set_invited_join_group_result(
+        &self,
+        request_id: String,
+        is_approve: bool,
+    ) -> Result<SetInvitedJoinGroupResultResponse>;
+
+    /// 按请求种类处理，reason在同意好友申请时作为备注，在拒绝加群申请时作为理由
+    async fn set_request_result(
+        &self,
+        kind: RequestKind,
+        request_id: String,
+        is_approve: bool,
+        reason: Option<String>,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl ProcessAPITrait for Bot {
+    async fn set_friend_apply_result(
+        &self,
+        request_id: String,
+        is_approve: bool,
+        remark: Option<String>,
+    ) -> Result<SetFriendApplyResultResponse> {
+        let request = SetFriendApplyResultRequest {
+            request_id,
+            is_approve,
+            remark,
+        };
+        self.process_set_friend_apply_result(request).await
+    }
+
+    async fn set_group_apply_result(
+        &self,
+        request_id: String,
+        is_approve: bool,
+        deny_reason: Option<String>,
+    ) -> Result<SetGroupApplyResultResponse> {
+        let request = SetGroupApplyResultRequest {
+            request_id,
+            is_approve,
+            deny_reason,
+        };
+        self.process_set_group_apply_result(request).await
+    }
+
+    async fn set_invited_join_group_result(
+        &self,
+        request_id: String,
+        is_approve: bool,
+    ) -> Result<SetInvitedJoinGroupResultResponse> {
+        let request = SetInvitedJoinGroupResultRequest {
+            request_id,
+            is_approve,
+        };
+        self.process_set_invited_join_group_result(request).await
+    }
+
+    async fn set_request_result(
+        &self,
+        kind: RequestKind,
+        request_id: String,
+        is_approve: bool,
+        reason: Option<String>,
+    ) -> Result<()> {
+        match kind {
+            RequestKind::FriendApply => {
+                let remark = reason.filter(|_| is_approve);
+                self.set_friend_apply_result(request_id, is_approve, remark)
+                    .await?;
+            }
+            RequestKind::GroupApply => {
+                let deny_reason = reason.filter(|_| !is_approve);
+                self.set_group_apply_result(request_id, is_approve, deny_reason)
+                    .await?;
+            }
+            RequestKind::InvitedGroup => {
+                self.set_invited_join_group_result(request_id, is_approve)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Bot {
+    /// 处理请求事件，Rust与JS的approve、reject共用
+    pub async fn settle_request(
+        bot: &Arc<RwLock<Bot>>,
+        kind: RequestKind,
+        request_id: String,
+        is_approve: bool,
+        reason: Option<String>,
+    ) -> Result<()> {
+        {
+            let bot = bot.read().await;
+            bot.set_request_result(kind, request_id, is_approve, reason)
+                .await?;
+        }
+        // Kritor端没有新增好友的通知，同意后重新获取好友列表
+        if is_approve && kind == RequestKind::FriendApply {
+            let bot = bot.clone();
+            tokio::spawn(async move {
+                if let Err(e) = bot.read().await.refresh_friends().await {
+                    warn!("Failed to refresh friends: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use reqwest::cookie::Jar;
+
+use crate::bot::bot::Bot;
+use crate::client_err;
+use crate::kritor::server::kritor_proto::*;
+use crate::model::error::Result;
+
+/// 凭证的缓存时间，过期后重新向Kritor端获取
+const CACHE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// 某个域名下的cookie和bkn（即csrf token）
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub cookie: String,
+    pub bkn: u32,
+}
+
+/// 按 `种类:域名` 缓存的凭证
+#[derive(Debug, Default)]
+pub struct WebCache {
+    entries: DashMap<String, (Instant, Credentials)>,
+}
+
+impl WebCache {
+    fn get(&self, key: &str) -> Option<Credentials> {
+        let entry = self.entries.get(key)?;
+        let (time, credentials) = entry.value();
+        (time.elapsed() < CACHE_TTL).then(|| credentials.clone())
+    }
+
+    fn insert(&self, key: String, credentials: Credentials) {
+        self.entries.insert(key, (Instant::now(), credentials));
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+#[async_trait]
+pub trait WebAPITrait {
+    async fn get_cookies(&self, domain: &str) -> Result<String>;
+
+    async fn get_csrf_token(&self, domain: &str) -> Result<u32>;
+
+    async fn get_credentials(&self, domain: &str) -> Result<Credentials>;
+
+    /// 通过appid和daid换取的cookie，不做缓存
+    async fn get_http_cookies(
+        &self,
+        appid: String,
+        daid: String,
+        jump_url: String,
+    ) -> Result<String>;
+
+    /// 清空缓存的凭证，cookie失效时调用
+    fn clear_web_cache(&self);
+
+    /// 带有该域名cookie的http客户端
+    async fn web_client(&self, domain: &str) -> Result<reqwest::Client>;
+}
+
+#[async_trait]
+impl WebAPITrait for Bot {
+    async fn get_cookies(&self, domain: &str) -> Result<String> {
+        let key = format!("cookies:{}", domain);
+        if let Some(credentials) = self.get_web_cache().get(&key) {
+            return Ok(credentials.cookie);
+        }
+        let request = GetCookiesRequest {
+            domain: Some(domain.to_string()),
+        };
+        let response: GetCookiesResponse = self.web_get_cookies(request).await?;
+        let credentials = Credentials {
+            cookie: response.cookie,
+            ..Default::default()
+        };
+        self.get_web_cache().insert(key, credentials.clone());
+        Ok(credentials.cookie)
+    }
+
+    async fn get_csrf_token(&self, domain: &str) -> Result<u32> {
+        let key = format!("csrf:{}", domain);
+        if let Some(credentials) = self.get_web_cache().get(&key) {
+            return Ok(credentials.bkn);
+        }
+        let request = GetCsrfTokenRequest {
+            domain: Some(domain.to_string()),
+        };
+        let response: GetCsrfTokenResponse = self.web_get_csrf_token(request).await?;
+        let credentials = Credentials {
+            bkn: response.bkn,
+            ..Default::default()
+        };
+        self.get_web_cache().insert(key, credentials.clone());
+        Ok(credentials.bkn)
+    }
+
+    async fn get_credentials(&self, domain: &str) -> Result<Credentials> {
+        let key = format!("credentials:{}", domain);
+        if let Some(credentials) = self.get_web_cache().get(&key) {
+            return Ok(credentials);
+        }
+        let request = GetCredentialsRequest {
+            domain: Some(domain.to_string()),
+        };
+        let response: GetCredentialsResponse = self.web_get_credentials(request).await?;
+        let credentials = Credentials {
+            cookie: response.cookie,
+            bkn: response.bkn,
+        };
+        self.get_web_cache().insert(key, credentials.clone());
+        Ok(credentials)
+    }
+
+    async fn get_http_cookies(
+        &self,
+        appid: String,
+        daid: String,
+        jump_url: String,
+    ) -> Result<String> {
+        let request = GetHttpCookiesRequest {
+            appid,
+            daid,
+            jump_url,
+        };
+        let response: GetHttpCookiesResponse = self.web_get_http_cookies(request).await?;
+        Ok(response.cookie)
+    }
+
+    fn clear_web_cache(&self) {
+        self.get_web_cache().clear();
+    }
+
+    async fn web_client(&self, domain: &str) -> Result<reqwest::Client> {
+        let cookie = self.get_cookies(domain).await?;
+        let Ok(url) = format!("https://{}", domain).parse::<reqwest::Url>() else {
+            return client_err!("invalid domain: {}", domain);
+        };
+        let jar = Jar::default();
+        for pair in cookie.split(';').map(str::trim).filter(|pair| !pair.is_empty()) {
+            jar.add_cookie_str(&format!("{}; Domain={}; Path=/", pair, domain), &url);
+        }
+        Ok(reqwest::Client::builder()
+            .cookie_provider(Arc::new(jar))
+            .build()?)
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use tonic::metadata::MetadataMap;
+use tonic::Status;
+
+use crate::model::config::TicketConfig;
+
+/// Kritor端连接时在metadata中携带的身份信息
+#[derive(Debug, Clone, PartialEq)]
+pub struct KritorIdentity {
+    pub uid: String,
+    pub uin: u64,
+    pub version: Option<String>,
+}
+
+fn get_metadata<'a>(metadata: &'a MetadataMap, key: &str) -> Result<Option<&'a str>, Status> {
+    match metadata.get(key) {
+        Some(value) => value
+            .to_str()
+            .map(Some)
+            .map_err(|_| Status::invalid_argument(format!("invalid metadata: {}", key))),
+        None => Ok(None),
+    }
+}
+
+/// 从metadata中读取ticket，优先使用authorization，兼容 Bearer 前缀
+pub fn get_ticket(metadata: &MetadataMap) -> Result<Option<String>, Status> {
+    if let Some(authorization) = get_metadata(metadata, "authorization")? {
+        let authorization = authorization.trim();
+        let ticket = authorization
+            .strip_prefix("Bearer ")
+            .unwrap_or(authorization)
+            .trim();
+        return Ok(Some(ticket.to_string()));
+    }
+    Ok(get_metadata(metadata, "ticket")?.map(|ticket| ticket.to_string()))
+}
+
+/// 校验ticket，未配置tickets时不做鉴权
+///
+/// ticket只对作为键的uid有效，uid同时是BOTS的键；账号配置了uin时，
+/// 连接必须携带一致的uin，避免用一个账号的ticket冒充其他账号
+pub fn verify_ticket(
+    metadata: &MetadataMap,
+    identity: &KritorIdentity,
+    tickets: Option<&HashMap<String, TicketConfig>>,
+) -> Result<(), Status> {
+    let tickets = match tickets {
+        Some(tickets) => tickets,
+        None => return Ok(()),
+    };
+    let account = tickets.get(&identity.uid).ok_or_else(|| {
+        Status::unauthenticated(format!("account {} is not allowed", identity.uid))
+    })?;
+    if let Some(uin) = account.uin() {
+        // 未携带uin时为0，同样拒绝
+        if identity.uin == 0 {
+            return Err(Status::unauthenticated(format!(
+                "missing kritor-self-uin for account {}",
+                identity.uid
+            )));
+        }
+        if identity.uin != uin {
+            return Err(Status::unauthenticated(format!(
+                "uin {} does not match account {}",
+                identity.uin, identity.uid
+            )));
+        }
+    }
+    let ticket = get_ticket(metadata)?.ok_or_else(|| Status::unauthenticated("missing ticket"))?;
+    if account.tickets().contains(&ticket) {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("invalid ticket"))
+    }
+}
+
+/// 解析连接的身份并校验ticket
+pub fn authenticate(
+    metadata: &MetadataMap,
+    tickets: Option<&HashMap<String, TicketConfig>>,
+) -> Result<KritorIdentity, Status> {
+    let uid = get_metadata(metadata, "kritor-self-uid")?
+        .filter(|uid| !uid.is_empty())
+        .ok_or_else(|| Status::invalid_argument("missing kritor-self-uid"))?
+        .to_string();
+    let uin = match get_metadata(metadata, "kritor-self-uin")? {
+        Some(uin) => uin
+            .parse()
+            .map_err(|_| Status::invalid_argument("invalid kritor-self-uin"))?,
+        None => 0,
+    };
+    let version = get_metadata(metadata, "kritor-self-version")?.map(|v| v.to_string());
+    let identity = KritorIdentity { uid, uin, version };
+    verify_ticket(metadata, &identity, tickets)?;
+    Ok(identity)
+}
@@ -0,0 +1,264 @@
+use crate::bot::bot::Bot;
+use crate::kritor::server::kritor_proto::event_service_client::EventServiceClient;
+use crate::kritor::server::kritor_proto::{
+    common, EventType, GetCurrentAccountRequest, GetCurrentAccountResponse, GetVersionRequest,
+    GetVersionResponse, RequestPushEvent,
+};
+use crate::kritor::server::get_or_create_bot;
+use crate::model::config::ActiveConfig;
+use crate::model::error::Result;
+use crate::{err, kritor_err};
+use bytes::{Buf, BufMut};
+use log::{debug, error, info, warn};
+use prost::Message;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tokio_stream::StreamExt;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+
+/// 透传protobuf字节的codec，common::Request中的buf已经是编码好的请求体
+#[derive(Debug, Clone, Default)]
+struct RawCodec;
+
+#[derive(Debug, Clone, Default)]
+struct RawEncoder;
+
+#[derive(Debug, Clone, Default)]
+struct RawDecoder;
+
+impl Codec for RawCodec {
+    type Encode = Vec<u8>;
+    type Decode = Vec<u8>;
+    type Encoder = RawEncoder;
+    type Decoder = RawDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        RawEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        RawDecoder
+    }
+}
+
+impl Encoder for RawEncoder {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> std::result::Result<(), Status> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for RawDecoder {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> std::result::Result<Option<Vec<u8>>, Status> {
+        let mut buf = vec![0; src.remaining()];
+        src.copy_to_slice(&mut buf);
+        Ok(Some(buf))
+    }
+}
+
+/// cmd所属的proto package，cmd格式为 Service.Method
+fn service_package(service: &str) -> Option<&'static str> {
+    match service {
+        "AuthenticationService" => Some("kritor.authentication"),
+        "CoreService" => Some("kritor.core"),
+        "DeveloperService" | "QsignService" => Some("kritor.developer"),
+        "GroupFileService" => Some("kritor.file"),
+        "FriendService" => Some("kritor.friend"),
+        "GroupService" => Some("kritor.group"),
+        "GuildService" => Some("kritor.guild"),
+        "MessageService" => Some("kritor.message"),
+        "ProcessService" => Some("kritor.process"),
+        "WebService" => Some("kritor.web"),
+        _ => None,
+    }
+}
+
+/// 将 Service.Method 形式的cmd转换为gRPC路径
+///
+/// 兼容 Service.MethodRequest 形式的cmd，方法名去掉Request后缀
+pub(crate) fn cmd_to_path(cmd: &str) -> Result<PathAndQuery> {
+    let (service, method) = match cmd.split_once('.') {
+        Some(pair) => pair,
+        None => return err!("invalid cmd: {}", cmd),
+    };
+    let method = method.strip_suffix("Request").unwrap_or(method);
+    let package = match service_package(service) {
+        Some(package) => package,
+        None => return err!("unknown service: {}", service),
+    };
+    PathAndQuery::from_str(&format!("/{}.{}/{}", package, service, method))
+        .or_else(|e| err!("invalid cmd {}: {}", cmd, e))
+}
+
+fn with_ticket<T>(message: T, ticket: &Option<String>) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Some(ticket) = ticket {
+        if let Ok(value) = ticket.parse() {
+            request.metadata_mut().insert("ticket", value);
+        }
+    }
+    request
+}
+
+/// 以unary方式调用Kritor端的接口，请求与响应均为编码后的字节
+async fn raw_unary(
+    channel: Channel,
+    ticket: &Option<String>,
+    cmd: &str,
+    buf: Vec<u8>,
+) -> std::result::Result<Vec<u8>, Status> {
+    let path = cmd_to_path(cmd).map_err(|e| Status::invalid_argument(e.error()))?;
+    let mut grpc = tonic::client::Grpc::new(channel);
+    grpc.ready()
+        .await
+        .map_err(|e| Status::unavailable(format!("Service was not ready: {}", e)))?;
+    let response = grpc
+        .unary(with_ticket(buf, ticket), path, RawCodec)
+        .await?;
+    Ok(response.into_inner())
+}
+
+/// 将Bot发出的common::Request转换为对Kritor端的gRPC调用
+async fn forward_request(
+    channel: Channel,
+    ticket: Option<String>,
+    request: common::Request,
+) -> common::Response {
+    let result = raw_unary(channel, &ticket, &request.cmd, request.buf).await;
+    match result {
+        Ok(buf) => common::Response {
+            cmd: request.cmd,
+            seq: request.seq,
+            code: 0,
+            msg: None,
+            buf,
+        },
+        Err(status) => {
+            warn!("request {} failed: {}", request.cmd, status);
+            // 非0即失败，沿用gRPC状态码
+            common::Response {
+                cmd: request.cmd,
+                seq: request.seq,
+                code: status.code() as i32,
+                msg: Some(status.message().to_string()),
+                buf: vec![],
+            }
+        }
+    }
+}
+
+async fn listen_active_events(
+    channel: Channel,
+    ticket: Option<String>,
+    event_type: EventType,
+    bot: Arc<RwLock<Bot>>,
+) -> Result<()> {
+    let mut client = EventServiceClient::new(channel);
+    let request = with_ticket(
+        RequestPushEvent {
+            r#type: event_type.into(),
+        },
+        &ticket,
+    );
+    let mut stream = client.register_active_listener(request).await?.into_inner();
+    info!("Active listener registered: {:?}", event_type);
+    while let Some(event) = stream.next().await {
+        let event = event?;
+        debug!("Received event: {:?}", event);
+        if let Some(event) = event.event {
+            Bot::push_event(&bot, event).await;
+        }
+    }
+    kritor_err!("event stream {:?} ended", event_type)
+}
+
+/// 连接一次Kritor端，直到连接断开才返回
+async fn run_active(config: &ActiveConfig) -> Result<()> {
+    let channel = Endpoint::from_shared(config.address.clone())?
+        .connect()
+        .await?;
+    info!("Connected to kritor: {}", config.address);
+    let ticket = config.ticket.clone();
+
+    let account = raw_unary(
+        channel.clone(),
+        &ticket,
+        "CoreService.GetCurrentAccount",
+        GetCurrentAccountRequest {}.encode_to_vec(),
+    )
+    .await?;
+    let account = GetCurrentAccountResponse::decode(account.as_slice())?;
+    let version = raw_unary(
+        channel.clone(),
+        &ticket,
+        "CoreService.GetVersion",
+        GetVersionRequest {}.encode_to_vec(),
+    )
+    .await
+    .ok()
+    .and_then(|buf| GetVersionResponse::decode(buf.as_slice()).ok())
+    .map(|version| version.version);
+
+    let (tx, mut rx) = mpsc::channel(4096);
+    let (bot, connection) =
+        get_or_create_bot(account.account_uin, account.account_uid, tx, version).await;
+
+    let forward_channel = channel.clone();
+    let forward_ticket = ticket.clone();
+    let forward_bot = bot.clone();
+    let forward = tokio::spawn(async move {
+        while let Some(request) = rx.recv().await {
+            let Ok(request) = request else {
+                continue;
+            };
+            let channel = forward_channel.clone();
+            let ticket = forward_ticket.clone();
+            let bot = forward_bot.clone();
+            tokio::spawn(async move {
+                let response = forward_request(channel, ticket, request).await;
+                bot.read().await.handle_response(response);
+            });
+        }
+    });
+
+    let bot_clone = bot.clone();
+    tokio::spawn(async move {
+        Bot::init(bot_clone).await;
+    });
+
+    let result = tokio::select! {
+        r = listen_active_events(channel.clone(), ticket.clone(), EventType::Message, bot.clone()) => r,
+        r = listen_active_events(channel.clone(), ticket.clone(), EventType::Notice, bot.clone()) => r,
+        r = listen_active_events(channel.clone(), ticket.clone(), EventType::Request, bot.clone()) => r,
+    };
+    forward.abort();
+    Bot::disconnect(bot, connection).await;
+    result
+}
+
+/// 主动模式：连接配置中的Kritor端，断开后按间隔重连
+pub async fn connect_active(config: ActiveConfig) {
+    let interval = Duration::from_secs(config.reconnect_interval.unwrap_or(5));
+    loop {
+        if let Err(e) = run_active(&config).await {
+            error!("Active connection to {} lost: {}", config.address, e);
+        }
+        info!(
+            "Reconnecting to {} in {}s",
+            config.address,
+            interval.as_secs()
+        );
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -1,2 +1,5 @@
+pub mod auth;
+pub mod client;
 pub mod r#impl;
+pub mod record;
 pub mod server;
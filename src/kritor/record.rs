@@ -0,0 +1,165 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{error, info};
+use once_cell::sync::OnceCell;
+use prost::Message;
+use tokio::sync::mpsc;
+
+use crate::kritor::server::kritor_proto::common;
+use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::kritor::server::kritor_proto::{EventStructure, EventType};
+use crate::model::error::Result;
+
+/// 录制文件中的一条记录，文件为length-delimited的Record序列
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+    /// 记录时间，毫秒时间戳
+    #[prost(uint64, tag = "1")]
+    pub time: u64,
+    #[prost(uint64, tag = "2")]
+    pub uin: u64,
+    #[prost(string, tag = "3")]
+    pub uid: String,
+    #[prost(oneof = "record::Data", tags = "4, 5, 6")]
+    pub data: Option<record::Data>,
+}
+
+pub mod record {
+    use crate::kritor::server::kritor_proto::common;
+    use crate::kritor::server::kritor_proto::EventStructure;
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Data {
+        #[prost(message, tag = "4")]
+        Event(EventStructure),
+        #[prost(message, tag = "5")]
+        Request(common::Request),
+        #[prost(message, tag = "6")]
+        Response(common::Response),
+    }
+}
+
+/// 记录按调用顺序进入队列，由单独的线程写入文件，不阻塞异步运行时
+pub struct Recorder {
+    sender: mpsc::UnboundedSender<Record>,
+}
+
+/// 全局的录制器，只由main根据配置开启
+static RECORDER: OnceCell<Recorder> = OnceCell::new();
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl Recorder {
+    /// 记录追加到path中
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let writer = BufWriter::new(file);
+        std::thread::spawn(move || write_records(writer, receiver));
+        Ok(Self { sender })
+    }
+
+    fn write(&self, uin: u64, uid: &str, data: record::Data) {
+        let record = Record {
+            time: now_millis(),
+            uin,
+            uid: uid.to_string(),
+            data: Some(data),
+        };
+        if self.sender.send(record).is_err() {
+            error!("Record writer stopped");
+        }
+    }
+
+    pub fn event(&self, uin: u64, uid: &str, event: &Event) {
+        let r#type = match event {
+            Event::Message(_) => EventType::Message,
+            Event::Notice(_) => EventType::Notice,
+            Event::Request(_) => EventType::Request,
+        };
+        let event = EventStructure {
+            r#type: r#type.into(),
+            event: Some(event.clone()),
+        };
+        self.write(uin, uid, record::Data::Event(event));
+    }
+
+    pub fn request(&self, uin: u64, uid: &str, request: &common::Request) {
+        self.write(uin, uid, record::Data::Request(request.clone()));
+    }
+
+    pub fn response(&self, uin: u64, uid: &str, response: &common::Response) {
+        self.write(uin, uid, record::Data::Response(response.clone()));
+    }
+}
+
+/// 开启全局录制，记录追加到path中
+pub fn init_recorder<P: AsRef<Path>>(path: P) -> Result<()> {
+    if RECORDER.get().is_some() {
+        return Ok(());
+    }
+    if RECORDER.set(Recorder::new(path.as_ref())?).is_ok() {
+        info!("Recording kritor traffic to {}", path.as_ref().display());
+    }
+    Ok(())
+}
+
+/// 写入队列中的记录，队列暂时为空时才刷新到文件
+fn write_records(mut writer: BufWriter<File>, mut receiver: mpsc::UnboundedReceiver<Record>) {
+    let mut next = receiver.blocking_recv();
+    while let Some(record) = next {
+        if let Err(e) = writer.write_all(&record.encode_length_delimited_to_vec()) {
+            error!("Failed to write record: {}", e);
+        }
+        next = match receiver.try_recv() {
+            Ok(record) => Some(record),
+            Err(_) => {
+                if let Err(e) = writer.flush() {
+                    error!("Failed to flush records: {}", e);
+                }
+                receiver.blocking_recv()
+            }
+        };
+    }
+}
+
+pub fn record_event(uin: u64, uid: &str, event: &Event) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.event(uin, uid, event);
+    }
+}
+
+/// 在请求发出之前调用，保证请求记录在其响应之前
+pub fn record_request(uin: u64, uid: &str, request: &common::Request) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.request(uin, uid, request);
+    }
+}
+
+pub fn record_response(uin: u64, uid: &str, response: &common::Response) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.response(uin, uid, response);
+    }
+}
+
+/// 读取录制文件中的全部记录
+pub fn read_records<P: AsRef<Path>>(path: P) -> Result<Vec<Record>> {
+    let buf = std::fs::read(path)?;
+    let mut buf = buf.as_slice();
+    let mut records = Vec::new();
+    while !buf.is_empty() {
+        records.push(Record::decode_length_delimited(&mut buf)?);
+    }
+    Ok(records)
+}
@@ -1,20 +1,30 @@
 use crate::bot::bot::Bot;
+use crate::bot::channel::EventReceiver;
+use crate::kritor::auth::{authenticate, KritorIdentity};
 use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::model::config::{get_config, ServerConfig, TlsConfig};
 use crate::service::register::listen_to_events;
 use dashmap::DashMap;
-use kritor_proto::event_service_server::EventService;
-use kritor_proto::reverse_service_server::ReverseService;
-use kritor_proto::{common, EventStructure, RequestPushEvent};
+use kritor_proto::event_service_server::{EventService, EventServiceServer};
+use kritor_proto::reverse_service_server::{ReverseService, ReverseServiceServer};
+use kritor_proto::{common, EventStructure, EventType, RequestPushEvent};
 use log::{debug, error, info, warn};
 use once_cell::sync::Lazy;
+use std::collections::HashSet;
 use std::error::Error;
+use std::fs;
 use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::{mpsc, Notify, RwLock};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, Notify, RwLock};
+use tokio_stream::wrappers::TcpListenerStream;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::Stream;
 use tokio_stream::StreamExt;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
 use tonic::{Request, Response, Status, Streaming};
 
 pub mod kritor_proto {
@@ -38,9 +48,15 @@ pub mod kritor_proto {
 
 static NOTIFY: Notify = Notify::const_new();
 
+const DEFAULT_ADDRESS: &str = "0.0.0.0:7001";
+
 pub static BOTS: Lazy<Arc<RwLock<DashMap<String, Arc<RwLock<Bot>>>>>> =
     Lazy::new(|| Arc::new(RwLock::new(DashMap::new())));
 
+/// 新注册到BOTS的Bot，未指定uid的主动监听者通过它订阅之后连接的Bot
+static BOT_CREATED: Lazy<broadcast::Sender<(String, Arc<RwLock<Bot>>)>> =
+    Lazy::new(|| broadcast::channel(16).0);
+
 #[derive(Debug, Default)]
 pub struct EventListener {}
 
@@ -52,9 +68,70 @@ impl EventService for EventListener {
 
     async fn register_active_listener(
         &self,
-        _request: Request<RequestPushEvent>,
+        request: Request<RequestPushEvent>,
     ) -> Result<Response<Self::RegisterActiveListenerStream>, Status> {
-        Err(Status::unimplemented("Not implemented"))
+        debug!("Received active listener registration");
+        // 指定kritor-self-uid时只订阅该Bot的事件，否则订阅全部Bot
+        let tickets = get_config().await.tickets;
+        let uid = if request.metadata().contains_key("kritor-self-uid") {
+            Some(authenticate(request.metadata(), tickets.as_ref())?.uid)
+        } else if tickets.is_some() {
+            return Err(Status::unauthenticated(
+                "kritor-self-uid is required when authentication is enabled",
+            ));
+        } else {
+            None
+        };
+        let event_type = EventType::try_from(request.get_ref().r#type)
+            .map_err(|_| Status::invalid_argument("unknown event type"))?;
+        // 先订阅新建的Bot再读取BOTS，避免遗漏两者之间连接的Bot
+        let created = uid.is_none().then(|| BOT_CREATED.subscribe());
+        let bots = {
+            let bots = BOTS.read().await;
+            bots.iter()
+                .filter(|entry| uid.as_ref().map_or(true, |uid| entry.key() == uid))
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect::<Vec<_>>()
+        };
+        if let Some(uid) = uid.as_ref() {
+            if bots.is_empty() {
+                return Err(Status::not_found(format!("bot {} not found", uid)));
+            }
+        }
+
+        let (tx, rx) = mpsc::channel(1024);
+        let mut subscribed = HashSet::new();
+        for (uid, bot) in bots {
+            subscribe_bot(&bot, &tx, event_type).await;
+            subscribed.insert(uid);
+        }
+        // 未指定uid时之后连接的Bot也转发给订阅者
+        if let Some(mut created) = created {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let result = tokio::select! {
+                        result = created.recv() => result,
+                        _ = tx.closed() => break,
+                    };
+                    match result {
+                        Ok((uid, bot)) => {
+                            if subscribed.insert(uid) {
+                                subscribe_bot(&bot, &tx, event_type).await;
+                            }
+                        }
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("active listener missed {} new bots", skipped);
+                        }
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+        let out_stream = ReceiverStream::new(rx);
+        Ok(Response::new(
+            Box::pin(out_stream) as Self::RegisterActiveListenerStream
+        ))
     }
 
     async fn register_passive_listener(
@@ -63,13 +140,8 @@ impl EventService for EventListener {
     ) -> Result<Response<RequestPushEvent>, Status> {
         debug!("Received passive listener registration");
         debug!("Request: {:?}", request.metadata());
-        let uid = request
-            .metadata()
-            .get("kritor-self-uid")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
+        let tickets = get_config().await.tickets;
+        let uid = authenticate(request.metadata(), tickets.as_ref())?.uid;
         // wait until bot is created through reverse stream
         loop {
             if BOTS.read().await.contains_key(&uid) {
@@ -88,25 +160,11 @@ impl EventService for EventListener {
         while let Some(event) = receiving_stream.next().await {
             match event {
                 Ok(event) => {
-                    let bot = bot.clone();
-                    tokio::spawn(async move {
-                        debug!("Received event: {:?}", event);
-                        let bot_guard = bot.read().await;
-                        match event.event.unwrap() {
-                            Event::Message(message) => {
-                                let sender = bot_guard.get_message_sender();
-                                let _ = sender.send(message);
-                            }
-                            Event::Notice(notice) => {
-                                let sender = bot_guard.get_notice_sender();
-                                let _ = sender.send(notice);
-                            }
-                            Event::Request(request) => {
-                                let sender = bot_guard.get_request_sender();
-                                let _ = sender.send(request);
-                            }
-                        }
-                    });
+                    debug!("Received event: {:?}", event);
+                    // 按顺序推送，队列满时暂停读取事件流
+                    if let Some(event) = event.event {
+                        Bot::push_event(&bot, event).await;
+                    }
                 }
                 Err(err) => {
                     error!("Error: {:?}", err);
@@ -134,43 +192,10 @@ impl ReverseService for ReverseListener {
         debug!("Received reverse stream");
         debug!("Request: {:?}", request.metadata());
         let (tx, rx) = mpsc::channel(4096);
-        let uid = request
-            .metadata()
-            .get("kritor-self-uid")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-        let version = request
-            .metadata()
-            .get("kritor-self-version")
-            .map(|m| m.to_str().unwrap().to_string());
-        let uin = request
-            .metadata()
-            .get("kritor-self-uin")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string()
-            .parse()
-            .unwrap_or(0);
-        // wait until bot is created
-        {
-            let bots = BOTS.write().await;
-            if !bots.contains_key(&uid) {
-                let tx_mutex = Arc::new(Some(tx));
-                let bot = Bot::new(uin, uid.clone(), tx_mutex, version);
-                let bot_ref = Arc::new(RwLock::new(bot));
-                listen_to_events(Arc::clone(&bot_ref)).await;
-                bots.insert(uid.clone(), Arc::clone(&bot_ref));
-                NOTIFY.notify_waiters();
-            }
-        }
-        let bot = {
-            let bots = BOTS.read().await;
-            let bot = bots.get(&uid).unwrap();
-            bot.clone()
-        };
+        let tickets = get_config().await.tickets;
+        let KritorIdentity { uid, uin, version } =
+            authenticate(request.metadata(), tickets.as_ref())?;
+        let (bot, connection) = get_or_create_bot(uin, uid.clone(), tx, version).await;
         let mut in_stream = request.into_inner();
         let bot_clone = bot.clone();
         tokio::spawn(async move {
@@ -180,13 +205,7 @@ impl ReverseService for ReverseListener {
                         let binding = bot_clone.clone();
                         tokio::spawn(async move {
                             debug!("Received: {:?}", v);
-                            let bot_guard = binding.read().await;
-                            let queue = bot_guard.get_request_queue().clone();
-                            if let Some(tx) = queue.remove(&v.seq) {
-                                tx.1.send(v).expect("queue not exists");
-                            } else {
-                                warn!("unknown response: cmd: {}, seq: {}", v.cmd, v.seq);
-                            }
+                            binding.read().await.handle_response(v);
                         });
                     }
                     Err(err) => {
@@ -201,7 +220,7 @@ impl ReverseService for ReverseListener {
                 }
             }
             println!("\treverse_stream ended");
-            BOTS.write().await.remove(&uid);
+            Bot::disconnect(bot_clone, connection).await;
         });
         tokio::spawn(async move {
             // sleep(Duration::from_secs(3)).await;
@@ -215,6 +234,179 @@ impl ReverseService for ReverseListener {
     }
 }
 
+/// 按订阅的事件类型转发Bot的事件，其他类型订阅全部事件
+async fn subscribe_bot(
+    bot: &Arc<RwLock<Bot>>,
+    tx: &mpsc::Sender<Result<EventStructure, Status>>,
+    event_type: EventType,
+) {
+    let bot = bot.read().await;
+    let all = !matches!(
+        event_type,
+        EventType::Message | EventType::Notice | EventType::Request
+    );
+    if all || event_type == EventType::Message {
+        forward_events(
+            bot.subscribe_message_lossy(),
+            tx.clone(),
+            EventType::Message,
+            Event::Message,
+        );
+    }
+    if all || event_type == EventType::Notice {
+        forward_events(
+            bot.subscribe_notice_lossy(),
+            tx.clone(),
+            EventType::Notice,
+            Event::Notice,
+        );
+    }
+    if all || event_type == EventType::Request {
+        forward_events(
+            bot.subscribe_request_lossy(),
+            tx.clone(),
+            EventType::Request,
+            Event::Request,
+        );
+    }
+}
+
+/// 将Bot的广播转发给主动监听的订阅者，订阅者断开后立即停止
+fn forward_events<T: Clone + Send + 'static>(
+    mut receiver: EventReceiver<T>,
+    tx: mpsc::Sender<Result<EventStructure, Status>>,
+    event_type: EventType,
+    wrap: fn(T) -> Event,
+) {
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                event = receiver.recv() => event,
+                _ = tx.closed() => None,
+            };
+            let Some(event) = event else {
+                break;
+            };
+            let event = EventStructure {
+                r#type: event_type.into(),
+                event: Some(wrap(event)),
+            };
+            if tx.send(Ok(event)).await.is_err() {
+                break;
+            }
+        }
+        debug!("active listener disconnected");
+    });
+}
+
+/// 创建Bot并注册到BOTS中，同时开始监听事件。已存在相同uid的Bot时视为重连，只替换发送端
+///
+/// 返回Bot和本次连接的编号，连接结束时用编号调用 `Bot::disconnect`
+pub async fn get_or_create_bot(
+    uin: u64,
+    uid: String,
+    tx: mpsc::Sender<Result<common::Request, Status>>,
+    version: Option<String>,
+) -> (Arc<RwLock<Bot>>, u64) {
+    let connection = {
+        let bots = BOTS.write().await;
+        let existing = bots.get(&uid).map(|bot| bot.clone());
+        if let Some(bot) = existing {
+            bot.read().await.reconnect(tx).await
+        } else {
+            let event_config = get_config().await.event.unwrap_or_default();
+            let bot = Bot::new(uin, uid.clone(), Some(tx), version, &event_config);
+            let bot_ref = Arc::new(RwLock::new(bot));
+            listen_to_events(Arc::clone(&bot_ref)).await;
+            bots.insert(uid.clone(), Arc::clone(&bot_ref));
+            let _ = BOT_CREATED.send((uid.clone(), Arc::clone(&bot_ref)));
+            NOTIFY.notify_waiters();
+            1
+        }
+    };
+    let bots = BOTS.read().await;
+    let bot = bots.get(&uid).unwrap();
+    (bot.clone(), connection)
+}
+
+fn load_tls(tls: &TlsConfig) -> std::io::Result<ServerTlsConfig> {
+    let cert = fs::read(&tls.cert)?;
+    let key = fs::read(&tls.key)?;
+    let mut tls_config = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+    if let Some(client_ca) = tls.client_ca.as_ref() {
+        tls_config = tls_config.client_ca_root(Certificate::from_pem(fs::read(client_ca)?));
+    }
+    Ok(tls_config)
+}
+
+/// 按配置启动被动模式的gRPC服务，每个地址单独监听
+pub async fn serve(config: ServerConfig) -> Result<(), Box<dyn Error>> {
+    let addresses = config
+        .addresses
+        .clone()
+        .unwrap_or_else(|| vec![DEFAULT_ADDRESS.to_string()]);
+    let tls_config = match config.tls.as_ref() {
+        Some(tls) => Some(load_tls(tls)?),
+        None => None,
+    };
+
+    let mut servers = Vec::new();
+    for address in addresses {
+        let addr: SocketAddr = address.parse()?;
+        let mut builder = Server::builder();
+        if let Some(tls_config) = tls_config.clone() {
+            builder = builder.tls_config(tls_config)?;
+        }
+        let (event_service, reverse_service) = services(&config);
+        info!(
+            "Listening on {}{}",
+            addr,
+            if tls_config.is_some() { " (tls)" } else { "" }
+        );
+        let server = builder
+            .add_service(event_service)
+            .add_service(reverse_service)
+            .serve(addr);
+        servers.push(tokio::spawn(server));
+    }
+    for server in servers {
+        server.await??;
+    }
+    Ok(())
+}
+
+fn services(
+    config: &ServerConfig,
+) -> (
+    EventServiceServer<EventListener>,
+    ReverseServiceServer<ReverseListener>,
+) {
+    let mut event_service = EventServiceServer::new(EventListener::default());
+    let mut reverse_service = ReverseServiceServer::new(ReverseListener::default());
+    if let Some(size) = config.max_decoding_message_size {
+        event_service = event_service.max_decoding_message_size(size);
+        reverse_service = reverse_service.max_decoding_message_size(size);
+    }
+    if let Some(size) = config.max_encoding_message_size {
+        event_service = event_service.max_encoding_message_size(size);
+        reverse_service = reverse_service.max_encoding_message_size(size);
+    }
+    (event_service, reverse_service)
+}
+
+/// 在已绑定的端口上启动不带TLS的gRPC服务，用于测试等需要随机端口的场景
+pub async fn serve_with_listener(
+    listener: TcpListener,
+    config: ServerConfig,
+) -> Result<(), tonic::transport::Error> {
+    let (event_service, reverse_service) = services(&config);
+    Server::builder()
+        .add_service(event_service)
+        .add_service(reverse_service)
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
 fn match_for_io_error(err_status: &Status) -> Option<&std::io::Error> {
     let mut err: &(dyn Error + 'static) = err_status;
 
@@ -1,3 +1,4 @@
+mod adapter;
 mod bot;
 mod kritor;
 mod model;
@@ -5,15 +6,19 @@ mod service;
 mod test;
 mod utils;
 
-use crate::kritor::server::kritor_proto::event_service_server::EventServiceServer;
-use crate::kritor::server::kritor_proto::reverse_service_server::ReverseServiceServer;
-use crate::kritor::server::{EventListener, ReverseListener};
-use crate::model::config::{get_config_sync, notify_config_change};
+use crate::adapter::console::run_console;
+use crate::adapter::onebot::connect_onebot;
+use crate::adapter::replay::replay;
+use crate::adapter::satori::connect_satori;
+use crate::kritor::client::connect_active;
+use crate::kritor::record::init_recorder;
+use crate::kritor::server::serve;
+use crate::model::config::{get_config, get_config_sync, notify_config_change};
 use crate::service::external::javascript::service::register_js_plugins;
+use log::error;
 use once_cell::sync::Lazy;
 use std::error::Error;
 use log4rs::config::Logger;
-use tonic::transport::Server;
 
 pub static LOG_INIT: Lazy<()> = Lazy::new(|| {
     let config = get_config_sync();
@@ -26,15 +31,35 @@ pub static LOG_INIT: Lazy<()> = Lazy::new(|| {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // console_subscriber::init();
-    let addr = "0.0.0.0:7001".parse()?;
     register_js_plugins().await;
     notify_config_change();
-    let event_listener = EventListener::default();
-    let reverse_listener = ReverseListener::default();
-    Server::builder()
-        .add_service(EventServiceServer::new(event_listener))
-        .add_service(ReverseServiceServer::new(reverse_listener))
-        .serve(addr)
-        .await?;
+    let config = get_config().await;
+    // 回放时不再录制，避免把回放的事件写回文件
+    if let (Some(record), None) = (&config.record, &config.replay) {
+        if let Err(e) = init_recorder(&record.path) {
+            error!("Failed to open record file {}: {}", record.path, e);
+        }
+    }
+    // 主动模式
+    for active in config.active.unwrap_or_default() {
+        tokio::spawn(connect_active(active));
+    }
+    for onebot in config.onebot.unwrap_or_default() {
+        tokio::spawn(connect_onebot(onebot));
+    }
+    for satori in config.satori.unwrap_or_default() {
+        tokio::spawn(connect_satori(satori));
+    }
+    if let Some(console) = config.console {
+        tokio::spawn(run_console(console));
+    }
+    if let Some(replay_config) = config.replay {
+        tokio::spawn(async move {
+            if let Err(e) = replay(replay_config).await {
+                error!("Replay failed: {}", e);
+            }
+        });
+    }
+    serve(config.server.unwrap_or_default()).await?;
     Ok(())
 }
@@ -0,0 +1,235 @@
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::error;
+use once_cell::sync::Lazy;
+use prost::Message;
+use rusqlite::{params, Connection};
+
+use crate::kritor::server::kritor_proto::{FriendInfo, GroupInfo, GroupMemberInfo};
+use crate::model::db::{connect, DB};
+use crate::model::error::Result;
+
+/// 从本地缓存读出的群，members_updated_at为None表示成员列表尚未加载
+#[derive(Debug, Clone, Default)]
+pub struct CachedGroup {
+    pub info: GroupInfo,
+    pub updated_at: u64,
+    pub members_updated_at: Option<u64>,
+    pub members: Vec<GroupMemberInfo>,
+}
+
+/// 群、好友信息的本地缓存，按Bot的uid区分，信息以protobuf编码保存
+pub struct CacheStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+/// 打开失败时为None，此时只使用内存缓存
+pub static CACHE_STORE: Lazy<Option<CacheStore>> = Lazy::new(|| {
+    match CacheStore::new(DB.as_ref()?.clone()) {
+        Ok(store) => Some(store),
+        Err(e) => {
+            error!("Failed to open cache store: {}", e);
+            None
+        }
+    }
+});
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl CacheStore {
+    /// path为":memory:"时使用内存数据库
+    pub fn open(path: &str) -> Result<Self> {
+        Self::new(Arc::new(Mutex::new(connect(path)?)))
+    }
+
+    /// 使用已有的连接，建表并读取数据
+    pub fn new(conn: Arc<Mutex<Connection>>) -> Result<Self> {
+        conn.lock().unwrap().execute_batch(
+            "CREATE TABLE IF NOT EXISTS avocado_group (
+                bot TEXT,
+                group_id INTEGER,
+                info BLOB,
+                updated_at INTEGER,
+                members_updated_at INTEGER,
+                PRIMARY KEY (bot, group_id)
+            );
+            CREATE TABLE IF NOT EXISTS avocado_group_member (
+                bot TEXT,
+                group_id INTEGER,
+                uin INTEGER,
+                info BLOB,
+                updated_at INTEGER,
+                PRIMARY KEY (bot, group_id, uin)
+            );
+            CREATE TABLE IF NOT EXISTS avocado_friend (
+                bot TEXT,
+                uin INTEGER,
+                info BLOB,
+                updated_at INTEGER,
+                PRIMARY KEY (bot, uin)
+            );",
+        )?;
+        Ok(CacheStore { conn })
+    }
+
+    pub fn load_groups(&self, bot: &str) -> Result<Vec<CachedGroup>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT group_id, info, updated_at, members_updated_at FROM avocado_group WHERE bot = ?",
+        )?;
+        let mut groups = stmt
+            .query_map([bot], |row| {
+                let info: Vec<u8> = row.get(1)?;
+                Ok(CachedGroup {
+                    info: GroupInfo::decode(info.as_slice()).unwrap_or_default(),
+                    updated_at: row.get::<_, i64>(2)? as u64,
+                    members_updated_at: row.get::<_, Option<i64>>(3)?.map(|t| t as u64),
+                    members: vec![],
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<CachedGroup>>>()?;
+        let mut stmt =
+            conn.prepare("SELECT info FROM avocado_group_member WHERE bot = ? AND group_id = ?")?;
+        for group in groups.iter_mut() {
+            if group.members_updated_at.is_none() {
+                continue;
+            }
+            group.members = stmt
+                .query_map(params![bot, group.info.group_id as i64], |row| {
+                    let info: Vec<u8> = row.get(0)?;
+                    Ok(GroupMemberInfo::decode(info.as_slice()).unwrap_or_default())
+                })?
+                .collect::<rusqlite::Result<Vec<GroupMemberInfo>>>()?;
+        }
+        Ok(groups)
+    }
+
+    /// 保存群列表，不在列表中的群连同成员一起删除
+    pub fn save_groups(&self, bot: &str, groups: &[GroupInfo]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("CREATE TEMP TABLE IF NOT EXISTS current_group (group_id INTEGER)", [])?;
+        tx.execute("DELETE FROM current_group", [])?;
+        for group in groups {
+            upsert_group(&tx, bot, group)?;
+            tx.execute(
+                "INSERT INTO current_group (group_id) VALUES (?)",
+                [group.group_id as i64],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM avocado_group_member WHERE bot = ?
+                AND group_id NOT IN (SELECT group_id FROM current_group)",
+            [bot],
+        )?;
+        tx.execute(
+            "DELETE FROM avocado_group WHERE bot = ?
+                AND group_id NOT IN (SELECT group_id FROM current_group)",
+            [bot],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn save_group(&self, bot: &str, group: &GroupInfo) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        upsert_group(&conn, bot, group)
+    }
+
+    pub fn remove_group(&self, bot: &str, group_id: u64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM avocado_group_member WHERE bot = ? AND group_id = ?",
+            params![bot, group_id as i64],
+        )?;
+        conn.execute(
+            "DELETE FROM avocado_group WHERE bot = ? AND group_id = ?",
+            params![bot, group_id as i64],
+        )?;
+        Ok(())
+    }
+
+    /// 替换群的全部成员，并记录成员列表的更新时间
+    pub fn save_members(&self, bot: &str, group_id: u64, members: &[GroupMemberInfo]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM avocado_group_member WHERE bot = ? AND group_id = ?",
+            params![bot, group_id as i64],
+        )?;
+        for member in members {
+            upsert_member(&tx, bot, group_id, member)?;
+        }
+        tx.execute(
+            "UPDATE avocado_group SET members_updated_at = ? WHERE bot = ? AND group_id = ?",
+            params![now() as i64, bot, group_id as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn save_member(&self, bot: &str, group_id: u64, member: &GroupMemberInfo) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        upsert_member(&conn, bot, group_id, member)
+    }
+
+    pub fn remove_member(&self, bot: &str, group_id: u64, uin: u64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM avocado_group_member WHERE bot = ? AND group_id = ? AND uin = ?",
+            params![bot, group_id as i64, uin as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn load_friends(&self, bot: &str) -> Result<Vec<FriendInfo>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT info FROM avocado_friend WHERE bot = ?")?;
+        let friends = stmt
+            .query_map([bot], |row| {
+                let info: Vec<u8> = row.get(0)?;
+                Ok(FriendInfo::decode(info.as_slice()).unwrap_or_default())
+            })?
+            .collect::<rusqlite::Result<Vec<FriendInfo>>>()?;
+        Ok(friends)
+    }
+
+    /// 替换全部好友
+    pub fn save_friends(&self, bot: &str, friends: &[FriendInfo]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM avocado_friend WHERE bot = ?", [bot])?;
+        for friend in friends {
+            tx.execute(
+                "INSERT INTO avocado_friend (bot, uin, info, updated_at) VALUES (?, ?, ?, ?)",
+                params![bot, friend.uin as i64, friend.encode_to_vec(), now() as i64],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn upsert_group(conn: &Connection, bot: &str, group: &GroupInfo) -> Result<()> {
+    conn.execute(
+        "INSERT INTO avocado_group (bot, group_id, info, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (bot, group_id) DO UPDATE SET info = excluded.info, updated_at = excluded.updated_at",
+        params![bot, group.group_id as i64, group.encode_to_vec(), now() as i64],
+    )?;
+    Ok(())
+}
+
+fn upsert_member(conn: &Connection, bot: &str, group_id: u64, member: &GroupMemberInfo) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO avocado_group_member (bot, group_id, uin, info, updated_at)
+            VALUES (?, ?, ?, ?, ?)",
+        params![bot, group_id as i64, member.uin as i64, member.encode_to_vec(), now() as i64],
+    )?;
+    Ok(())
+}
@@ -6,6 +6,7 @@ use log::{debug, error, info};
 use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -16,18 +17,290 @@ use tokio::sync::RwLock;
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub owner: Option<Vec<String>>,
+    /// Bot管理员的uin或uid，权限仅次于主人
+    pub admins: Option<Vec<String>>,
     pub log_level: Option<String>,
+    /// 主动模式下需要连接的Kritor端
+    pub active: Option<Vec<ActiveConfig>>,
+    /// 每个账号（uid）允许使用的ticket，未配置时不鉴权
+    pub tickets: Option<HashMap<String, TicketConfig>>,
+    /// 被动模式的gRPC服务配置
+    pub server: Option<ServerConfig>,
+    /// OneBot v11协议的连接
+    pub onebot: Option<Vec<OneBotConfig>>,
+    /// Satori协议的连接
+    pub satori: Option<Vec<SatoriConfig>>,
+    /// 控制台调试，配置后从标准输入模拟消息
+    pub console: Option<ConsoleConfig>,
+    /// 录制收发的事件和请求，用于复现问题
+    pub record: Option<RecordConfig>,
+    /// 回放录制文件
+    pub replay: Option<ReplayConfig>,
+    /// 发送消息的限速
+    pub send: Option<SendConfig>,
+    /// 事件队列
+    pub event: Option<EventConfig>,
+    /// 群、好友缓存
+    pub cache: Option<CacheConfig>,
+    /// 服务权限
+    pub permission: Option<PermissionConfig>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             owner: None,
+            admins: None,
             log_level: Some("info".to_string()),
+            active: None,
+            tickets: None,
+            server: None,
+            onebot: None,
+            satori: None,
+            console: None,
+            record: None,
+            replay: None,
+            send: None,
+            event: None,
+            cache: None,
+            permission: None,
         }
     }
 }
 
+/// 一个账号的ticket，只能用于作为键的uid，指定uin时连接携带的uin也必须一致
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TicketConfig {
+    Tickets(Vec<String>),
+    Account { uin: Option<u64>, tickets: Vec<String> },
+}
+
+impl TicketConfig {
+    pub fn tickets(&self) -> &[String] {
+        match self {
+            TicketConfig::Tickets(tickets) | TicketConfig::Account { tickets, .. } => tickets,
+        }
+    }
+
+    pub fn uin(&self) -> Option<u64> {
+        match self {
+            TicketConfig::Tickets(_) => None,
+            TicketConfig::Account { uin, .. } => *uin,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ServerConfig {
+    /// 监听地址，可配置多个，默认 0.0.0.0:7001
+    pub addresses: Option<Vec<String>>,
+    pub tls: Option<TlsConfig>,
+    /// 单条gRPC消息解码的最大字节数
+    pub max_decoding_message_size: Option<usize>,
+    /// 单条gRPC消息编码的最大字节数
+    pub max_encoding_message_size: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM格式的证书路径
+    pub cert: String,
+    /// PEM格式的私钥路径
+    pub key: String,
+    /// 配置后校验客户端证书（mTLS）
+    pub client_ca: Option<String>,
+}
+
+/// 主动模式，由avocado作为gRPC客户端连接Kritor端
+#[derive(Deserialize, Debug, Clone)]
+pub struct ActiveConfig {
+    /// Kritor端地址，如 http://127.0.0.1:5700
+    pub address: String,
+    /// Kritor端开启鉴权时使用的ticket
+    pub ticket: Option<String>,
+    /// 断线重连间隔，单位秒，默认5秒
+    pub reconnect_interval: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OneBotMode {
+    /// 正向WebSocket，由avocado连接OneBot实现
+    Forward,
+    /// 反向WebSocket，由OneBot实现连接avocado
+    Reverse,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OneBotConfig {
+    pub mode: OneBotMode,
+    /// 正向时为OneBot实现的地址，如 ws://127.0.0.1:6700；反向时为监听地址，如 0.0.0.0:6701
+    pub address: String,
+    /// OneBot的access_token
+    pub access_token: Option<String>,
+    /// 正向连接断线重连间隔，单位秒，默认5秒
+    pub reconnect_interval: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SatoriConfig {
+    /// Satori的API地址，如 http://127.0.0.1:5500/satori/v1
+    pub address: String,
+    pub token: Option<String>,
+    /// 断线重连间隔，单位秒，默认5秒
+    pub reconnect_interval: Option<u64>,
+}
+
+/// 控制台调试使用的虚拟身份
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ConsoleConfig {
+    pub self_uin: Option<u64>,
+    pub nickname: Option<String>,
+    pub group_id: Option<u64>,
+    pub group_name: Option<String>,
+    pub sender_uin: Option<u64>,
+    pub sender_nick: Option<String>,
+    /// 发出的图片保存目录
+    pub image_dir: Option<String>,
+}
+
+impl ConsoleConfig {
+    pub fn self_uin(&self) -> u64 {
+        self.self_uin.unwrap_or(10000)
+    }
+
+    pub fn nickname(&self) -> String {
+        self.nickname.clone().unwrap_or("avocado".to_string())
+    }
+
+    pub fn group_id(&self) -> u64 {
+        self.group_id.unwrap_or(100000)
+    }
+
+    pub fn group_name(&self) -> String {
+        self.group_name.clone().unwrap_or("console".to_string())
+    }
+
+    pub fn sender_uin(&self) -> u64 {
+        self.sender_uin.unwrap_or(123456)
+    }
+
+    pub fn sender_nick(&self) -> String {
+        self.sender_nick.clone().unwrap_or("tester".to_string())
+    }
+
+    pub fn image_dir(&self) -> String {
+        self.image_dir.clone().unwrap_or("temp/console".to_string())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecordConfig {
+    /// 录制文件路径，记录以追加方式写入
+    pub path: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReplayConfig {
+    pub path: String,
+    /// 回放倍速，默认1即按原始间隔，0表示不等待
+    pub speed: Option<f64>,
+}
+
+/// 发送限速，rate为每秒补充的令牌数，burst为最多可连续发送的消息数，rate为0时不限速
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SendConfig {
+    pub bot_rate: Option<f64>,
+    pub bot_burst: Option<u32>,
+    pub contact_rate: Option<f64>,
+    pub contact_burst: Option<u32>,
+    /// 随SendMessageRequest发送，由Kritor端在发送失败时重试
+    pub retry_count: Option<u32>,
+}
+
+impl SendConfig {
+    pub fn bot_rate(&self) -> f64 {
+        self.bot_rate.unwrap_or(5.0)
+    }
+
+    pub fn bot_burst(&self) -> u32 {
+        self.bot_burst.unwrap_or(10)
+    }
+
+    pub fn contact_rate(&self) -> f64 {
+        self.contact_rate.unwrap_or(1.0)
+    }
+
+    pub fn contact_burst(&self) -> u32 {
+        self.contact_burst.unwrap_or(3)
+    }
+
+    pub fn retry_count(&self) -> u32 {
+        self.retry_count.unwrap_or(2)
+    }
+}
+
+/// 事件队列，capacity为每类事件的队列长度。backpressure为true时每个消费者独立排队，
+/// 队列满时暂停读取Kritor端的事件流，否则丢弃最旧的事件
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct EventConfig {
+    pub message_capacity: Option<usize>,
+    pub notice_capacity: Option<usize>,
+    pub request_capacity: Option<usize>,
+    pub backpressure: Option<bool>,
+}
+
+impl EventConfig {
+    pub fn message_capacity(&self) -> usize {
+        self.message_capacity.unwrap_or(1024)
+    }
+
+    pub fn notice_capacity(&self) -> usize {
+        self.notice_capacity.unwrap_or(256)
+    }
+
+    pub fn request_capacity(&self) -> usize {
+        self.request_capacity.unwrap_or(256)
+    }
+
+    pub fn backpressure(&self) -> bool {
+        self.backpressure.unwrap_or(false)
+    }
+}
+
+/// 群、好友缓存，resync_interval为重新同步的间隔，0为不同步。
+/// 成员列表超过ttl后在访问或同步时重新获取，单位均为秒
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CacheConfig {
+    pub resync_interval: Option<u64>,
+    pub ttl: Option<u64>,
+}
+
+impl CacheConfig {
+    pub fn resync_interval(&self) -> u64 {
+        self.resync_interval.unwrap_or(3600)
+    }
+
+    pub fn ttl(&self) -> u64 {
+        self.ttl.unwrap_or(86400)
+    }
+}
+
+/// 权限不足时的回复，{role}会替换为需要的角色，为空时不回复
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PermissionConfig {
+    pub denied_reply: Option<String>,
+}
+
+impl PermissionConfig {
+    pub fn denied_reply(&self) -> String {
+        self.denied_reply
+            .clone()
+            .unwrap_or("权限不足，需要{role}权限".to_string())
+    }
+}
+
 pub const CONFIG_PATH: &'static str = "config/config.toml";
 
 fn read_toml_config() -> Result<Config> {
@@ -0,0 +1,33 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::error;
+use once_cell::sync::Lazy;
+use rusqlite::Connection;
+
+use crate::model::error::Result;
+
+/// 本地数据库路径，测试中使用内存数据库，避免写入data.db
+#[cfg(not(test))]
+pub const DB_PATH: &str = "data.db";
+#[cfg(test)]
+pub const DB_PATH: &str = ":memory:";
+
+/// 等待其他连接释放锁的时间，超时后返回SQLITE_BUSY
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 服务开关、角色与群信息缓存共用的连接，打开失败时为None
+pub static DB: Lazy<Option<Arc<Mutex<Connection>>>> = Lazy::new(|| match connect(DB_PATH) {
+    Ok(conn) => Some(Arc::new(Mutex::new(conn))),
+    Err(e) => {
+        error!("Failed to open database: {}", e);
+        None
+    }
+});
+
+/// path为":memory:"时使用内存数据库
+pub fn connect(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
@@ -16,6 +16,10 @@ pub enum Kind {
     Kritor,
     Client,
     Network,
+    /// 等待响应超时
+    Timeout,
+    /// Kritor端返回的code不为0
+    Response { cmd: String, code: i32 },
 }
 
 impl Error {
@@ -45,6 +49,32 @@ impl Error {
             kind: Kind::Network,
         }
     }
+    pub fn timeout(msg: String) -> Self {
+        Error {
+            msg,
+            kind: Kind::Timeout,
+        }
+    }
+
+    pub fn response(cmd: String, code: i32, msg: String) -> Self {
+        Error {
+            msg: format!("{} failed, code: {}, msg: {}", cmd, code, msg),
+            kind: Kind::Response { cmd, code },
+        }
+    }
+
+    /// Kritor端返回的code，仅Response类型的错误有
+    pub fn code(&self) -> Option<i32> {
+        match self.kind {
+            Kind::Response { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.kind, Kind::Timeout)
+    }
+
     pub fn error(&self) -> String {
         self.msg.clone()
     }
@@ -70,6 +100,26 @@ impl From<tonic::Status> for Error {
     }
 }
 
+impl From<tonic::transport::Error> for Error {
+    fn from(e: tonic::transport::Error) -> Self {
+        let msg = format!("{}", e);
+        Error {
+            msg,
+            kind: Kind::Network,
+        }
+    }
+}
+
+impl From<prost::DecodeError> for Error {
+    fn from(e: prost::DecodeError) -> Self {
+        let msg = format!("{}", e);
+        Error {
+            msg,
+            kind: Kind::Kritor,
+        }
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(e: reqwest::Error) -> Self {
         let msg = format!("{}", e);
@@ -120,6 +170,25 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        let msg = format!("{}", e);
+        Error {
+            msg,
+            kind: Kind::Client,
+        }
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        let msg = format!("{}", e);
+        Error {
+            msg,
+            kind: Kind::Network,
+        }
+    }
+}
 
 impl From<rusqlite::Error> for Error {
     fn from(value: rusqlite::Error) -> Self {
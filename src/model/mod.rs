@@ -1,3 +1,8 @@
+pub mod cache;
 pub mod config;
+pub mod db;
 pub mod error;
+pub mod permission;
+pub mod policy;
 pub mod store;
+
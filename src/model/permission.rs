@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, RwLock};
+
+use log::error;
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection};
+
+use crate::model::db::{connect, DB};
+use crate::model::error::Result;
+use crate::model::policy::Scope;
+
+/// 触发者的角色，按权限从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Role {
+    #[default]
+    Member,
+    GroupAdmin,
+    GroupOwner,
+    Admin,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Role::Member => "member",
+            Role::GroupAdmin => "group_admin",
+            Role::GroupOwner => "group_owner",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn from_str_name(name: &str) -> Option<Self> {
+        match name {
+            "member" => Some(Role::Member),
+            "group_admin" => Some(Role::GroupAdmin),
+            "group_owner" => Some(Role::GroupOwner),
+            "admin" => Some(Role::Admin),
+            "owner" => Some(Role::Owner),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Member => write!(f, "群成员"),
+            Role::GroupAdmin => write!(f, "群管理员"),
+            Role::GroupOwner => write!(f, "群主"),
+            Role::Admin => write!(f, "Bot管理员"),
+            Role::Owner => write!(f, "主人"),
+        }
+    }
+}
+
+/// 手动授予的角色，保存在SQLite中，读取时只访问内存
+///
+/// 全局授予的角色在所有会话生效，群内授予的只在该群生效
+pub struct PermissionStore {
+    conn: Arc<Mutex<Connection>>,
+    grants: RwLock<HashMap<(String, Scope), Role>>,
+}
+
+/// 打开失败时为None，此时只使用配置与群信息中的角色
+pub static PERMISSION_STORE: Lazy<Option<PermissionStore>> = Lazy::new(|| {
+    match PermissionStore::new(DB.as_ref()?.clone()) {
+        Ok(store) => Some(store),
+        Err(e) => {
+            error!("Failed to open permission store: {}", e);
+            None
+        }
+    }
+});
+
+impl PermissionStore {
+    /// path为":memory:"时使用内存数据库
+    pub fn open(path: &str) -> Result<Self> {
+        Self::new(Arc::new(Mutex::new(connect(path)?)))
+    }
+
+    /// 使用已有的连接，建表并读取数据
+    pub fn new(conn: Arc<Mutex<Connection>>) -> Result<Self> {
+        let guard = conn.lock().unwrap();
+        guard.execute(
+            "CREATE TABLE IF NOT EXISTS avocado_role_grant (
+                user TEXT,
+                scope TEXT,
+                target TEXT,
+                role TEXT,
+                PRIMARY KEY (user, scope, target)
+            )",
+            [],
+        )?;
+        let mut grants = HashMap::new();
+        {
+            let mut stmt =
+                guard.prepare("SELECT user, scope, target, role FROM avocado_role_grant")?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<(String, String, String, String)>>>()?;
+            for (user, kind, target, role) in rows {
+                if let (Some(scope), Some(role)) =
+                    (Scope::from_parts(&kind, target), Role::from_str_name(&role))
+                {
+                    grants.insert((user, scope), role);
+                }
+            }
+        }
+        drop(guard);
+        Ok(PermissionStore {
+            conn,
+            grants: RwLock::new(grants),
+        })
+    }
+
+    /// user在scope下被授予的最高角色，包括全局授予的角色
+    pub fn get(&self, user: &str, scope: Option<&Scope>) -> Option<Role> {
+        let grants = self.grants.read().unwrap();
+        let global = grants.get(&(user.to_string(), Scope::Global)).copied();
+        let scoped =
+            scope.and_then(|scope| grants.get(&(user.to_string(), scope.clone())).copied());
+        global.max(scoped)
+    }
+
+    pub fn grant(&self, user: &str, scope: Scope, role: Role) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO avocado_role_grant (user, scope, target, role)
+                VALUES (?, ?, ?, ?)",
+            params![user, scope.kind(), scope.target(), role.as_str_name()],
+        )?;
+        self.grants
+            .write()
+            .unwrap()
+            .insert((user.to_string(), scope), role);
+        Ok(())
+    }
+
+    /// 返回是否有被撤销的角色
+    pub fn revoke(&self, user: &str, scope: Scope) -> Result<bool> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM avocado_role_grant WHERE user = ? AND scope = ? AND target = ?",
+            params![user, scope.kind(), scope.target()],
+        )?;
+        Ok(self
+            .grants
+            .write()
+            .unwrap()
+            .remove(&(user.to_string(), scope))
+            .is_some())
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, RwLock};
+
+use log::error;
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection};
+
+use crate::model::db::{connect, DB};
+use crate::model::error::Result;
+
+/// 管理开关的服务名，与插件名一致，不受开关限制
+pub const SWITCH_SERVICE: &str = "switch";
+
+/// 开关生效的范围，群与私聊的target分别为群号和对方的uin
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Global,
+    Group(String),
+    Private(String),
+}
+
+impl Scope {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Scope::Global => "global",
+            Scope::Group(_) => "group",
+            Scope::Private(_) => "private",
+        }
+    }
+
+    pub(crate) fn target(&self) -> &str {
+        match self {
+            Scope::Global => "",
+            Scope::Group(target) | Scope::Private(target) => target,
+        }
+    }
+
+    pub(crate) fn from_parts(kind: &str, target: String) -> Option<Self> {
+        match kind {
+            "global" => Some(Scope::Global),
+            "group" => Some(Scope::Group(target)),
+            "private" => Some(Scope::Private(target)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Scope::Global => write!(f, "全局"),
+            Scope::Group(target) => write!(f, "群{}", target),
+            Scope::Private(target) => write!(f, "私聊{}", target),
+        }
+    }
+}
+
+/// 服务的默认状态，Denylist时默认启用，Allowlist时只在启用过的群、私聊生效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PolicyMode {
+    #[default]
+    Denylist,
+    Allowlist,
+}
+
+impl PolicyMode {
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            PolicyMode::Denylist => "deny",
+            PolicyMode::Allowlist => "allow",
+        }
+    }
+
+    pub fn from_str_name(name: &str) -> Option<Self> {
+        match name {
+            "deny" => Some(PolicyMode::Denylist),
+            "allow" => Some(PolicyMode::Allowlist),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ServicePolicy {
+    pub mode: PolicyMode,
+    pub rules: HashMap<Scope, bool>,
+}
+
+impl ServicePolicy {
+    /// 全局关闭优先，其次是当前范围的开关，都没有时按mode决定
+    pub fn is_enabled(&self, scope: Option<&Scope>) -> bool {
+        if self.rules.get(&Scope::Global) == Some(&false) {
+            return false;
+        }
+        match scope.and_then(|scope| self.rules.get(scope)) {
+            Some(enabled) => *enabled,
+            None => self.mode == PolicyMode::Denylist,
+        }
+    }
+}
+
+/// 服务开关，保存在SQLite中，读取时只访问内存
+pub struct PolicyStore {
+    conn: Arc<Mutex<Connection>>,
+    policies: RwLock<HashMap<String, ServicePolicy>>,
+}
+
+/// 打开失败时为None，此时所有服务都启用
+pub static POLICY_STORE: Lazy<Option<PolicyStore>> = Lazy::new(|| {
+    match PolicyStore::new(DB.as_ref()?.clone()) {
+        Ok(store) => Some(store),
+        Err(e) => {
+            error!("Failed to open policy store: {}", e);
+            None
+        }
+    }
+});
+
+impl PolicyStore {
+    /// path为":memory:"时使用内存数据库
+    pub fn open(path: &str) -> Result<Self> {
+        Self::new(Arc::new(Mutex::new(connect(path)?)))
+    }
+
+    /// 使用已有的连接，建表并读取数据
+    pub fn new(conn: Arc<Mutex<Connection>>) -> Result<Self> {
+        let guard = conn.lock().unwrap();
+        guard.execute_batch(
+            "CREATE TABLE IF NOT EXISTS avocado_service_rule (
+                service TEXT,
+                scope TEXT,
+                target TEXT,
+                enabled INTEGER,
+                PRIMARY KEY (service, scope, target)
+            );
+            CREATE TABLE IF NOT EXISTS avocado_service_mode (
+                service TEXT PRIMARY KEY,
+                mode TEXT
+            );",
+        )?;
+        let mut policies: HashMap<String, ServicePolicy> = HashMap::new();
+        {
+            let mut stmt = guard.prepare("SELECT service, mode FROM avocado_service_mode")?;
+            let modes = stmt
+                .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+                .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+            for (service, mode) in modes {
+                policies.entry(service).or_default().mode =
+                    PolicyMode::from_str_name(&mode).unwrap_or_default();
+            }
+            let mut stmt =
+                guard.prepare("SELECT service, scope, target, enabled FROM avocado_service_rule")?;
+            let rules = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, bool>(3)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<(String, String, String, bool)>>>()?;
+            for (service, kind, target, enabled) in rules {
+                if let Some(scope) = Scope::from_parts(&kind, target) {
+                    policies.entry(service).or_default().rules.insert(scope, enabled);
+                }
+            }
+        }
+        drop(guard);
+        Ok(PolicyStore {
+            conn,
+            policies: RwLock::new(policies),
+        })
+    }
+
+    pub fn get(&self, service: &str) -> ServicePolicy {
+        self.policies
+            .read()
+            .unwrap()
+            .get(service)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn is_enabled(&self, service: &str, scope: Option<&Scope>) -> bool {
+        self.policies
+            .read()
+            .unwrap()
+            .get(service)
+            .map(|policy| policy.is_enabled(scope))
+            .unwrap_or(true)
+    }
+
+    pub fn set_rule(&self, service: &str, scope: Scope, enabled: bool) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO avocado_service_rule (service, scope, target, enabled)
+                VALUES (?, ?, ?, ?)",
+            params![service, scope.kind(), scope.target(), enabled],
+        )?;
+        self.policies
+            .write()
+            .unwrap()
+            .entry(service.to_string())
+            .or_default()
+            .rules
+            .insert(scope, enabled);
+        Ok(())
+    }
+
+    pub fn set_mode(&self, service: &str, mode: PolicyMode) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO avocado_service_mode (service, mode) VALUES (?, ?)",
+            params![service, mode.as_str_name()],
+        )?;
+        self.policies
+            .write()
+            .unwrap()
+            .entry(service.to_string())
+            .or_default()
+            .mode = mode;
+        Ok(())
+    }
+}
@@ -1,12 +1,15 @@
 use std::collections::HashMap;
 use std::future::Future;
 
+use crate::bot::bot::Bot;
 use crate::bot::friend::Friend;
 use crate::bot::group::Group;
+use crate::bot::process::RequestKind;
 use crate::kritor::r#impl::ContactJsObject;
 use crate::kritor::server::kritor_proto::common::element::{Data, ElementType};
 use crate::kritor::server::kritor_proto::common::*;
 use crate::kritor::server::BOTS;
+use crate::model::permission::Role;
 use crate::service::service::KritorContext;
 use boa_engine::object::builtins::{JsArray, JsMap, JsRegExp};
 use boa_engine::object::ObjectInitializer;
@@ -68,6 +71,7 @@ pub fn generate_context(
     contact: Option<Contact>,
     elements: Vec<Element>,
     plugin_name: String,
+    role: Role,
     kritor_context: &KritorContext,
 ) -> Context {
     let mut context = Context::default();
@@ -177,6 +181,11 @@ pub fn generate_context(
             js_string!("sendMessage"),
             3,
         )
+        .function(
+            NativeFunction::from_async_fn(send_channel_msg),
+            js_string!("sendChannelMessage"),
+            3,
+        )
         .build();
 
     context
@@ -193,7 +202,28 @@ pub fn generate_context(
         })
         .collect::<Vec<String>>()
         .join("");
-    let contact_js = contact.map(ContactJsObject::from).unwrap();
+    // 频道消息额外提供guild_id和channel_id
+    let (guild_id, channel_id) = match contact.as_ref() {
+        Some(c) if c.scene == i32::from(Scene::Guild) => (
+            JsValue::from(js_string!(c.peer.clone())),
+            JsValue::from(js_string!(c.sub_peer.clone().unwrap_or_default())),
+        ),
+        _ => (JsValue::Undefined, JsValue::Undefined),
+    };
+    // 请求事件没有contact
+    let contact_js: JsValue = contact
+        .map(|contact| JsObject::from_proto_and_data(None, ContactJsObject::from(contact)).into())
+        .unwrap_or_default();
+    // 请求事件提供request_id和request_type，用于e.approve()和e.reject(reason)
+    let (request_id, request_type) = match kritor_context.request.as_ref() {
+        Some(request) => (
+            JsValue::from(js_string!(request.request_id.clone())),
+            RequestKind::of(request)
+                .map(|kind| JsValue::from(js_string!(kind.as_str_name())))
+                .unwrap_or_default(),
+        ),
+        None => (JsValue::Undefined, JsValue::Undefined),
+    };
     let is_master = kritor_context.is_master;
     let e = ObjectInitializer::new(&mut context)
         .property(js_string!("msg"), js_string!(msg), Attribute::all())
@@ -202,16 +232,21 @@ pub fn generate_context(
             JsObject::from_proto_and_data(None, sender),
             Attribute::all(),
         )
-        .property(
-            js_string!("contact"),
-            JsObject::from_proto_and_data(None, contact_js),
-            Attribute::all(),
-        )
+        .property(js_string!("contact"), contact_js, Attribute::all())
         .property(js_string!("uin"), uin, Attribute::all())
         .property(js_string!("uid"), js_string!(uid.clone()), Attribute::all())
         .property(js_string!("is_master"), is_master, Attribute::all())
+        .property(js_string!("role"), js_string!(role.as_str_name()), Attribute::all())
+        .property(js_string!("guild_id"), guild_id, Attribute::all())
+        .property(js_string!("channel_id"), channel_id, Attribute::all())
+        .property(js_string!("request_id"), request_id, Attribute::all())
+        .property(js_string!("request_type"), request_type, Attribute::all())
         .property(js_string!("bot"), bot, Attribute::all())
+        // 插件处理了事件时置为true，reply、approve、reject会自动设置
+        .property(js_string!("handled"), false, Attribute::all())
         .function(NativeFunction::from_async_fn(reply), js_string!("reply"), 2)
+        .function(NativeFunction::from_async_fn(approve), js_string!("approve"), 0)
+        .function(NativeFunction::from_async_fn(reject), js_string!("reject"), 1)
         .build();
 
     context
@@ -221,6 +256,16 @@ pub fn generate_context(
     context
 }
 
+/// 脚本执行后读取e.handled，判断插件是否处理了本次事件
+pub fn is_handled(context: &mut Context) -> bool {
+    let e = context.global_object().get(js_string!("e"), context);
+    e.ok()
+        .and_then(|e| e.as_object().cloned())
+        .and_then(|e| e.get(js_string!("handled"), context).ok())
+        .map(|handled| handled.to_boolean())
+        .unwrap_or(false)
+}
+
 fn elements_from_js(value: JsValue, context: &mut Context) -> JsResult<Vec<Element>> {
     if value.is_string() {
         return Ok(vec![Element {
@@ -397,6 +442,57 @@ fn send_msg(
     }
 }
 
+/// Bot.sendChannelMessage(guildId, channelId, msg)
+fn send_channel_msg(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> impl Future<Output = JsResult<JsValue>> {
+    let arg_to_string = |value: Option<&JsValue>, context: &mut Context| {
+        value
+            .cloned()
+            .unwrap_or_default()
+            .to_string(context)
+            .map(|s| s.to_std_string_escaped())
+            .unwrap_or_default()
+    };
+    let guild_id = arg_to_string(args.get(0), context);
+    let channel_id = arg_to_string(args.get(1), context);
+    let elements = elements_from_js(args.get(2).cloned().unwrap_or_default(), context);
+    let uid = this
+        .as_object()
+        .and_then(|bot| bot.get(js_string!("uid"), context).ok())
+        .and_then(|uid| uid.as_string().map(|uid| uid.to_std_string_escaped()));
+    let contact = Contact {
+        scene: Scene::Guild.into(),
+        peer: guild_id,
+        sub_peer: Some(channel_id),
+    };
+    async move {
+        let elements = elements?;
+        let Some(uid) = uid else {
+            return Err(JsError::from_opaque(JsValue::from(js_string!(
+                "sendChannelMessage must be called on a bot"
+            ))));
+        };
+        let bots = BOTS.read().await;
+        let Some(bot) = bots.get(&uid) else {
+            return Err(JsError::from_opaque(JsValue::from(js_string!(format!(
+                "bot {} not found",
+                uid
+            )))));
+        };
+        let bot_guard = bot.read().await;
+        let result = bot_guard.send_msg(elements, contact).await;
+        match result {
+            Ok(response) => Ok(JsObject::from_proto_and_data(None, response).into()),
+            Err(error) => Err(JsError::from_opaque(JsValue::from(js_string!(
+                error.to_string()
+            )))),
+        }
+    }
+}
+
 fn reply(
     this: &JsValue,
     args: &[JsValue],
@@ -406,6 +502,7 @@ fn reply(
     let msg = args.get(0).unwrap();
     let elements = elements_from_js(msg.clone(), context).unwrap();
     let e = this.as_object().unwrap();
+    let _ = e.set(js_string!("handled"), true, false, context);
     let contact = e.get(js_string!("contact"), context).unwrap();
     let contact = contact
         .as_object()
@@ -434,3 +531,62 @@ fn reply(
         }
     }
 }
+
+fn approve(
+    this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> impl Future<Output = JsResult<JsValue>> {
+    set_request_result(this, true, None, context)
+}
+
+fn reject(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context,
+) -> impl Future<Output = JsResult<JsValue>> {
+    let reason = args
+        .get(0)
+        .and_then(|reason| reason.as_string())
+        .map(|reason| reason.to_std_string_escaped());
+    set_request_result(this, false, reason, context)
+}
+
+fn set_request_result(
+    this: &JsValue,
+    is_approve: bool,
+    reason: Option<String>,
+    context: &mut Context,
+) -> impl Future<Output = JsResult<JsValue>> {
+    let e = this.as_object();
+    if let Some(e) = e {
+        let _ = e.set(js_string!("handled"), true, false, context);
+    }
+    let mut get_string = |key: &str| {
+        e.and_then(|e| e.get(js_string!(key), context).ok())
+            .and_then(|value| value.as_string().map(|s| s.to_std_string_escaped()))
+    };
+    let uid = get_string("uid").unwrap_or_default();
+    let request_id = get_string("request_id");
+    let kind = get_string("request_type").and_then(|kind| RequestKind::from_str_name(&kind));
+    async move {
+        let (Some(request_id), Some(kind)) = (request_id, kind) else {
+            return Err(JsError::from_opaque(JsValue::from(js_string!(
+                "not a request event"
+            ))));
+        };
+        let Some(bot) = BOTS.read().await.get(&uid).cloned() else {
+            return Err(JsError::from_opaque(JsValue::from(js_string!(format!(
+                "bot {} not found",
+                uid
+            )))));
+        };
+        let result = Bot::settle_request(&bot, kind, request_id, is_approve, reason).await;
+        match result {
+            Ok(_) => Ok(JsValue::Undefined),
+            Err(error) => Err(JsError::from_opaque(JsValue::from(js_string!(
+                error.to_string()
+            )))),
+        }
+    }
+}
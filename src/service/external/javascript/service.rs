@@ -1,12 +1,15 @@
 use crate::kritor::server::kritor_proto::common::{Contact, Scene};
 use crate::kritor::server::kritor_proto::{event_structure, EventStructure};
-use crate::service::external::javascript::loader::generate_context;
-use crate::service::register::register_service;
-use crate::service::service::{get_concat_from_event, KritorContext, Matchable, Service};
+use crate::service::external::javascript::loader::{generate_context, is_handled};
+use crate::service::register::{register_service, ServiceOptions};
+use crate::service::service::{
+    get_concat_from_event, KritorContext, Matchable, ProcessResult, Service,
+};
 use crate::utils::kritor::same_contact_and_sender;
 use async_trait::async_trait;
 use avocado_common::Event;
 use boa_engine::Source;
+use log::warn;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -25,9 +28,21 @@ impl Matchable for ExternalJsService {
 
 #[async_trait]
 impl Service for ExternalJsService {
-    async fn process(&self, context: KritorContext) {
+    async fn process(&self, context: KritorContext) -> ProcessResult {
         let bot_arc = context.bot.clone();
         let bot = bot_arc.read().await;
+        // 群成员列表按需加载，保证当前群的gml可用
+        if let Some(group_id) = context
+            .message
+            .as_ref()
+            .and_then(|message| message.contact.as_ref())
+            .filter(|contact| contact.scene == Scene::Group as i32)
+            .and_then(|contact| contact.peer.parse::<u64>().ok())
+        {
+            if let Err(e) = bot.get_group_members(group_id).await {
+                warn!("Failed to load members of group {}: {}", group_id, e);
+            }
+        }
         let group = bot.get_groups().await;
         let friends = bot.get_friends().await;
 
@@ -36,6 +51,7 @@ impl Service for ExternalJsService {
         let uid = bot.get_uid().unwrap_or_default();
 
         drop(bot);
+        let role = context.role().await;
 
         let elements = context
             .message
@@ -72,14 +88,21 @@ impl Service for ExternalJsService {
                 contact,
                 elements.unwrap_or_default(),
                 plugin_name.unwrap_or("unknown".to_string()),
+                role,
                 &context,
             );
             let source = Source::from_filepath(path.as_path()).unwrap();
             boa_context
                 .eval(source)
                 .expect("external javascript plugin execute error");
+            is_handled(&mut boa_context)
         });
-        blocking_task.await.unwrap();
+        // js插件在js端自行判断是否匹配，没有处理事件时不算作匹配，不阻止其他服务
+        if blocking_task.await.unwrap() {
+            ProcessResult::Continue
+        } else {
+            ProcessResult::Ignored
+        }
     }
 }
 
@@ -106,6 +129,7 @@ pub async fn register_js_plugins() {
                         service_arc,
                         vec![Event::Message, Event::Notice, Event::Request],
                         plugin_name.to_string(),
+                        ServiceOptions::default(),
                     );
                 });
             }
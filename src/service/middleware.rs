@@ -0,0 +1,187 @@
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use log::{debug, error, warn};
+use once_cell::sync::Lazy;
+use tokio::sync::RwLock;
+
+use crate::kritor::server::kritor_proto::EventType;
+use crate::model::config::get_config;
+use crate::model::permission::Role;
+use crate::model::policy::{POLICY_STORE, SWITCH_SERVICE};
+use crate::service::register::EventHandler;
+use crate::service::service::{KritorContext, ProcessResult};
+use crate::text;
+
+/// 超过该时间的服务会打印警告
+const SLOW_SERVICE: Duration = Duration::from_secs(10);
+
+/// 一次服务调用的结果，服务panic时result为Err，内容为panic信息
+#[derive(Debug, Clone)]
+pub struct ServiceOutcome {
+    pub service_name: String,
+    pub elapsed: Duration,
+    pub result: std::result::Result<ProcessResult, String>,
+}
+
+/// 包裹每次服务调用的中间件
+///
+/// before在服务匹配之前调用，可以修改context，返回None时该服务不处理本次事件；
+/// after只在服务实际执行后调用，执行顺序与before相反
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// 越小越先执行
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    async fn before(&self, context: KritorContext) -> Option<KritorContext> {
+        Some(context)
+    }
+
+    async fn after(&self, _context: &KritorContext, _outcome: &ServiceOutcome) {}
+}
+
+pub static MIDDLEWARES: Lazy<RwLock<Vec<Arc<dyn Middleware>>>> =
+    Lazy::new(|| RwLock::new(vec![Arc::new(PolicyMiddleware), Arc::new(LogMiddleware)]));
+
+pub async fn register_middleware(middleware: Arc<dyn Middleware>) {
+    let mut middlewares = MIDDLEWARES.write().await;
+    middlewares.push(middleware);
+    middlewares.sort_by_key(|middleware| middleware.priority());
+}
+
+pub async fn unregister_middleware(middleware: &Arc<dyn Middleware>) {
+    MIDDLEWARES
+        .write()
+        .await
+        .retain(|registered| !Arc::ptr_eq(registered, middleware));
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// 经过中间件调用服务，服务不匹配或被中间件拦截时返回None
+///
+/// 触发者的角色低于permission时不执行服务，消息事件会回复配置的提示并视为已处理
+pub async fn invoke(
+    service_name: String,
+    handler: EventHandler,
+    permission: Role,
+    context: KritorContext,
+) -> Option<ProcessResult> {
+    let middlewares = MIDDLEWARES.read().await.clone();
+    let mut context = context;
+    for middleware in middlewares.iter() {
+        context = middleware.before(context).await?;
+    }
+    let context = handler.pre_process(context);
+    if !handler.matches(context.clone()) {
+        return None;
+    }
+    if permission > Role::Member {
+        let role = context.role().await;
+        if role < permission {
+            debug!(
+                "Service {} requires {:?}, sender is {:?}",
+                service_name, permission, role
+            );
+            return deny(&context, permission).await;
+        }
+    }
+
+    debug!("Dispatching event to service: {}", service_name);
+    let start = Instant::now();
+    let result = AssertUnwindSafe(handler.process(context.clone()))
+        .catch_unwind()
+        .await
+        .map_err(panic_message);
+    let outcome = ServiceOutcome {
+        service_name,
+        elapsed: start.elapsed(),
+        result,
+    };
+    for middleware in middlewares.iter().rev() {
+        middleware.after(&context, &outcome).await;
+    }
+    // 服务panic时视为未处理，继续分发给后面的服务
+    Some(outcome.result.unwrap_or_default())
+}
+
+async fn deny(context: &KritorContext, permission: Role) -> Option<ProcessResult> {
+    if context.r#type != EventType::Message {
+        return None;
+    }
+    let reply = get_config()
+        .await
+        .permission
+        .unwrap_or_default()
+        .denied_reply()
+        .replace("{role}", &permission.to_string());
+    if !reply.is_empty() {
+        if let Err(e) = context.reply_with_quote(vec![text!(reply)]).await {
+            warn!("Failed to reply permission denied: {}", e);
+        }
+    }
+    Some(ProcessResult::Stop)
+}
+
+/// 记录服务的耗时与panic
+struct LogMiddleware;
+
+#[async_trait]
+impl Middleware for LogMiddleware {
+    async fn after(&self, _context: &KritorContext, outcome: &ServiceOutcome) {
+        match &outcome.result {
+            Ok(result) => debug!(
+                "Service {} finished processing in {:?}: {:?}",
+                outcome.service_name, outcome.elapsed, result
+            ),
+            Err(panic) => error!("Service {} panicked: {}", outcome.service_name, panic),
+        }
+        if outcome.elapsed > SLOW_SERVICE {
+            warn!(
+                "Service {} took {:?} to process",
+                outcome.service_name, outcome.elapsed
+            );
+        }
+    }
+}
+
+/// 按群、私聊的开关过滤服务，在匹配之前执行
+struct PolicyMiddleware;
+
+#[async_trait]
+impl Middleware for PolicyMiddleware {
+    fn priority(&self) -> i32 {
+        -100
+    }
+
+    async fn before(&self, context: KritorContext) -> Option<KritorContext> {
+        let Some(store) = POLICY_STORE.as_ref() else {
+            return Some(context);
+        };
+        let service_name = context
+            .current_service_name
+            .read()
+            .await
+            .clone()
+            .unwrap_or_default();
+        // 开关服务本身不能被关闭，否则无法再打开
+        let scope = context.scope();
+        if service_name == SWITCH_SERVICE || store.is_enabled(&service_name, scope.as_ref()) {
+            Some(context)
+        } else {
+            None
+        }
+    }
+}
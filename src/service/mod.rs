@@ -1,4 +1,5 @@
 pub mod external;
+pub mod middleware;
 mod plugins;
 pub mod register;
 pub mod service;
@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use avocado_common::Event;
+use avocado_macro::service;
+use regex::Regex;
+
+use crate::bot::developer::DeveloperAPITrait;
+use crate::service::service::Elements;
+use crate::service::service::{KritorContext, ProcessResult, Service};
+use crate::text;
+
+/// 日志默认只回复最后几行，避免消息过长
+const DEFAULT_LOG_LINES: usize = 30;
+
+#[derive(Debug, Clone, Default)]
+#[service(
+    name = "developer",
+    pattern = "^[#＃]kritor\\s*(log|日志|clear|清除缓存|info|信息|stats|统计)(\\s+\\d+)?$",
+    events(Event::Message),
+    permission = "owner"
+)]
+struct DeveloperService;
+
+#[async_trait]
+impl Service for DeveloperService {
+    async fn process(&self, context: KritorContext) -> ProcessResult {
+        let text = context
+            .message
+            .as_ref()
+            .and_then(|message| message.elements.get_text_elements())
+            .map(|texts| texts.into_iter().map(|t| t.text).collect::<String>())
+            .unwrap_or_default();
+        let re = Regex::new("(log|日志|clear|清除缓存|info|信息|stats|统计)(\\s+(\\d+))?").unwrap();
+        let Some(captures) = re.captures(text.trim()) else {
+            return ProcessResult::Continue;
+        };
+        let reply = {
+            let bot = context.bot.read().await;
+            match &captures[1] {
+                "log" | "日志" => {
+                    let lines = captures
+                        .get(3)
+                        .and_then(|n| n.as_str().parse().ok())
+                        .unwrap_or(DEFAULT_LOG_LINES);
+                    bot.get_log(0, true).await.map(|response| {
+                        let log: Vec<&str> = response.log.lines().collect();
+                        log[log.len().saturating_sub(lines)..].join("\n")
+                    })
+                }
+                "clear" | "清除缓存" => bot
+                    .clear_cache()
+                    .await
+                    .map(|_| "Kritor端缓存已清除".to_string()),
+                "stats" | "统计" => Ok(bot
+                    .get_request_stats()
+                    .iter()
+                    .map(|(cmd, stats)| {
+                        format!(
+                            "{}：{}次，失败{}，超时{}，取消{}，等待中{}，未匹配响应{}，平均{}ms，最大{}ms",
+                            cmd,
+                            stats.total,
+                            stats.failed,
+                            stats.timeout,
+                            stats.cancelled,
+                            stats.pending,
+                            stats.unmatched,
+                            stats.avg_latency(),
+                            stats.max_latency
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")),
+                _ => bot.get_developer_info().await,
+            }
+        };
+        let reply = match reply {
+            Ok(reply) if reply.is_empty() => "无内容".to_string(),
+            Ok(reply) => reply,
+            Err(e) => format!("请求失败：{}", e),
+        };
+        context.reply_with_quote(vec![text!(reply)]).await.ok();
+        ProcessResult::Stop
+    }
+}
@@ -1,4 +1,4 @@
-use crate::service::service::{Elements, KritorContext, Matchable, Service};
+use crate::service::service::{Elements, KritorContext, Matchable, ProcessResult, Service};
 use crate::text;
 use async_trait::async_trait;
 use avocado_common::Event;
@@ -22,7 +22,7 @@ impl Matchable for RepeatPlugin {
 }
 #[async_trait]
 impl Service for RepeatPlugin {
-    async fn process(&self, context: KritorContext) {
+    async fn process(&self, context: KritorContext) -> ProcessResult {
         // let text = context.message.unwrap().elements.get_text_elements().unwrap().get(0).unwrap().text.clone();
         // context.set_store("repeat".to_string(), Box::new(text)).await;
         info!("RepeatPlugin");
@@ -33,7 +33,8 @@ impl Service for RepeatPlugin {
         context
             .start_transaction("repeat".to_string(), None)
             .await
-            .unwrap()
+            .unwrap();
+        ProcessResult::Stop
     }
 
     async fn transaction(&self, context: KritorContext) {
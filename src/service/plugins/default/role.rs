@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use avocado_common::Event;
+use avocado_macro::service;
+
+use crate::model::permission::{Role, PERMISSION_STORE};
+use crate::model::policy::Scope;
+use crate::service::service::Elements;
+use crate::service::service::{KritorContext, ProcessResult, Service};
+use crate::text;
+
+/// 授予、撤销角色
+///
+/// #grant <uin> <角色> [global] 在当前群或全局授予角色，私聊中总是全局授予
+/// #revoke <uin> [global] 撤销当前群或全局授予的角色
+#[derive(Debug, Clone, Default)]
+#[service(
+    name = "role",
+    pattern = "^[#＃](grant|revoke|授权|取消授权)\\s+\\S+",
+    events(Event::Message),
+    permission = "owner"
+)]
+struct RoleService;
+
+fn target(scope: Option<Scope>, global: bool) -> Scope {
+    match scope {
+        Some(Scope::Group(group_id)) if !global => Scope::Group(group_id),
+        _ => Scope::Global,
+    }
+}
+
+fn grant(args: &[&str], scope: Option<Scope>) -> String {
+    let Some(store) = PERMISSION_STORE.as_ref() else {
+        return "权限存储不可用".to_string();
+    };
+    let (user, role, global) = match args {
+        [user, role] => (*user, *role, false),
+        [user, role, "global" | "全局"] => (*user, *role, true),
+        _ => return "用法：#grant <uin> <角色> [global]".to_string(),
+    };
+    // 主人只能在配置中指定
+    let Some(role) = Role::from_str_name(role).filter(|role| *role != Role::Owner) else {
+        return "角色只能是member、group_admin、group_owner或admin".to_string();
+    };
+    let scope = target(scope, global);
+    match store.grant(user, scope.clone(), role) {
+        Ok(_) => format!("已在{}授予{}{}", scope, user, role),
+        Err(e) => format!("授权失败：{}", e),
+    }
+}
+
+fn revoke(args: &[&str], scope: Option<Scope>) -> String {
+    let Some(store) = PERMISSION_STORE.as_ref() else {
+        return "权限存储不可用".to_string();
+    };
+    let (user, global) = match args {
+        [user] => (*user, false),
+        [user, "global" | "全局"] => (*user, true),
+        _ => return "用法：#revoke <uin> [global]".to_string(),
+    };
+    let scope = target(scope, global);
+    match store.revoke(user, scope.clone()) {
+        Ok(true) => format!("已撤销{}在{}的角色", user, scope),
+        Ok(false) => format!("{}在{}没有被授予角色", user, scope),
+        Err(e) => format!("撤销失败：{}", e),
+    }
+}
+
+#[async_trait]
+impl Service for RoleService {
+    async fn process(&self, context: KritorContext) -> ProcessResult {
+        let text = context
+            .message
+            .as_ref()
+            .and_then(|message| message.elements.get_text_elements())
+            .map(|texts| texts.into_iter().map(|t| t.text).collect::<String>())
+            .unwrap_or_default();
+        let mut words = text.trim().trim_start_matches(['#', '＃']).split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<&str>>();
+        let reply = match command {
+            "grant" | "授权" => grant(&args, context.scope()),
+            _ => revoke(&args, context.scope()),
+        };
+        context.reply_with_quote(vec![text!(reply)]).await.ok();
+        ProcessResult::Stop
+    }
+}
@@ -16,7 +16,7 @@ use avocado_macro::service;
 
 use crate::image;
 use crate::service::service::Elements;
-use crate::service::service::{KritorContext, Service};
+use crate::service::service::{KritorContext, ProcessResult, Service};
 use crate::utils::common::bytes_to_readable_string;
 use crate::utils::common::memory::get_current_memory_usage;
 use crate::utils::image::{
@@ -45,7 +45,7 @@ impl Service for StatusService {
     //     false
     // }
 
-    async fn process(&self, context: KritorContext) {
+    async fn process(&self, context: KritorContext) -> ProcessResult {
         // let text = {
         //     let bot = context.bot.read().await;
         //     let nickname = context.message.as_ref().and_then(|m| m.sender.as_ref().and_then(|s| s.nick.as_ref())).cloned().unwrap_or_default();
@@ -81,6 +81,7 @@ impl Service for StatusService {
             .reply(vec![image!(draw(&context).await)])
             .await
             .unwrap();
+        ProcessResult::Stop
     }
 }
 
@@ -0,0 +1,114 @@
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use avocado_common::Event;
+use avocado_macro::service;
+
+use crate::model::policy::{PolicyMode, Scope, POLICY_STORE, SWITCH_SERVICE};
+use crate::service::register::{MESSAGE_SERVICES, NOTICE_SERVICES, REQUEST_SERVICES};
+use crate::service::service::Elements;
+use crate::service::service::{KritorContext, ProcessResult, Service};
+use crate::text;
+
+/// 按群、私聊开关服务，仅主人可用
+///
+/// #disable status 查看当前会话的服务状态
+/// #enable/#disable <服务> [global] 在当前会话或全局开关服务
+/// #disable mode <服务> allow|deny 设置为白名单或黑名单模式
+#[derive(Debug, Clone, Default)]
+#[service(
+    name = "switch",
+    pattern = "^[#＃](enable|disable|启用|禁用)\\s+\\S+",
+    events(Event::Message),
+    permission = "owner"
+)]
+struct SwitchService;
+
+async fn service_names() -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for services in [&MESSAGE_SERVICES, &NOTICE_SERVICES, &REQUEST_SERVICES] {
+        names.extend(services.lock().await.keys().cloned());
+    }
+    names.remove(SWITCH_SERVICE);
+    names
+}
+
+async fn status(scope: Option<&Scope>) -> String {
+    let Some(store) = POLICY_STORE.as_ref() else {
+        return "服务开关不可用".to_string();
+    };
+    let mut lines = vec![format!(
+        "当前范围：{}",
+        scope.map(|s| s.to_string()).unwrap_or("无".to_string())
+    )];
+    for name in service_names().await {
+        let policy = store.get(&name);
+        let state = if policy.rules.get(&Scope::Global) == Some(&false) {
+            "禁用（全局）"
+        } else if policy.is_enabled(scope) {
+            "启用"
+        } else if policy.mode == PolicyMode::Allowlist {
+            "禁用（白名单）"
+        } else {
+            "禁用"
+        };
+        lines.push(format!("{}：{}", name, state));
+    }
+    lines.join("\n")
+}
+
+async fn switch(args: &[&str], enabled: bool, scope: Option<Scope>) -> String {
+    let Some(store) = POLICY_STORE.as_ref() else {
+        return "服务开关不可用".to_string();
+    };
+    let (name, target) = match args {
+        ["mode", name, mode] => {
+            let Some(mode) = PolicyMode::from_str_name(mode) else {
+                return "模式只能是allow或deny".to_string();
+            };
+            if !service_names().await.contains(*name) {
+                return format!("未找到服务{}", name);
+            }
+            return match store.set_mode(name, mode) {
+                Ok(_) => format!("{}已设置为{}模式", name, mode.as_str_name()),
+                Err(e) => format!("设置失败：{}", e),
+            };
+        }
+        [name] => (*name, scope),
+        [name, "global" | "全局"] => (*name, Some(Scope::Global)),
+        _ => return "用法：#enable/#disable <服务> [global]".to_string(),
+    };
+    if !service_names().await.contains(name) {
+        return format!("未找到服务{}", name);
+    }
+    let Some(target) = target else {
+        return "当前会话不支持开关服务".to_string();
+    };
+    let action = if enabled { "启用" } else { "禁用" };
+    match store.set_rule(name, target.clone(), enabled) {
+        Ok(_) => format!("已在{}{}{}", target, action, name),
+        Err(e) => format!("{}失败：{}", action, e),
+    }
+}
+
+#[async_trait]
+impl Service for SwitchService {
+    async fn process(&self, context: KritorContext) -> ProcessResult {
+        let text = context
+            .message
+            .as_ref()
+            .and_then(|message| message.elements.get_text_elements())
+            .map(|texts| texts.into_iter().map(|t| t.text).collect::<String>())
+            .unwrap_or_default();
+        let mut words = text.trim().trim_start_matches(['#', '＃']).split_whitespace();
+        let enabled = matches!(words.next(), Some("enable" | "启用"));
+        let args = words.collect::<Vec<&str>>();
+        let scope = context.scope();
+        let reply = match args.as_slice() {
+            ["status" | "状态"] => status(scope.as_ref()).await,
+            args => switch(args, enabled, scope).await,
+        };
+        context.reply_with_quote(vec![text!(reply)]).await.ok();
+        ProcessResult::Stop
+    }
+}
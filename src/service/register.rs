@@ -2,10 +2,15 @@ use crate::bot::bot::Bot;
 use crate::bot::group::Group;
 use crate::kritor::server::kritor_proto::common::Scene;
 use crate::model::config::get_config;
-use crate::service::service::{get_concat_from_event, Elements, KritorContext, Service};
+use crate::model::permission::Role;
+use crate::service::middleware::invoke;
+use crate::service::service::{
+    get_concat_from_event, Elements, KritorContext, LifecycleEvent, ProcessResult, Service,
+};
 use crate::utils::kritor::same_contact_and_sender;
 use crate::LOG_INIT;
 use avocado_common::Event;
+use futures::future::join_all;
 use log::{debug, info};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
@@ -16,13 +21,34 @@ use tokio::sync::{Mutex, RwLock};
 pub type KritorEvent = crate::kritor::server::kritor_proto::event_structure::Event;
 pub type EventHandler = Arc<dyn Service + Send + Sync>;
 
-pub static MESSAGE_SERVICES: Lazy<Mutex<HashMap<String, EventHandler>>> =
+/// 服务的分发顺序，priority越小越先执行，fallback的服务只在没有其他服务匹配时执行。
+/// 触发者的角色低于permission时不执行服务
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServiceOptions {
+    pub priority: i32,
+    pub fallback: bool,
+    pub permission: Role,
+}
+
+#[derive(Clone)]
+pub struct RegisteredService {
+    pub handler: EventHandler,
+    pub options: ServiceOptions,
+}
+
+impl RegisteredService {
+    pub fn new(handler: EventHandler, options: ServiceOptions) -> Self {
+        Self { handler, options }
+    }
+}
+
+pub static MESSAGE_SERVICES: Lazy<Mutex<HashMap<String, RegisteredService>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
-pub static NOTICE_SERVICES: Lazy<Mutex<HashMap<String, EventHandler>>> =
+pub static NOTICE_SERVICES: Lazy<Mutex<HashMap<String, RegisteredService>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
-pub static REQUEST_SERVICES: Lazy<Mutex<HashMap<String, EventHandler>>> =
+pub static REQUEST_SERVICES: Lazy<Mutex<HashMap<String, RegisteredService>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
 pub static RUNTIME: Lazy<Runtime> =
@@ -36,7 +62,7 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
     let mut notice_receiver = bot_guard.subscribe_notice();
     let mut request_receiver = bot_guard.subscribe_request();
     async fn dispatch(
-        handlers: &HashMap<String, EventHandler>,
+        handlers: &HashMap<String, RegisteredService>,
         event_arc: Arc<KritorEvent>,
         bot: Arc<RwLock<Bot>>,
     ) {
@@ -82,58 +108,60 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
             }
         };
 
-        for service_name in handlers.keys() {
-            let service = handlers.get(service_name).unwrap();
-            let service_clone = Arc::clone(service);
-            let event_clone = Arc::clone(&event_arc);
-            let context = KritorContext::new(
-                event_clone.as_ref().clone(),
-                bot.clone(),
-                service_name.clone(),
-                is_master,
-                at_bot
-            );
-            if let KritorEvent::Message(ref message) = event_arc.as_ref() {
-                let current_contact = message.contact.clone().unwrap();
-                let current_sender = message.sender.clone().unwrap();
-                if let Some((trans_context, _, _)) =
-                    con.read().await.iter().find(|(_, contact, sender)| {
-                        same_contact_and_sender(
-                            (contact, sender),
-                            (&current_contact, &current_sender),
-                        )
-                    })
+        if let KritorEvent::Message(ref message) = event_arc.as_ref() {
+            let current_contact = message.contact.clone().unwrap();
+            let current_sender = message.sender.clone().unwrap();
+            if let Some((trans_context, _, _)) =
+                con.read().await.iter().find(|(_, contact, sender)| {
+                    same_contact_and_sender(
+                        (contact, sender),
+                        (&current_contact, &current_sender),
+                    )
+                })
+            {
+                let trans_service_name = trans_context.current_service_name.read().await;
+                if let Some(service) = trans_service_name
+                    .as_ref()
+                    .and_then(|name| handlers.get(name))
                 {
-                    let trans_service_name = trans_context.current_service_name.read().await;
-                    if let Some(trans_service_name) = trans_service_name.as_ref() {
-                        // 当前服务就是锁定的trans服务
-                        if service_name == trans_service_name {
-                            let mut trans_context = trans_context.clone();
-                            trans_context.message = Some(message.clone());
-                            tokio::spawn(async move {
-                                service_clone.transaction(trans_context).await;
-                            });
-                            // 本条消息应该就只有这一次 不会被其他服务接受和处理
-                            break;
-                        }
-                    }
+                    // 锁定了trans服务，本条消息只交给该服务，不会被其他服务接受和处理
+                    let service = Arc::clone(&service.handler);
+                    let mut trans_context = trans_context.clone();
+                    trans_context.message = Some(message.clone());
+                    tokio::spawn(async move {
+                        service.transaction(trans_context).await;
+                    });
+                    return;
                 }
             }
-            // 正常情况，分发给各个服务
-            if service_clone.matches(context.clone()) {
-                let service_name = service_name.clone();
-                tokio::spawn(async move {
-                    debug!("Dispatching event to service: {}", service_name);
-                    service_clone.process(context).await;
-                    debug!("Service {} finished processing", service_name);
-                });
-            }
         }
+
+        // 按优先级排序，相同优先级按名称排序保证顺序稳定
+        let mut services: Vec<(String, RegisteredService)> = handlers
+            .iter()
+            .map(|(name, service)| (name.clone(), service.clone()))
+            .collect();
+        services.sort_by(|a, b| {
+            a.1.options
+                .priority
+                .cmp(&b.1.options.priority)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        let (fallbacks, services): (Vec<_>, Vec<_>) = services
+            .into_iter()
+            .partition(|(_, service)| service.options.fallback);
+        let event = event_arc.as_ref().clone();
+        tokio::spawn(async move {
+            let matched = run_chain(services, &event, &bot, is_master, at_bot).await;
+            if !matched {
+                run_chain(fallbacks, &event, &bot, is_master, at_bot).await;
+            }
+        });
     }
     // 异步打印日志
     let bot_clone = bot.clone();
     tokio::spawn(async move {
-        while let Ok(event) = message_receiver.recv().await {
+        while let Some(event) = message_receiver.recv().await {
             debug!("Received event: {:?}", event);
 
             let event_arc = Arc::new(KritorEvent::Message(event.clone())); // 将消息体包裹在Arc中
@@ -153,8 +181,8 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
                 match scene {
                     Scene::Group => {
                         let group_id = event.contact.as_ref().cloned().unwrap().peer;
-                        let group_id = group_id.parse().unwrap();
-                        let group = gl.get(&group_id);
+                        // 其他平台的群号不一定是数字
+                        let group = group_id.parse::<u64>().ok().and_then(|id| gl.get(&id));
                         let content = event.elements.clone().get_raw_msg();
                         let sender = event.sender.as_ref().unwrap();
                         let uin = sender.uin.unwrap_or(0);
@@ -209,6 +237,19 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
                             }
                         }
                     }
+                    Scene::Guild => {
+                        let contact = event.contact.as_ref().cloned().unwrap();
+                        let sender = event.sender.as_ref().unwrap();
+                        let content = event.elements.clone().get_raw_msg();
+                        info!(
+                            "[Guild: {}/{}] {}({}): {}",
+                            contact.peer,
+                            contact.sub_peer.unwrap_or_default(),
+                            sender.nick.clone().unwrap_or_default(),
+                            sender.uid,
+                            content
+                        );
+                    }
                     _ => info!("[{:?}]", event),
                 }
             });
@@ -216,8 +257,10 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
     });
     let bot_clone = bot.clone();
     tokio::spawn(async move {
-        while let Ok(event) = notice_receiver.recv().await {
+        while let Some(event) = notice_receiver.recv().await {
             debug!("Received event: {:?}", event);
+            // 先更新缓存，服务处理时拿到的是最新的群、好友信息
+            Bot::apply_notice(&bot_clone, &event).await;
             let event_arc = Arc::new(KritorEvent::Notice(event)); // 将消息体包裹在Arc中
             let handlers = NOTICE_SERVICES.lock().await;
             dispatch(&handlers, event_arc, bot_clone.clone()).await;
@@ -225,7 +268,7 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
     });
     let bot_clone = bot.clone();
     tokio::spawn(async move {
-        while let Ok(event) = request_receiver.recv().await {
+        while let Some(event) = request_receiver.recv().await {
             debug!("Received event: {:?}", event);
             let event_arc = Arc::new(KritorEvent::Request(event)); // 将消息体包裹在Arc中
 
@@ -235,10 +278,87 @@ pub async fn listen_to_events(bot: Arc<RwLock<Bot>>) {
     });
 }
 
-pub fn register_service(service: Arc<dyn Service + Send + Sync>, event: Vec<Event>, name: String) {
+/// 按优先级经过中间件执行匹配的服务，相同优先级的服务并发执行，
+/// 有服务返回Stop时不再执行后面的服务。返回是否有服务匹配，返回Ignored的服务不算匹配
+async fn run_chain(
+    services: Vec<(String, RegisteredService)>,
+    event: &KritorEvent,
+    bot: &Arc<RwLock<Bot>>,
+    is_master: bool,
+    at_bot: bool,
+) -> bool {
+    let mut matched = false;
+    let mut services = services.into_iter().peekable();
+    while let Some((name, service)) = services.next() {
+        let priority = service.options.priority;
+        let mut level = vec![(name, service)];
+        while let Some(next) = services.next_if(|(_, s)| s.options.priority == priority) {
+            level.push(next);
+        }
+        let tasks = level
+            .into_iter()
+            .map(|(service_name, service)| {
+                let context = KritorContext::new(
+                    event.clone(),
+                    bot.clone(),
+                    service_name.clone(),
+                    is_master,
+                    at_bot,
+                );
+                tokio::spawn(invoke(
+                    service_name,
+                    Arc::clone(&service.handler),
+                    service.options.permission,
+                    context,
+                ))
+            })
+            .collect::<Vec<_>>();
+        let results = join_all(tasks)
+            .await
+            .into_iter()
+            .filter_map(|result| result.ok().flatten())
+            .filter(|result| *result != ProcessResult::Ignored)
+            .collect::<Vec<ProcessResult>>();
+        if results.is_empty() {
+            continue;
+        }
+        matched = true;
+        if results.contains(&ProcessResult::Stop) {
+            break;
+        }
+    }
+    matched
+}
+
+/// 通知所有服务Bot的连接状态变化
+pub async fn dispatch_lifecycle(bot: Arc<RwLock<Bot>>, event: LifecycleEvent) {
+    let mut services: HashMap<String, EventHandler> = HashMap::new();
+    for handlers in [&MESSAGE_SERVICES, &NOTICE_SERVICES, &REQUEST_SERVICES] {
+        let handlers = handlers.lock().await;
+        services.extend(
+            handlers
+                .iter()
+                .map(|(name, service)| (name.clone(), Arc::clone(&service.handler))),
+        );
+    }
+    for (service_name, service) in services {
+        let bot = bot.clone();
+        tokio::spawn(async move {
+            debug!("Dispatching {:?} to service: {}", event, service_name);
+            service.on_lifecycle(bot, event).await;
+        });
+    }
+}
+
+pub fn register_service(
+    service: Arc<dyn Service + Send + Sync>,
+    event: Vec<Event>,
+    name: String,
+    options: ServiceOptions,
+) {
     let _guard = RUNTIME.enter();
     let future = async {
-        _register_service(service, event, name).await;
+        _register_service(service, event, name, options).await;
         let mut initialized = INITIALIZED.lock().await;
         *initialized = true;
     };
@@ -249,9 +369,14 @@ async fn _register_service(
     service: Arc<dyn Service + Send + Sync>,
     event: Vec<Event>,
     name: String,
+    options: ServiceOptions,
 ) {
     Lazy::force(&LOG_INIT);
-    info!("Registering service \"{}\" with events {:?}", name, event);
+    info!(
+        "Registering service \"{}\" with events {:?}, {:?}",
+        name, event, options
+    );
+    let service = RegisteredService::new(service, options);
     for et in event {
         match et {
             Event::Notice => {
@@ -4,10 +4,11 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
-use log::warn;
+use log::{debug, warn};
 use tokio::sync::RwLock;
 
 use crate::bot::bot::Bot;
+use crate::bot::process::RequestKind;
 use crate::kritor::server::kritor_proto::common::element::{Data, ElementType};
 use crate::kritor::server::kritor_proto::common::{
     image_element, video_element, voice_element, AtElement, Contact, Element, FileElement,
@@ -19,7 +20,10 @@ use crate::kritor::server::kritor_proto::notice_event::Notice;
 use crate::kritor::server::kritor_proto::{
     EventType, NoticeEvent, RequestEvent, SendMessageResponse,
 };
+use crate::model::config::get_config;
 use crate::model::error::Result;
+use crate::model::permission::{Role, PERMISSION_STORE};
+use crate::model::policy::Scope;
 use crate::service::register::KritorEvent;
 use crate::{client_err, err};
 
@@ -81,10 +85,16 @@ impl KritorContext {
     pub async fn reply(&self, elements: Vec<Element>) -> Result<SendMessageResponse> {
         match self.r#type {
             EventType::Message => {
+                let priority = self.is_privileged().await;
                 let bot_guard = self.bot.read().await;
                 let msg = self.message.as_ref().cloned().unwrap();
+                let contact = msg.contact.as_ref().cloned().unwrap();
+                // 频道消息需要回复到子频道
+                if contact.scene == i32::from(Scene::Guild) && contact.sub_peer.is_none() {
+                    return client_err!("Guild contact without channel");
+                }
                 bot_guard
-                    .send_msg(elements, msg.contact.as_ref().cloned().unwrap())
+                    .send_msg_with_priority(elements, contact, priority)
                     .await
             }
             EventType::Notice => {
@@ -94,8 +104,11 @@ impl KritorContext {
                     .map(|n| Event::Notice(n.clone()))
                     .unwrap();
                 let contact = get_concat_from_event(&event).0.unwrap();
+                let priority = self.is_privileged().await;
                 let bot_guard = self.bot.read().await;
-                bot_guard.send_msg(elements, contact).await
+                bot_guard
+                    .send_msg_with_priority(elements, contact, priority)
+                    .await
             }
             EventType::Request => {
                 client_err!("Cannot reply to request")
@@ -106,6 +119,94 @@ impl KritorContext {
         }
     }
 
+    /// 事件所在的会话与触发者，请求事件没有会话
+    pub fn get_contact_and_sender(&self) -> (Option<Contact>, Option<Sender>) {
+        let event = match self.r#type {
+            EventType::Message => self.message.as_ref().cloned().map(Message),
+            EventType::Notice => self.notice.as_ref().cloned().map(Event::Notice),
+            _ => None,
+        };
+        event
+            .as_ref()
+            .map(get_concat_from_event)
+            .unwrap_or((None, None))
+    }
+
+    /// 服务开关的范围，群聊为群号，私聊为对方的uin
+    pub fn scope(&self) -> Option<Scope> {
+        let (contact, sender) = self.get_contact_and_sender();
+        let contact = contact?;
+        match Scene::try_from(contact.scene) {
+            Ok(Scene::Group) => Some(Scope::Group(contact.peer)),
+            Ok(Scene::Guild) | Err(_) => None,
+            Ok(_) => Some(Scope::Private(
+                sender
+                    .and_then(|sender| sender.uin)
+                    .map(|uin| uin.to_string())
+                    .unwrap_or(contact.peer),
+            )),
+        }
+    }
+
+    /// 触发者的角色，取配置、群信息与手动授予的角色中最高的
+    pub async fn role(&self) -> Role {
+        if self.is_master {
+            return Role::Owner;
+        }
+        let (contact, sender) = self.get_contact_and_sender();
+        let Some(sender) = sender else {
+            return Role::Member;
+        };
+        // 配置与授予的角色可以用uin或uid指定
+        let ids = [Some(sender.uid.clone()), sender.uin.map(|uin| uin.to_string())]
+            .into_iter()
+            .flatten()
+            .filter(|id| !id.is_empty())
+            .collect::<Vec<String>>();
+        let mut role = Role::Member;
+        let config = get_config().await;
+        if let Some(admins) = config.admins.as_ref() {
+            if ids.iter().any(|id| admins.contains(id)) {
+                role = Role::Admin;
+            }
+        }
+        if let (Some(contact), Some(uin)) = (contact, sender.uin) {
+            role = role.max(self.group_role(&contact, uin).await);
+        }
+        if let Some(store) = PERMISSION_STORE.as_ref() {
+            let scope = self.scope();
+            for id in ids.iter() {
+                role = role.max(store.get(id, scope.as_ref()).unwrap_or_default());
+            }
+        }
+        role
+    }
+
+    /// 根据群信息判断群主、群管理员，缓存中没有群主时通过GetGroupInfo获取
+    async fn group_role(&self, contact: &Contact, uin: u64) -> Role {
+        if contact.scene != i32::from(Scene::Group) {
+            return Role::Member;
+        }
+        let Ok(group_id) = contact.peer.parse::<u64>() else {
+            return Role::Member;
+        };
+        let bot = self.bot.read().await;
+        match bot.get_group_roles(group_id).await {
+            Ok(info) if info.owner == uin => Role::GroupOwner,
+            Ok(info) if info.admins.contains(&uin) => Role::GroupAdmin,
+            Ok(_) => Role::Member,
+            Err(e) => {
+                debug!("Failed to get roles of group {}: {}", group_id, e);
+                Role::Member
+            }
+        }
+    }
+
+    /// 主人、Bot管理员或群主、群管理员触发的事件，回复时走发送队列的优先通道
+    async fn is_privileged(&self) -> bool {
+        self.role().await >= Role::GroupAdmin
+    }
+
     pub async fn reply_with_quote(&self, elements: Vec<Element>) -> Result<SendMessageResponse> {
         let mut elements = elements;
         if self.r#type == EventType::Message {
@@ -148,6 +249,33 @@ impl KritorContext {
         Ok(())
     }
 
+    /// 同意请求事件（好友申请、加群申请、邀请入群）
+    pub async fn approve(&self) -> Result<()> {
+        self.set_request_result(true, None).await
+    }
+
+    /// 拒绝请求事件，reason仅对加群申请有效
+    pub async fn reject(&self, reason: Option<String>) -> Result<()> {
+        self.set_request_result(false, reason).await
+    }
+
+    async fn set_request_result(&self, is_approve: bool, reason: Option<String>) -> Result<()> {
+        let Some(request) = self.request.as_ref() else {
+            return client_err!("Not a request event");
+        };
+        let Some(kind) = RequestKind::of(request) else {
+            return client_err!("Unknown request type");
+        };
+        Bot::settle_request(
+            &self.bot,
+            kind,
+            request.request_id.clone(),
+            is_approve,
+            reason,
+        )
+        .await
+    }
+
     pub async fn stop_transaction(&self) -> Result<()> {
         let bot = self.bot.clone();
         // 让bot继续广播并结束本次事务
@@ -160,17 +288,40 @@ impl KritorContext {
     }
 }
 
+/// Bot连接状态变化的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// 连接建立并完成初始化（包括重连）
+    Connected,
+    /// 连接断开
+    Disconnected,
+}
+
+/// 服务处理的结果，Stop时不再分发给优先级更低的服务
+///
+/// Ignored表示服务匹配后没有处理该事件，不算作匹配，fallback服务仍会执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProcessResult {
+    #[default]
+    Continue,
+    Stop,
+    Ignored,
+}
+
 #[async_trait]
 pub trait Service: Matchable {
     fn pre_process(&self, context: KritorContext) -> KritorContext {
         context
     }
 
-    async fn process(&self, context: KritorContext);
+    async fn process(&self, context: KritorContext) -> ProcessResult;
 
     async fn transaction(&self, _context: KritorContext) {
         warn!("default transaction");
     }
+
+    /// Bot上线或离线时调用
+    async fn on_lifecycle(&self, _bot: Arc<RwLock<Bot>>, _event: LifecycleEvent) {}
 }
 
 #[async_trait]
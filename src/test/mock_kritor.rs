@@ -0,0 +1,245 @@
+#![cfg(test)]
+//! 进程内模拟的Kritor端，连接随机端口上的avocado服务，用于端到端测试
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use dashmap::DashMap;
+use prost::Message;
+use tokio::net::TcpListener;
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, RwLock};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Request;
+
+use crate::bot::bot::{Bot, BotState};
+use crate::kritor::server::kritor_proto::common;
+use crate::kritor::server::kritor_proto::event_service_client::EventServiceClient;
+use crate::kritor::server::kritor_proto::event_structure::Event;
+use crate::kritor::server::kritor_proto::reverse_service_client::ReverseServiceClient;
+use crate::kritor::server::kritor_proto::{EventStructure, EventType, SendMessageRequest};
+use crate::kritor::server::{serve_with_listener, BOTS};
+use crate::model::config::ServerConfig;
+use crate::service::middleware::{register_middleware, unregister_middleware, Middleware};
+use crate::service::register::{RegisteredService, MESSAGE_SERVICES};
+
+const WAIT: Duration = Duration::from_secs(5);
+
+/// 测试中注册的服务与中间件，drop时从全局注册表中移除，避免影响其他测试
+#[derive(Default)]
+pub struct MockRegistry {
+    services: Vec<String>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl MockRegistry {
+    pub async fn service(&mut self, name: &str, service: RegisteredService) {
+        MESSAGE_SERVICES
+            .lock()
+            .await
+            .insert(name.to_string(), service);
+        self.services.push(name.to_string());
+    }
+
+    pub async fn middleware(&mut self, middleware: Arc<dyn Middleware>) {
+        register_middleware(middleware.clone()).await;
+        self.middlewares.push(middleware);
+    }
+}
+
+impl Drop for MockRegistry {
+    /// Drop中不能await，需要在多线程运行时中阻塞等待
+    fn drop(&mut self) {
+        let services = std::mem::take(&mut self.services);
+        let middlewares = std::mem::take(&mut self.middlewares);
+        tokio::task::block_in_place(|| {
+            Handle::current().block_on(async {
+                let mut handlers = MESSAGE_SERVICES.lock().await;
+                for name in services {
+                    handlers.remove(&name);
+                }
+                drop(handlers);
+                for middleware in middlewares {
+                    unregister_middleware(&middleware).await;
+                }
+            })
+        });
+    }
+}
+
+pub struct MockKritor {
+    pub uid: String,
+    pub uin: u64,
+    /// 按cmd预设的响应，未预设的cmd返回空响应
+    responses: Arc<DashMap<String, Vec<u8>>>,
+    /// 不做响应的cmd，用于测试超时
+    silent: Arc<Mutex<HashSet<String>>>,
+    /// 返回错误code的cmd
+    failures: Arc<DashMap<String, (i32, String)>>,
+    requests: Option<mpsc::UnboundedReceiver<common::Request>>,
+    events: Option<mpsc::Sender<EventStructure>>,
+}
+
+fn with_identity<T>(message: T, uid: &str, uin: u64) -> Request<T> {
+    let mut request = Request::new(message);
+    let metadata = request.metadata_mut();
+    metadata.insert("kritor-self-uid", uid.parse().unwrap());
+    metadata.insert("kritor-self-uin", uin.to_string().parse().unwrap());
+    metadata.insert("kritor-self-version", "mock".parse().unwrap());
+    request
+}
+
+impl MockKritor {
+    /// 每个测试使用不同的uid，避免共用全局的BOTS
+    pub fn new(uid: &str, uin: u64) -> Self {
+        Self {
+            uid: uid.to_string(),
+            uin,
+            responses: Arc::new(DashMap::new()),
+            silent: Arc::new(Mutex::new(HashSet::new())),
+            failures: Arc::new(DashMap::new()),
+            requests: None,
+            events: None,
+        }
+    }
+
+    pub fn respond<M: Message>(&self, cmd: &str, response: M) {
+        self.responses
+            .insert(cmd.to_string(), response.encode_to_vec());
+    }
+
+    pub fn silence(&self, cmd: &str) {
+        self.silent.lock().unwrap().insert(cmd.to_string());
+    }
+
+    pub fn fail(&self, cmd: &str, code: i32, msg: &str) {
+        self.failures.insert(cmd.to_string(), (code, msg.to_string()));
+    }
+
+    /// 在随机端口启动avocado服务，并以Kritor端身份建立反向流和事件流
+    pub async fn connect(&mut self) -> Arc<RwLock<Bot>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_with_listener(listener, ServerConfig::default()));
+        let channel = Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        self.open_reverse_stream(channel.clone()).await;
+        self.open_event_stream(channel);
+        self.wait_online().await
+    }
+
+    async fn open_reverse_stream(&mut self, channel: Channel) {
+        let (response_tx, response_rx) = mpsc::channel(1024);
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let mut client = ReverseServiceClient::new(channel);
+        let mut stream = client
+            .reverse_stream(with_identity(
+                ReceiverStream::new(response_rx),
+                &self.uid,
+                self.uin,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        let responses = self.responses.clone();
+        let silent = self.silent.clone();
+        let failures = self.failures.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(request)) = stream.next().await {
+                let _ = request_tx.send(request.clone());
+                if request.no_response || silent.lock().unwrap().contains(&request.cmd) {
+                    continue;
+                }
+                let buf = responses
+                    .get(&request.cmd)
+                    .map(|buf| buf.clone())
+                    .unwrap_or_default();
+                let (code, msg) = failures
+                    .get(&request.cmd)
+                    .map(|failure| (failure.0, Some(failure.1.clone())))
+                    .unwrap_or((0, None));
+                let response = common::Response {
+                    cmd: request.cmd,
+                    seq: request.seq,
+                    code,
+                    msg,
+                    buf,
+                };
+                if response_tx.send(response).await.is_err() {
+                    break;
+                }
+            }
+        });
+        self.requests = Some(request_rx);
+    }
+
+    fn open_event_stream(&mut self, channel: Channel) {
+        let (event_tx, event_rx) = mpsc::channel(1024);
+        let mut client = EventServiceClient::new(channel);
+        let request = with_identity(ReceiverStream::new(event_rx), &self.uid, self.uin);
+        tokio::spawn(async move {
+            let _ = client.register_passive_listener(request).await;
+        });
+        self.events = Some(event_tx);
+    }
+
+    async fn wait_online(&self) -> Arc<RwLock<Bot>> {
+        tokio::time::timeout(WAIT, async {
+            loop {
+                let bot = BOTS.read().await.get(&self.uid).map(|bot| bot.clone());
+                if let Some(bot) = bot {
+                    if bot.read().await.get_state().await == BotState::Online {
+                        return bot;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("bot should come online")
+    }
+
+    pub async fn push(&self, event: Event) {
+        let r#type = match event {
+            Event::Message(_) => EventType::Message,
+            Event::Notice(_) => EventType::Notice,
+            Event::Request(_) => EventType::Request,
+        };
+        let event = EventStructure {
+            r#type: r#type.into(),
+            event: Some(event),
+        };
+        self.events
+            .as_ref()
+            .expect("not connected")
+            .send(event)
+            .await
+            .unwrap();
+    }
+
+    /// 等待Bot发出指定cmd的请求，跳过其他请求
+    pub async fn expect_request(&mut self, cmd: &str) -> common::Request {
+        let requests = self.requests.as_mut().expect("not connected");
+        tokio::time::timeout(WAIT, async {
+            loop {
+                let request = requests.recv().await.expect("reverse stream closed");
+                if request.cmd == cmd {
+                    return request;
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("no {} request received", cmd))
+    }
+
+    pub async fn expect_message(&mut self) -> SendMessageRequest {
+        let request = self.expect_request("MessageService.SendMessage").await;
+        SendMessageRequest::decode(request.buf.as_slice()).unwrap()
+    }
+}
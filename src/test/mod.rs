@@ -1,3 +1,15 @@
+mod mock_kritor;
+mod test_auth;
 mod test_boa;
+mod test_cache;
+mod test_channel;
+mod test_client;
 mod test_image;
+mod test_kritor;
+mod test_limiter;
+mod test_onebot;
+mod test_permission;
+mod test_policy;
+mod test_record;
+mod test_satori;
 mod test_time;
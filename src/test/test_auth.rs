@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tonic::metadata::MetadataMap;
+    use tonic::Code;
+
+    use crate::kritor::auth::authenticate;
+    use crate::model::config::TicketConfig;
+
+    fn metadata(pairs: &[(&'static str, &str)]) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        for (key, value) in pairs {
+            metadata.insert(*key, value.parse().unwrap());
+        }
+        metadata
+    }
+
+    fn tickets() -> HashMap<String, TicketConfig> {
+        HashMap::from([
+            (
+                "u_1".to_string(),
+                TicketConfig::Account {
+                    uin: Some(10001),
+                    tickets: vec!["secret".to_string()],
+                },
+            ),
+            (
+                "u_2".to_string(),
+                TicketConfig::Tickets(vec!["other".to_string()]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn without_tickets_accepts_any_connection() {
+        let md = metadata(&[("kritor-self-uid", "u_1"), ("kritor-self-uin", "10001")]);
+        let identity = authenticate(&md, None).unwrap();
+        assert_eq!(identity.uid, "u_1");
+        assert_eq!(identity.uin, 10001);
+    }
+
+    #[test]
+    fn ticket_is_checked() {
+        let tickets = tickets();
+        let ok = metadata(&[
+            ("kritor-self-uid", "u_1"),
+            ("kritor-self-uin", "10001"),
+            ("authorization", "Bearer secret"),
+        ]);
+        assert!(authenticate(&ok, Some(&tickets)).is_ok());
+
+        let ok = metadata(&[
+            ("kritor-self-uid", "u_1"),
+            ("kritor-self-uin", "10001"),
+            ("ticket", "secret"),
+        ]);
+        assert!(authenticate(&ok, Some(&tickets)).is_ok());
+
+        let wrong = metadata(&[
+            ("kritor-self-uid", "u_1"),
+            ("kritor-self-uin", "10001"),
+            ("ticket", "guess"),
+        ]);
+        let status = authenticate(&wrong, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let missing = metadata(&[("kritor-self-uid", "u_1"), ("kritor-self-uin", "10001")]);
+        let status = authenticate(&missing, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let other = metadata(&[
+            ("kritor-self-uid", "u_3"),
+            ("kritor-self-uin", "10003"),
+            ("ticket", "secret"),
+        ]);
+        let status = authenticate(&other, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn ticket_is_bound_to_account() {
+        let tickets = tickets();
+        // 合法的uin配上其他账号的uid，不能接管其他Bot
+        let hijack = metadata(&[
+            ("kritor-self-uid", "u_2"),
+            ("kritor-self-uin", "10001"),
+            ("ticket", "secret"),
+        ]);
+        let status = authenticate(&hijack, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // uid正确但uin与配置不一致
+        let mismatch = metadata(&[
+            ("kritor-self-uid", "u_1"),
+            ("kritor-self-uin", "10002"),
+            ("ticket", "secret"),
+        ]);
+        let status = authenticate(&mismatch, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // 配置了uin的账号必须携带uin
+        let without_uin = metadata(&[("kritor-self-uid", "u_1"), ("ticket", "secret")]);
+        let status = authenticate(&without_uin, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        let zero_uin = metadata(&[
+            ("kritor-self-uid", "u_1"),
+            ("kritor-self-uin", "0"),
+            ("ticket", "secret"),
+        ]);
+        let status = authenticate(&zero_uin, Some(&tickets)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // 未配置uin的账号只校验uid
+        let own = metadata(&[("kritor-self-uid", "u_2"), ("ticket", "other")]);
+        assert!(authenticate(&own, Some(&tickets)).is_ok());
+    }
+
+    #[test]
+    fn malformed_handshake_is_rejected() {
+        let status = authenticate(&metadata(&[("kritor-self-uin", "10001")]), None).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let md = metadata(&[("kritor-self-uid", "u_1"), ("kritor-self-uin", "abc")]);
+        let status = authenticate(&md, None).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+}
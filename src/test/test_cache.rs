@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use crate::bot::bot::Bot;
+    use crate::bot::group::Group;
+    use crate::kritor::server::kritor_proto::notice_event::Notice;
+    use crate::kritor::server::kritor_proto::*;
+    use crate::model::cache::CacheStore;
+    use crate::model::config::EventConfig;
+
+    fn notice(notice: Notice) -> NoticeEvent {
+        NoticeEvent {
+            notice: Some(notice),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn notices_update_group_cache() {
+        let bot = Bot::new(10000, "10000".to_string(), None, None, &EventConfig::default());
+        let bot = Arc::new(RwLock::new(bot));
+        let members = [10001, 10002]
+            .into_iter()
+            .map(|uin| {
+                let member = GroupMemberInfo {
+                    uid: uin.to_string(),
+                    uin,
+                    ..Default::default()
+                };
+                (uin, member)
+            })
+            .collect::<HashMap<u64, GroupMemberInfo>>();
+        let group = Group::new(
+            GroupInfo {
+                group_id: 20001,
+                ..Default::default()
+            },
+            members,
+        );
+        bot.read()
+            .await
+            .get_groups_arc()
+            .write()
+            .await
+            .replace(HashMap::from([(20001, group)]));
+
+        Bot::apply_notice(
+            &bot,
+            &notice(Notice::GroupCardChanged(GroupCardChangedNotice {
+                group_id: 20001,
+                operator_uid: "10001".to_string(),
+                operator_uin: 10001,
+                target_uid: "10001".to_string(),
+                target_uin: 10001,
+                new_card: "card".to_string(),
+            })),
+        )
+        .await;
+        Bot::apply_notice(
+            &bot,
+            &notice(Notice::GroupAdminChange(GroupAdminChangedNotice {
+                group_id: 20001,
+                target_uid: "10001".to_string(),
+                target_uin: 10001,
+                is_admin: true,
+            })),
+        )
+        .await;
+        Bot::apply_notice(
+            &bot,
+            &notice(Notice::GroupMemberDecrease(GroupMemberDecreasedNotice {
+                group_id: 20001,
+                target_uid: Some("10002".to_string()),
+                ..Default::default()
+            })),
+        )
+        .await;
+
+        let groups = bot.read().await.get_groups().await.unwrap();
+        let group = groups.get(&20001).unwrap();
+        assert_eq!(group.members.get(&10001).unwrap().card, "card");
+        assert_eq!(group.inner.admins, vec![10001]);
+        assert!(!group.members.contains_key(&10002));
+
+        // Bot自己退群时移除整个群
+        Bot::apply_notice(
+            &bot,
+            &notice(Notice::GroupMemberDecrease(GroupMemberDecreasedNotice {
+                group_id: 20001,
+                target_uin: Some(10000),
+                ..Default::default()
+            })),
+        )
+        .await;
+        assert!(bot.read().await.get_groups().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let store = CacheStore::open(":memory:").unwrap();
+        let groups = [20001, 20002].map(|group_id| GroupInfo {
+            group_id,
+            ..Default::default()
+        });
+        store.save_groups("bot", &groups).unwrap();
+        let member = GroupMemberInfo {
+            uin: 10001,
+            card: "card".to_string(),
+            ..Default::default()
+        };
+        store.save_members("bot", 20001, &[member]).unwrap();
+
+        let cached = store.load_groups("bot").unwrap();
+        assert_eq!(cached.len(), 2);
+        let loaded = cached.iter().find(|g| g.info.group_id == 20001).unwrap();
+        assert!(loaded.members_updated_at.is_some());
+        assert_eq!(loaded.members[0].card, "card");
+        let lazy = cached.iter().find(|g| g.info.group_id == 20002).unwrap();
+        assert!(lazy.members_updated_at.is_none());
+
+        // 不在新列表中的群连同成员一起删除
+        store.save_groups("bot", &groups[1..]).unwrap();
+        let cached = store.load_groups("bot").unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].info.group_id, 20002);
+        assert!(store.load_groups("other").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bot::channel::EventChannel;
+
+    #[tokio::test]
+    async fn lagged_receiver_keeps_receiving() {
+        let channel = EventChannel::new("test", 2, false);
+        let mut receiver = channel.subscribe();
+        for i in 0..5 {
+            channel.send(i).await;
+        }
+        // 积压的事件被跳过，之后的事件仍能收到
+        assert_eq!(receiver.recv().await, Some(3));
+        assert_eq!(receiver.recv().await, Some(4));
+        channel.send(5).await;
+        assert_eq!(receiver.recv().await, Some(5));
+    }
+
+    #[tokio::test]
+    async fn backpressure_waits_for_consumer() {
+        let channel = EventChannel::new("test", 1, true);
+        let mut receiver = channel.subscribe();
+        channel.send(1).await;
+        let blocked = tokio::time::timeout(Duration::from_millis(50), channel.send(2)).await;
+        assert!(blocked.is_err());
+        assert_eq!(receiver.recv().await, Some(1));
+        channel.send(3).await;
+        assert_eq!(receiver.recv().await, Some(3));
+        // 消费者断开后不再等待
+        drop(receiver);
+        channel.send(4).await;
+        channel.send(5).await;
+    }
+
+    #[tokio::test]
+    async fn lossy_receiver_does_not_block_backpressure() {
+        let channel = EventChannel::new("test", 1, true);
+        let mut receiver = channel.subscribe();
+        let mut lossy = channel.subscribe_lossy();
+        channel.send(1).await;
+        assert_eq!(receiver.recv().await, Some(1));
+        // 外部订阅者不读取也不会让发送方等待
+        channel.send(2).await;
+        assert_eq!(receiver.recv().await, Some(2));
+        assert_eq!(lossy.recv().await, Some(2));
+    }
+}
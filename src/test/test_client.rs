@@ -0,0 +1,20 @@
+#[cfg(test)]
+mod tests {
+    use crate::kritor::client::cmd_to_path;
+
+    #[test]
+    fn cmd_maps_to_grpc_path() {
+        for cmd in ["MessageService.SendMessage", "MessageService.SendMessageRequest"] {
+            assert_eq!(
+                cmd_to_path(cmd).unwrap().path(),
+                "/kritor.message.MessageService/SendMessage"
+            );
+        }
+        assert_eq!(
+            cmd_to_path("GroupFileService.GetFileListRequest").unwrap().path(),
+            "/kritor.file.GroupFileService/GetFileList"
+        );
+        assert!(cmd_to_path("UnknownService.Foo").is_err());
+        assert!(cmd_to_path("SendMessage").is_err());
+    }
+}
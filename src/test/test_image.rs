@@ -762,4 +762,14 @@ mod tests {
             println!("{}", cpu.frequency());
         }
     }
+
+    #[test]
+    fn image_extension_from_bytes() {
+        use crate::adapter::console::image_extension;
+        assert_eq!(Some("png"), image_extension(b"\x89PNG\r\n\x1a\n0000"));
+        assert_eq!(Some("jpg"), image_extension(&[0xFF, 0xD8, 0xFF, 0xE0]));
+        assert_eq!(Some("gif"), image_extension(b"GIF89a"));
+        assert_eq!(Some("webp"), image_extension(b"RIFF\0\0\0\0WEBPVP8 "));
+        assert_eq!(None, image_extension(b"hello"));
+    }
 }
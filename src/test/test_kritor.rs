@@ -0,0 +1,718 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use prost::Message;
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, RwLock};
+    use tokio_stream::StreamExt;
+    use tonic::transport::Endpoint;
+
+    use crate::bot::bot::{Bot, BotState};
+    use crate::bot::core::CoreAPITrait;
+    use crate::kritor::server::kritor_proto::common::element::Data;
+    use crate::kritor::server::kritor_proto::common::{
+        self, Contact, PushMessageBody, Scene, Sender,
+    };
+    use crate::kritor::server::kritor_proto::event_structure::Event;
+    use crate::kritor::server::kritor_proto::event_service_client::EventServiceClient;
+    use crate::kritor::client::cmd_to_path;
+    use crate::kritor::server::kritor_proto::{
+        EventType, GetGroupInfoResponse, GetGroupListRequest, GetGroupListResponse,
+        GetVersionRequest, GetVersionResponse, GroupInfo, RequestPushEvent, SendMessageRequest,
+    };
+    use crate::kritor::server::serve_with_listener;
+    use crate::model::config::{EventConfig, ServerConfig};
+    use crate::model::permission::Role;
+    use crate::service::middleware::{Middleware, ServiceOutcome};
+    use crate::service::register::{RegisteredService, ServiceOptions};
+    use crate::service::service::{Elements, KritorContext, Matchable, ProcessResult, Service};
+    use crate::test::mock_kritor::{MockKritor, MockRegistry};
+    use crate::text;
+
+    fn group_message(text: &str, sender: u64) -> Event {
+        Event::Message(PushMessageBody {
+            time: 1700000000,
+            message_id: format!("mock-{}", text),
+            contact: Some(Contact {
+                scene: Scene::Group.into(),
+                peer: "30001".to_string(),
+                sub_peer: None,
+            }),
+            sender: Some(Sender {
+                uid: sender.to_string(),
+                uin: Some(sender),
+                nick: Some("tester".to_string()),
+            }),
+            elements: vec![text!(text)],
+            ..Default::default()
+        })
+    }
+
+    fn text_of(context: &KritorContext) -> String {
+        context
+            .message
+            .as_ref()
+            .and_then(|message| message.elements.get_text_elements())
+            .map(|texts| texts.into_iter().map(|t| t.text).collect())
+            .unwrap_or_default()
+    }
+
+    fn sent_text(request: &crate::kritor::server::kritor_proto::SendMessageRequest) -> String {
+        request
+            .elements
+            .iter()
+            .filter_map(|element| match element.data.as_ref() {
+                Some(Data::Text(text)) => Some(text.text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    struct MockPing;
+
+    impl Matchable for MockPing {
+        fn matches(&self, context: KritorContext) -> bool {
+            text_of(&context) == "mock-ping"
+        }
+    }
+
+    #[async_trait]
+    impl Service for MockPing {
+        async fn process(&self, context: KritorContext) -> ProcessResult {
+            context.reply(vec![text!("mock-pong")]).await.unwrap();
+            ProcessResult::Stop
+        }
+    }
+
+    struct MockAsk;
+
+    impl Matchable for MockAsk {
+        fn matches(&self, context: KritorContext) -> bool {
+            text_of(&context) == "mock-ask"
+        }
+    }
+
+    #[async_trait]
+    impl Service for MockAsk {
+        async fn process(&self, context: KritorContext) -> ProcessResult {
+            context
+                .start_transaction("mock-ask".to_string(), Some(10))
+                .await
+                .unwrap();
+            context.reply(vec![text!("mock-what")]).await.unwrap();
+            ProcessResult::Stop
+        }
+
+        async fn transaction(&self, context: KritorContext) {
+            let answer = text_of(&context);
+            context
+                .reply(vec![text!(format!("mock-got {}", answer))])
+                .await
+                .unwrap();
+            context.stop_transaction().await.unwrap();
+        }
+    }
+
+    /// 匹配text的消息时回复reply
+    struct MockReply {
+        text: &'static str,
+        reply: &'static str,
+        result: ProcessResult,
+    }
+
+    impl Matchable for MockReply {
+        fn matches(&self, context: KritorContext) -> bool {
+            text_of(&context) == self.text
+        }
+    }
+
+    #[async_trait]
+    impl Service for MockReply {
+        async fn process(&self, context: KritorContext) -> ProcessResult {
+            context.reply(vec![text!(self.reply)]).await.unwrap();
+            self.result
+        }
+    }
+
+    /// 与js插件一样匹配所有事件，但没有处理
+    struct MockIgnore;
+
+    impl Matchable for MockIgnore {
+        fn matches(&self, _context: KritorContext) -> bool {
+            true
+        }
+    }
+
+    #[async_trait]
+    impl Service for MockIgnore {
+        async fn process(&self, _context: KritorContext) -> ProcessResult {
+            ProcessResult::Ignored
+        }
+    }
+
+    /// 把mock-alias改写为mock-rewritten，并记录执行过的服务
+    #[derive(Default)]
+    struct MockMiddleware {
+        processed: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Middleware for MockMiddleware {
+        async fn before(&self, mut context: KritorContext) -> Option<KritorContext> {
+            if text_of(&context) == "mock-alias" {
+                if let Some(message) = context.message.as_mut() {
+                    message.elements = vec![text!("mock-rewritten")];
+                }
+            }
+            Some(context)
+        }
+
+        async fn after(&self, _context: &KritorContext, outcome: &ServiceOutcome) {
+            self.processed
+                .lock()
+                .unwrap()
+                .push(outcome.service_name.clone());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn request_round_trip_and_timeout() {
+        let mut mock = MockKritor::new("mock_request", 40001);
+        mock.respond(
+            "CoreService.GetVersion",
+            GetVersionResponse {
+                version: "1.0.0".to_string(),
+                app_name: "mock".to_string(),
+            },
+        );
+        mock.silence("CoreService.SwitchAccount");
+        let bot = mock.connect().await;
+
+        let request = common::Request {
+            cmd: "CoreService.GetVersion".to_string(),
+            seq: 1,
+            buf: GetVersionRequest {}.encode_to_vec(),
+            no_response: false,
+        };
+        let response = bot
+            .read()
+            .await
+            .send_request_with_timeout(request, Some(Duration::from_secs(2)))
+            .await
+            .unwrap();
+        let version = GetVersionResponse::decode(response.buf.as_slice()).unwrap();
+        assert_eq!(response.seq, 1);
+        assert_eq!(version.app_name, "mock");
+
+        let request = common::Request {
+            cmd: "CoreService.SwitchAccount".to_string(),
+            seq: 2,
+            buf: vec![],
+            no_response: false,
+        };
+        let result = bot
+            .read()
+            .await
+            .send_request_with_timeout(request, Some(Duration::from_millis(200)))
+            .await;
+        assert!(result.unwrap_err().is_timeout());
+        // 超时的请求从队列中移除
+        assert!(bot.read().await.get_request_queue().is_empty());
+
+        // 超时后迟到的响应计入未匹配
+        bot.read().await.handle_response(common::Response {
+            cmd: "CoreService.SwitchAccount".to_string(),
+            seq: 2,
+            code: 0,
+            msg: None,
+            buf: vec![],
+        });
+        let stats = bot.read().await.get_request_stats();
+        let (_, switch) = stats
+            .iter()
+            .find(|(cmd, _)| cmd == "CoreService.SwitchAccount")
+            .unwrap();
+        assert_eq!(switch.timeout, 1);
+        assert_eq!(switch.cancelled, 0);
+        assert_eq!(switch.responded, 0);
+        assert_eq!(switch.unmatched, 1);
+        assert_eq!(switch.avg_latency(), 0);
+        let (_, version) = stats
+            .iter()
+            .find(|(cmd, _)| cmd == "CoreService.GetVersion")
+            .unwrap();
+        assert_eq!(version.responded, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn seq_is_unique_and_errors_are_typed() {
+        let mut mock = MockKritor::new("mock_seq", 40004);
+        mock.fail("CoreService.GetVersion", 5, "mock failure");
+        let bot = mock.connect().await;
+        let bot = bot.read().await;
+
+        let first = bot.next_seq();
+        let second = bot.next_seq();
+        assert!(second > first);
+
+        let error = bot.get_version().await.unwrap_err();
+        assert_eq!(error.code(), Some(5));
+        let stats = bot.get_request_stats();
+        let (_, version) = stats
+            .iter()
+            .find(|(cmd, _)| cmd == "CoreService.GetVersion")
+            .unwrap();
+        assert_eq!(version.failed, 1);
+        assert_eq!(version.pending, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retry_count_is_sent_to_kritor() {
+        let mut mock = MockKritor::new("mock_retry", 40011);
+        mock.fail("MessageService.SendMessage", 3, "mock failure");
+        let bot = mock.connect().await;
+        let contact = Contact {
+            scene: Scene::Group.into(),
+            peer: "30002".to_string(),
+            sub_peer: None,
+        };
+        let error = bot
+            .read()
+            .await
+            .send_msg(vec![text!("mock-retry")], contact)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some(3));
+        // 重试交给Kritor端，默认重试两次，avocado只发送一次
+        let sent = mock.expect_message().await;
+        assert_eq!(sent_text(&sent), "mock-retry");
+        assert_eq!(sent.retry_count, Some(2));
+        let stats = bot.read().await.get_request_stats();
+        let (_, send) = stats
+            .iter()
+            .find(|(cmd, _)| cmd == "MessageService.SendMessage")
+            .unwrap();
+        assert_eq!(send.total, 1);
+        assert_eq!(bot.read().await.get_sent(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn message_is_dispatched_to_service() {
+        let mut registry = MockRegistry::default();
+        registry
+            .service(
+                "mock_ping",
+                RegisteredService::new(Arc::new(MockPing), ServiceOptions::default()),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_dispatch", 40002);
+        mock.connect().await;
+
+        mock.push(group_message("mock-ping", 50001)).await;
+        let sent = mock.expect_message().await;
+        assert_eq!(sent.contact.unwrap().peer, "30001");
+        assert_eq!(sent_text(&sent), "mock-pong");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn guild_message_is_replied_in_channel() {
+        let mut registry = MockRegistry::default();
+        registry
+            .service(
+                "mock_guild",
+                RegisteredService::new(
+                    Arc::new(MockReply {
+                        text: "mock-guild",
+                        reply: "mock-channel",
+                        result: ProcessResult::Stop,
+                    }),
+                    ServiceOptions::default(),
+                ),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_guild", 40010);
+        mock.connect().await;
+
+        mock.push(Event::Message(PushMessageBody {
+            time: 1700000000,
+            message_id: "mock-guild".to_string(),
+            contact: Some(Contact {
+                scene: Scene::Guild.into(),
+                peer: "60001".to_string(),
+                sub_peer: Some("60002".to_string()),
+            }),
+            sender: Some(Sender {
+                uid: "guild_tester".to_string(),
+                uin: None,
+                nick: Some("tester".to_string()),
+            }),
+            elements: vec![text!("mock-guild")],
+            ..Default::default()
+        }))
+        .await;
+        let sent = mock.expect_message().await;
+        let contact = sent.contact.clone().unwrap();
+        assert_eq!(contact.scene, i32::from(Scene::Guild));
+        assert_eq!(contact.peer, "60001");
+        assert_eq!(contact.sub_peer.as_deref(), Some("60002"));
+        assert_eq!(sent_text(&sent), "mock-channel");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn group_admin_passes_permission_check() {
+        let mut registry = MockRegistry::default();
+        registry
+            .service(
+                "mock_admin",
+                RegisteredService::new(
+                    Arc::new(MockReply {
+                        text: "mock-admin",
+                        reply: "mock-admin-ok",
+                        result: ProcessResult::Stop,
+                    }),
+                    ServiceOptions {
+                        permission: Role::GroupAdmin,
+                        ..Default::default()
+                    },
+                ),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_role", 40012);
+        // 群主与管理员来自群信息
+        mock.respond(
+            "GroupService.GetGroupList",
+            GetGroupListResponse {
+                groups_info: vec![GroupInfo {
+                    group_id: 30001,
+                    owner: 50007,
+                    admins: vec![50008],
+                    ..Default::default()
+                }],
+            },
+        );
+        mock.connect().await;
+
+        mock.push(group_message("mock-admin", 50008)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-admin-ok");
+        mock.push(group_message("mock-admin", 50009)).await;
+        assert!(sent_text(&mock.expect_message().await).starts_with("权限不足"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn group_roles_are_loaded_on_demand() {
+        let mut registry = MockRegistry::default();
+        registry
+            .service(
+                "mock_lazy_admin",
+                RegisteredService::new(
+                    Arc::new(MockReply {
+                        text: "mock-lazy-admin",
+                        reply: "mock-lazy-admin-ok",
+                        result: ProcessResult::Stop,
+                    }),
+                    ServiceOptions {
+                        permission: Role::GroupAdmin,
+                        ..Default::default()
+                    },
+                ),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_lazy_role", 40014);
+        // 与适配器一样，群列表中没有群主与管理员，只在GetGroupInfo中返回
+        mock.respond(
+            "GroupService.GetGroupList",
+            GetGroupListResponse {
+                groups_info: vec![GroupInfo {
+                    group_id: 30001,
+                    ..Default::default()
+                }],
+            },
+        );
+        mock.respond(
+            "GroupService.GetGroupInfo",
+            GetGroupInfoResponse {
+                group_info: Some(GroupInfo {
+                    group_id: 30001,
+                    owner: 50011,
+                    admins: vec![50012],
+                    ..Default::default()
+                }),
+            },
+        );
+        mock.connect().await;
+
+        mock.push(group_message("mock-lazy-admin", 50012)).await;
+        mock.expect_request("GroupService.GetGroupInfo").await;
+        assert_eq!(
+            sent_text(&mock.expect_message().await),
+            "mock-lazy-admin-ok"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transaction_receives_follow_up() {
+        let mut registry = MockRegistry::default();
+        registry
+            .service(
+                "mock_ask",
+                RegisteredService::new(Arc::new(MockAsk), ServiceOptions::default()),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_transaction", 40003);
+        mock.connect().await;
+
+        mock.push(group_message("mock-ask", 50002)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-what");
+
+        mock.push(group_message("blue", 50002)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-got blue");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn services_run_in_priority_order() {
+        let services = [
+            ("mock_order_last", "mock-order", "mock-last", 10, false),
+            ("mock_order_first", "mock-order", "mock-first", -10, false),
+            ("mock_order_fallback", "mock-fallback", "mock-fallback", 0, true),
+        ];
+        let mut registry = MockRegistry::default();
+        for (name, text, reply, priority, fallback) in services {
+            let service = MockReply {
+                text,
+                reply,
+                result: ProcessResult::Stop,
+            };
+            registry
+                .service(
+                    name,
+                    RegisteredService::new(
+                        Arc::new(service),
+                        ServiceOptions {
+                            priority,
+                            fallback,
+                            ..Default::default()
+                        },
+                    ),
+                )
+                .await;
+        }
+        let mut mock = MockKritor::new("mock_order", 40005);
+        mock.connect().await;
+
+        // 优先级高的服务返回Stop，后面的服务不再执行
+        mock.push(group_message("mock-order", 50003)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-first");
+        // 没有其他服务匹配时才执行fallback
+        mock.push(group_message("mock-fallback", 50003)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-fallback");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ignored_service_does_not_block_fallback() {
+        let mut registry = MockRegistry::default();
+        registry
+            .service(
+                "mock_ignore",
+                RegisteredService::new(Arc::new(MockIgnore), ServiceOptions::default()),
+            )
+            .await;
+        let fallback = MockReply {
+            text: "mock-unhandled",
+            reply: "mock-fallback-ran",
+            result: ProcessResult::Stop,
+        };
+        registry
+            .service(
+                "mock_ignore_fallback",
+                RegisteredService::new(
+                    Arc::new(fallback),
+                    ServiceOptions {
+                        fallback: true,
+                        ..Default::default()
+                    },
+                ),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_ignore", 40013);
+        mock.connect().await;
+
+        // 总是匹配的服务返回Ignored时不算作匹配，fallback仍会执行
+        mock.push(group_message("mock-unhandled", 50010)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-fallback-ran");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn middleware_rewrites_context() {
+        let middleware = Arc::new(MockMiddleware::default());
+        let mut registry = MockRegistry::default();
+        registry.middleware(middleware.clone()).await;
+        let service = MockReply {
+            text: "mock-rewritten",
+            reply: "mock-rewritten",
+            result: ProcessResult::Stop,
+        };
+        registry
+            .service(
+                "mock_rewrite",
+                RegisteredService::new(Arc::new(service), ServiceOptions::default()),
+            )
+            .await;
+        let mut mock = MockKritor::new("mock_middleware", 40006);
+        mock.connect().await;
+
+        mock.push(group_message("mock-alias", 50004)).await;
+        assert_eq!(sent_text(&mock.expect_message().await), "mock-rewritten");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(middleware
+            .processed
+            .lock()
+            .unwrap()
+            .contains(&"mock_rewrite".to_string()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn active_listener_receives_bots_connected_later() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_with_listener(listener, ServerConfig::default()));
+        let channel = Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        // 不指定uid，订阅时本测试的Bot还未连接
+        let mut stream = EventServiceClient::new(channel)
+            .register_active_listener(RequestPushEvent {
+                r#type: EventType::Message.into(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let mut mock = MockKritor::new("mock_active_later", 40007);
+        mock.connect().await;
+        mock.push(group_message("mock-active", 50005)).await;
+        // 其他测试的Bot的消息也会转发，只检查本Bot的消息
+        let received = tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(Ok(event)) = stream.next().await {
+                if let Some(Event::Message(message)) = event.event {
+                    if message.message_id == "mock-mock-active" {
+                        return true;
+                    }
+                }
+            }
+            false
+        })
+        .await
+        .unwrap_or(false);
+        assert!(received);
+    }
+
+    #[tokio::test]
+    async fn push_event_releases_bot_lock_while_waiting() {
+        let bot = Bot::new(
+            60002,
+            "mock_push_lock".to_string(),
+            None,
+            None,
+            &EventConfig {
+                message_capacity: Some(1),
+                backpressure: Some(true),
+                ..Default::default()
+            },
+        );
+        let mut receiver = bot.subscribe_message();
+        let bot = Arc::new(RwLock::new(bot));
+        Bot::push_event(&bot, group_message("mock-first", 50006)).await;
+        // 队列已满，第二个事件等待消费者
+        let pushing = {
+            let bot = bot.clone();
+            tokio::spawn(async move {
+                Bot::push_event(&bot, group_message("mock-second", 50006)).await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!pushing.is_finished());
+        let write = tokio::time::timeout(Duration::from_millis(200), bot.write()).await;
+        assert!(write.is_ok());
+        drop(write);
+        assert_eq!(receiver.recv().await.unwrap().message_id, "mock-mock-first");
+        assert_eq!(receiver.recv().await.unwrap().message_id, "mock-mock-second");
+        pushing.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_disconnect_keeps_new_connection() {
+        let (old_tx, _old_rx) = mpsc::channel(8);
+        let (new_tx, mut new_rx) = mpsc::channel(8);
+        let bot = Bot::new(
+            60001,
+            "mock_stale".to_string(),
+            Some(old_tx),
+            None,
+            &EventConfig::default(),
+        );
+        let old = bot.current_connection();
+        let new = bot.reconnect(new_tx).await;
+        let bot = Arc::new(RwLock::new(bot));
+
+        // 旧连接在重连之后才结束，不影响新连接
+        Bot::disconnect(bot.clone(), old).await;
+        assert_eq!(bot.read().await.get_state().await, BotState::Connecting);
+        let request_bot = bot.clone();
+        let request = tokio::spawn(async move {
+            let request = common::Request {
+                cmd: "CoreService.GetVersion".to_string(),
+                seq: 0,
+                buf: vec![],
+                no_response: false,
+            };
+            request_bot
+                .read()
+                .await
+                .send_request_with_timeout(request, Some(Duration::from_secs(5)))
+                .await
+        });
+        let sent = new_rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.cmd, "CoreService.GetVersion");
+
+        // 当前连接断开时，等待中的请求立即失败
+        Bot::disconnect(bot.clone(), new).await;
+        assert!(request.await.unwrap().is_err());
+        assert_eq!(bot.read().await.get_state().await, BotState::Offline);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn generated_cmd_matches_grpc_path() {
+        let mut mock = MockKritor::new("mock_generated", 40008);
+        let bot = mock.connect().await;
+        let bot = bot.read().await;
+
+        // 生成的方法使用proto中的 Service.Method 作为cmd，主动模式按cmd_to_path调用
+        bot.core_get_version(GetVersionRequest {}).await.unwrap();
+        let request = mock.expect_request("CoreService.GetVersion").await;
+        assert_eq!(
+            cmd_to_path(&request.cmd).unwrap().path(),
+            "/kritor.core.CoreService/GetVersion"
+        );
+        bot.message_send_message(SendMessageRequest::default())
+            .await
+            .unwrap();
+        let request = mock.expect_request("MessageService.SendMessage").await;
+        assert_eq!(
+            cmd_to_path(&request.cmd).unwrap().path(),
+            "/kritor.message.MessageService/SendMessage"
+        );
+        bot.group_get_group_list(GetGroupListRequest::default())
+            .await
+            .unwrap();
+        let request = mock.expect_request("GroupService.GetGroupList").await;
+        assert_eq!(
+            cmd_to_path(&request.cmd).unwrap().path(),
+            "/kritor.group.GroupService/GetGroupList"
+        );
+    }
+}
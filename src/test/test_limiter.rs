@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::bot::limiter::{SendScheduler, TokenBucket};
+    use crate::kritor::server::kritor_proto::common::{Contact, Scene};
+    use crate::model::config::SendConfig;
+
+    fn contact(peer: &str) -> Contact {
+        Contact {
+            scene: Scene::Group.into(),
+            peer: peer.to_string(),
+            sub_peer: None,
+        }
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_waits() {
+        let mut bucket = TokenBucket::new(10.0, 2);
+        for _ in 0..2 {
+            assert!(bucket.wait_time().is_zero());
+            bucket.take();
+        }
+        assert!(!bucket.wait_time().is_zero());
+        let mut unlimited = TokenBucket::new(0.0, 1);
+        assert!(unlimited.wait_time().is_zero());
+    }
+
+    #[tokio::test]
+    async fn contact_rate_is_smoothed() {
+        let config = SendConfig {
+            bot_rate: Some(0.0),
+            contact_rate: Some(20.0),
+            contact_burst: Some(1),
+            ..Default::default()
+        };
+        let scheduler = SendScheduler::default();
+        let start = Instant::now();
+        for _ in 0..3 {
+            scheduler.acquire(&contact("1"), false, &config).await;
+        }
+        // 第一条立即发送，之后每条间隔50ms
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        // 其他会话不受影响
+        let start = Instant::now();
+        scheduler.acquire(&contact("2"), false, &config).await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn idle_contacts_are_swept() {
+        let config = SendConfig {
+            bot_rate: Some(0.0),
+            contact_rate: Some(1000.0),
+            contact_burst: Some(1),
+            ..Default::default()
+        };
+        let scheduler = SendScheduler::default();
+        scheduler.acquire(&contact("1"), false, &config).await;
+        let slow = SendConfig {
+            contact_rate: Some(0.001),
+            ..config.clone()
+        };
+        scheduler.acquire(&contact("2"), false, &slow).await;
+        assert_eq!(scheduler.contact_count(), 2);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // 令牌未补满的会话不能清理，否则会绕过限速
+        scheduler.sweep(Duration::ZERO);
+        assert_eq!(scheduler.contact_count(), 1);
+        scheduler.sweep(Duration::from_secs(60));
+        assert_eq!(scheduler.contact_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn priority_does_not_overdraw_contact() {
+        let config = SendConfig {
+            bot_rate: Some(0.0),
+            contact_rate: Some(20.0),
+            contact_burst: Some(1),
+            ..Default::default()
+        };
+        let scheduler = SendScheduler::default();
+        for _ in 0..5 {
+            scheduler.acquire(&contact("1"), true, &config).await;
+        }
+        // 优先消息用完令牌后不再透支，普通消息最多等待一个令牌的时间
+        let start = Instant::now();
+        scheduler.acquire(&contact("1"), false, &config).await;
+        assert!(start.elapsed() < Duration::from_millis(150));
+    }
+}
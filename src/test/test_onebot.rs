@@ -0,0 +1,301 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::{SinkExt, StreamExt};
+    use prost::Message;
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+    use tokio::sync::{mpsc, oneshot};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+    use tokio_tungstenite::{accept_async, connect_async};
+
+    use crate::adapter::onebot::event::to_event;
+    use crate::adapter::onebot::message::{elements_to_segments, parse_cq_code, segments_to_elements};
+    use crate::adapter::onebot::action::handle;
+    use crate::adapter::onebot::{run_session, OneBotClient};
+    use crate::bot::bot::BotState;
+    use crate::kritor::server::kritor_proto::common::element::Data;
+    use crate::kritor::server::kritor_proto::common::{self, Contact, Scene};
+    use crate::kritor::server::kritor_proto::event_structure::Event;
+    use crate::kritor::server::kritor_proto::request_event::Request;
+    use crate::kritor::server::kritor_proto::{
+        GetGroupInfoRequest, GetGroupInfoResponse, GetGroupListResponse,
+        SetGroupApplyResultRequest, SetInvitedJoinGroupResultRequest,
+    };
+    use crate::kritor::server::BOTS;
+    use crate::text;
+
+    #[test]
+    fn parse_cq_code_message() {
+        let segments = parse_cq_code("hi[CQ:at,qq=10002] [CQ:face,id=14]&#91;x&#93;");
+        assert_eq!(
+            segments,
+            vec![
+                json!({"type": "text", "data": {"text": "hi"}}),
+                json!({"type": "at", "data": {"qq": "10002"}}),
+                json!({"type": "text", "data": {"text": " "}}),
+                json!({"type": "face", "data": {"id": "14"}}),
+                json!({"type": "text", "data": {"text": "[x]"}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_round_trip() {
+        let message = json!([
+            {"type": "text", "data": {"text": "hello"}},
+            {"type": "at", "data": {"qq": "10002"}},
+            {"type": "reply", "data": {"id": "123"}},
+            {"type": "image", "data": {"file": "https://example.com/a.png"}},
+            {"type": "unknown", "data": {}},
+        ]);
+        let elements = segments_to_elements(&message);
+        assert_eq!(elements.len(), 4);
+        match elements[1].data.as_ref().unwrap() {
+            Data::At(at) => assert_eq!(at.uin, Some(10002)),
+            _ => panic!("expect at element"),
+        }
+        assert_eq!(
+            elements_to_segments(&elements),
+            json!([
+                {"type": "text", "data": {"text": "hello"}},
+                {"type": "at", "data": {"qq": "10002"}},
+                {"type": "reply", "data": {"id": "123"}},
+                {"type": "image", "data": {"file": "https://example.com/a.png"}},
+            ])
+        );
+    }
+
+    #[test]
+    fn convert_request_event() {
+        let event = to_event(&json!({
+            "post_type": "request",
+            "request_type": "group",
+            "sub_type": "invite",
+            "group_id": 20001,
+            "user_id": 10002,
+            "flag": "flag_1",
+            "time": 1700000000,
+        }));
+        let Some(Event::Request(request)) = event else {
+            panic!("expect request event");
+        };
+        assert_eq!(request.request_id, "flag_1");
+        match request.request.unwrap() {
+            Request::InvitedGroup(invite) => {
+                assert_eq!(invite.group_id, 20001);
+                assert_eq!(invite.inviter_uin, 10002);
+            }
+            _ => panic!("expect invited group request"),
+        }
+        // 元事件不转换
+        assert!(to_event(&json!({"post_type": "meta_event", "meta_event_type": "heartbeat"})).is_none());
+    }
+
+    #[tokio::test]
+    async fn process_request_uses_flag() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let client = Arc::new(OneBotClient::new(tx));
+        let (sent_tx, mut sent_rx) = mpsc::unbounded_channel::<Value>();
+        let responder = client.clone();
+        tokio::spawn(async move {
+            while let Some(WsMessage::Text(text)) = rx.recv().await {
+                let frame: Value = serde_json::from_str(&text).unwrap();
+                sent_tx.send(frame.clone()).unwrap();
+                responder.handle_frame(
+                    json!({"status": "ok", "retcode": 0, "data": null, "echo": frame["echo"]}),
+                );
+            }
+        });
+
+        let request = common::Request {
+            cmd: "ProcessService.SetGroupApplyResult".to_string(),
+            buf: SetGroupApplyResultRequest {
+                request_id: "flag-1".to_string(),
+                is_approve: false,
+                deny_reason: Some("no".to_string()),
+            }
+            .encode_to_vec(),
+            ..Default::default()
+        };
+        handle(&client, &request).await.unwrap();
+        let frame = sent_rx.recv().await.unwrap();
+        assert_eq!(frame["action"], "set_group_add_request");
+        assert_eq!(
+            frame["params"],
+            json!({"flag": "flag-1", "sub_type": "add", "approve": false, "reason": "no"})
+        );
+
+        let request = common::Request {
+            cmd: "ProcessService.SetInvitedJoinGroupResult".to_string(),
+            buf: SetInvitedJoinGroupResultRequest {
+                request_id: "flag-2".to_string(),
+                is_approve: true,
+            }
+            .encode_to_vec(),
+            ..Default::default()
+        };
+        handle(&client, &request).await.unwrap();
+        let frame = sent_rx.recv().await.unwrap();
+        assert_eq!(frame["params"]["flag"], "flag-2");
+        assert_eq!(frame["params"]["sub_type"], "invite");
+        assert_eq!(frame["params"]["approve"], true);
+    }
+
+    #[tokio::test]
+    async fn group_info_has_owner_and_admins() {
+        let (tx, mut rx) = mpsc::channel(8);
+        let client = Arc::new(OneBotClient::new(tx));
+        let responder = client.clone();
+        tokio::spawn(async move {
+            while let Some(WsMessage::Text(text)) = rx.recv().await {
+                let frame: Value = serde_json::from_str(&text).unwrap();
+                let data = match frame["action"].as_str().unwrap() {
+                    "get_group_list" => json!([{"group_id": 20001, "group_name": "test"}]),
+                    "get_group_info" => json!({"group_id": 20001, "group_name": "test"}),
+                    "get_group_member_list" => json!([
+                        {"user_id": 10001, "role": "owner"},
+                        {"user_id": 10002, "role": "admin"},
+                        {"user_id": 10003, "role": "member"},
+                    ]),
+                    _ => json!(null),
+                };
+                responder.handle_frame(
+                    json!({"status": "ok", "retcode": 0, "data": data, "echo": frame["echo"]}),
+                );
+            }
+        });
+
+        // 群列表不请求成员列表，群主与管理员为空
+        let request = common::Request {
+            cmd: "GroupService.GetGroupList".to_string(),
+            ..Default::default()
+        };
+        let buf = handle(&client, &request).await.unwrap();
+        let response = GetGroupListResponse::decode(buf.as_slice()).unwrap();
+        assert_eq!(response.groups_info[0].group_id, 20001);
+        assert_eq!(response.groups_info[0].owner, 0);
+
+        let request = common::Request {
+            cmd: "GroupService.GetGroupInfo".to_string(),
+            buf: GetGroupInfoRequest { group_id: 20001 }.encode_to_vec(),
+            ..Default::default()
+        };
+        let buf = handle(&client, &request).await.unwrap();
+        let group = GetGroupInfoResponse::decode(buf.as_slice())
+            .unwrap()
+            .group_info
+            .unwrap();
+        assert_eq!(group.group_id, 20001);
+        assert_eq!(group.owner, 10001);
+        assert_eq!(group.admins, vec![10002]);
+    }
+
+    /// 模拟OneBot实现，记录收到的send_group_msg参数
+    async fn stand_in(
+        listener: TcpListener,
+        push: oneshot::Receiver<()>,
+        sent: mpsc::UnboundedSender<Value>,
+    ) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let ws = accept_async(stream).await.unwrap();
+        let (mut sink, mut stream) = ws.split();
+        let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+        tokio::spawn(async move {
+            while let Some(frame) = rx.recv().await {
+                if sink.send(WsMessage::Text(frame.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let event_tx = tx.clone();
+        tokio::spawn(async move {
+            push.await.unwrap();
+            event_tx
+                .send(json!({
+                    "post_type": "message",
+                    "message_type": "group",
+                    "time": 1700000000,
+                    "self_id": 10001,
+                    "message_id": 7,
+                    "group_id": 20001,
+                    "user_id": 10002,
+                    "message": "hello[CQ:at,qq=10001]",
+                    "sender": {"user_id": 10002, "nickname": "tester", "card": ""},
+                }))
+                .unwrap();
+        });
+        while let Some(Ok(WsMessage::Text(text))) = stream.next().await {
+            let frame: Value = serde_json::from_str(&text).unwrap();
+            let data = match frame["action"].as_str().unwrap() {
+                "get_login_info" => json!({"user_id": 10001, "nickname": "avocado"}),
+                "get_version_info" => json!({"app_name": "stand-in", "app_version": "1.0"}),
+                "get_friend_list" | "get_group_list" => json!([]),
+                "send_group_msg" => {
+                    sent.send(frame["params"].clone()).unwrap();
+                    json!({"message_id": 42})
+                }
+                _ => json!({}),
+            };
+            tx.send(json!({"status": "ok", "retcode": 0, "data": data, "echo": frame["echo"]}))
+                .unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn forward_session_with_stand_in() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (push_tx, push_rx) = oneshot::channel();
+        let (sent_tx, mut sent_rx) = mpsc::unbounded_channel();
+        tokio::spawn(stand_in(listener, push_rx, sent_tx));
+
+        let (ws, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+        tokio::spawn(run_session(ws));
+
+        let bot = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(bot) = BOTS.read().await.get("10001").map(|bot| bot.clone()) {
+                    if bot.read().await.get_state().await == BotState::Online {
+                        return bot;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("bot should come online");
+
+        let mut receiver = bot.read().await.subscribe_message();
+        push_tx.send(()).unwrap();
+        let message = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.message_id, "7");
+        assert_eq!(message.contact.as_ref().unwrap().peer, "20001");
+        assert_eq!(message.sender.as_ref().unwrap().uin, Some(10002));
+        assert_eq!(message.elements.len(), 2);
+
+        let contact = Contact {
+            scene: Scene::Group.into(),
+            peer: "20001".to_string(),
+            sub_peer: None,
+        };
+        let response = bot
+            .read()
+            .await
+            .send_msg(vec![text!("hi")], contact)
+            .await
+            .unwrap();
+        assert_eq!(response.message_id, "42");
+        let params = sent_rx.recv().await.unwrap();
+        assert_eq!(params["group_id"], json!(20001));
+        assert_eq!(
+            params["message"],
+            json!([{"type": "text", "data": {"text": "hi"}}])
+        );
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::model::db::connect;
+    use crate::model::permission::{PermissionStore, Role};
+    use crate::model::policy::{PolicyStore, Scope};
+
+    #[test]
+    fn highest_grant_applies() {
+        let store = PermissionStore::open(":memory:").unwrap();
+        let group = Scope::Group("123".to_string());
+        let other = Scope::Group("456".to_string());
+        assert_eq!(store.get("10001", Some(&group)), None);
+
+        store.grant("10001", group.clone(), Role::GroupAdmin).unwrap();
+        assert_eq!(store.get("10001", Some(&group)), Some(Role::GroupAdmin));
+        assert_eq!(store.get("10001", Some(&other)), None);
+
+        // 全局授予的角色在所有群生效，取较高的角色
+        store.grant("10001", Scope::Global, Role::Admin).unwrap();
+        assert_eq!(store.get("10001", Some(&group)), Some(Role::Admin));
+        assert_eq!(store.get("10001", None), Some(Role::Admin));
+
+        assert!(store.revoke("10001", Scope::Global).unwrap());
+        assert!(!store.revoke("10001", Scope::Global).unwrap());
+        assert_eq!(store.get("10001", Some(&group)), Some(Role::GroupAdmin));
+        assert!(Role::Owner > Role::Admin && Role::GroupAdmin > Role::Member);
+    }
+
+    #[test]
+    fn stores_share_one_connection() {
+        let conn = Arc::new(Mutex::new(connect(":memory:").unwrap()));
+        let store = PermissionStore::new(conn.clone()).unwrap();
+        store.grant("10001", Scope::Global, Role::Admin).unwrap();
+        PolicyStore::new(conn.clone()).unwrap();
+
+        // 内存数据库只在同一连接内可见，重新读取到角色说明写入的是共用的连接
+        let reloaded = PermissionStore::new(conn).unwrap();
+        assert_eq!(reloaded.get("10001", None), Some(Role::Admin));
+    }
+}
@@ -0,0 +1,26 @@
+#[cfg(test)]
+mod tests {
+    use crate::model::policy::{PolicyMode, PolicyStore, Scope};
+
+    #[test]
+    fn scope_rules_override_mode() {
+        let store = PolicyStore::open(":memory:").unwrap();
+        let group = Scope::Group("123".to_string());
+        let private = Scope::Private("456".to_string());
+        assert!(store.is_enabled("repeat", Some(&group)));
+
+        store.set_rule("repeat", group.clone(), false).unwrap();
+        assert!(!store.is_enabled("repeat", Some(&group)));
+        assert!(store.is_enabled("repeat", Some(&private)));
+
+        store.set_mode("repeat", PolicyMode::Allowlist).unwrap();
+        assert!(!store.is_enabled("repeat", Some(&private)));
+        store.set_rule("repeat", private.clone(), true).unwrap();
+        assert!(store.is_enabled("repeat", Some(&private)));
+
+        // 全局关闭优先于单独开启
+        store.set_rule("repeat", Scope::Global, false).unwrap();
+        assert!(!store.is_enabled("repeat", Some(&private)));
+        assert!(store.is_enabled("status", Some(&private)));
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use crate::adapter::replay::ReplayHandler;
+    use crate::adapter::RequestHandler;
+    use crate::kritor::record::{read_records, record, Record, Recorder};
+    use crate::kritor::server::kritor_proto::common;
+
+    fn response(uid: &str, cmd: &str, buf: &[u8]) -> Record {
+        Record {
+            time: 1700000000000,
+            uin: 10000,
+            uid: uid.to_string(),
+            data: Some(record::Data::Response(common::Response {
+                cmd: cmd.to_string(),
+                seq: 1,
+                code: 0,
+                msg: None,
+                buf: buf.to_vec(),
+            })),
+        }
+    }
+
+    fn request(cmd: &str) -> common::Request {
+        common::Request {
+            cmd: cmd.to_string(),
+            seq: 1,
+            buf: vec![],
+            no_response: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_recorded_responses() {
+        let records = vec![
+            response("record_a", "CoreService.GetVersion", b"first"),
+            response("record_a", "CoreService.GetVersion", b"second"),
+            response("record_b", "CoreService.GetVersion", b"other"),
+        ];
+        let path = std::env::temp_dir().join("avocado_test_record.bin");
+        let buf: Vec<u8> = records
+            .iter()
+            .flat_map(|record| record.encode_length_delimited_to_vec())
+            .collect();
+        std::fs::write(&path, buf).unwrap();
+        let read = read_records(&path).unwrap();
+        assert_eq!(read, records);
+
+        let handler = ReplayHandler::new(&read, "record_a");
+        let version = request("CoreService.GetVersion");
+        assert_eq!(handler.handle(&version).await.unwrap(), b"first");
+        assert_eq!(handler.handle(&version).await.unwrap(), b"second");
+        // 最后一个响应会被重复使用
+        assert_eq!(handler.handle(&version).await.unwrap(), b"second");
+        let unknown = request("CoreService.SwitchAccount");
+        assert!(handler.handle(&unknown).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_keep_call_order() {
+        let path = std::env::temp_dir().join("avocado_test_record_order.bin");
+        let _ = std::fs::remove_file(&path);
+        let recorder = Recorder::new(&path).unwrap();
+        recorder.request(10000, "record_order", &request("CoreService.GetVersion"));
+        let response = match response("record_order", "CoreService.GetVersion", b"v").data {
+            Some(record::Data::Response(response)) => response,
+            _ => unreachable!(),
+        };
+        recorder.response(10000, "record_order", &response);
+
+        // 写入在单独的线程中进行，等待记录落盘
+        let mut records = vec![];
+        for _ in 0..50 {
+            records = read_records(&path)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|record| record.uid == "record_order")
+                .collect();
+            if records.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(matches!(records[0].data, Some(record::Data::Request(_))));
+        assert!(matches!(records[1].data, Some(record::Data::Response(_))));
+    }
+}
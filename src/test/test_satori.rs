@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::adapter::satori::action::group_channel;
+    use crate::adapter::satori::event::to_event;
+    use crate::adapter::satori::message::{content_to_elements, elements_to_content, parse, Node};
+    use crate::kritor::server::kritor_proto::common::element::Data;
+    use crate::kritor::server::kritor_proto::common::Scene;
+    use crate::kritor::server::kritor_proto::event_structure::Event;
+
+    #[test]
+    fn parse_element_tree() {
+        let nodes = parse("a &lt;b&gt; <at id=\"10002\" name='tester'/><b>bold</b>");
+        assert_eq!(nodes[0], Node::Text("a <b> ".to_string()));
+        match &nodes[1] {
+            Node::Element { name, attrs, children } => {
+                assert_eq!(name, "at");
+                assert_eq!(attrs["id"], "10002");
+                assert_eq!(attrs["name"], "tester");
+                assert!(children.is_empty());
+            }
+            _ => panic!("expect at element"),
+        }
+        match &nodes[2] {
+            Node::Element { name, children, .. } => {
+                assert_eq!(name, "b");
+                assert_eq!(children, &vec![Node::Text("bold".to_string())]);
+            }
+            _ => panic!("expect b element"),
+        }
+    }
+
+    #[test]
+    fn content_round_trip() {
+        let elements = content_to_elements(
+            "<quote id=\"m1\"/>hello <b>world</b><br/><at type=\"all\"/><img src=\"https://example.com/a.png\"/>",
+        );
+        assert_eq!(elements.len(), 4);
+        match elements[1].data.as_ref().unwrap() {
+            Data::Text(text) => assert_eq!(text.text, "hello world\n"),
+            _ => panic!("expect text element"),
+        }
+        assert_eq!(
+            elements_to_content(&elements),
+            "<quote id=\"m1\"/>hello world\n<at type=\"all\"/><img src=\"https://example.com/a.png\"/>"
+        );
+    }
+
+    #[test]
+    fn convert_message_event() {
+        let body = json!({
+            "id": 1,
+            "type": "message-created",
+            "platform": "discord",
+            "self_id": "10001",
+            "timestamp": 1700000000000u64,
+            "channel": {"id": "30001", "type": 0},
+            "guild": {"id": "20001"},
+            "user": {"id": "10002", "name": "tester"},
+            "member": {"nick": "card"},
+            "message": {"id": "m2", "content": "hi <at id=\"10001\"/>"},
+        });
+        let Some(Event::Message(message)) = to_event(&body) else {
+            panic!("expect message event");
+        };
+        let contact = message.contact.unwrap();
+        assert_eq!(contact.scene, i32::from(Scene::Group));
+        assert_eq!(contact.peer, "20001");
+        assert_eq!(contact.sub_peer, Some("30001".to_string()));
+        assert_eq!(group_channel(contact), "30001");
+        assert_eq!(message.message_id, "m2");
+        assert_eq!(message.time, 1700000000);
+        assert_eq!(message.sender.unwrap().nick, Some("card".to_string()));
+        assert_eq!(message.elements.len(), 2);
+
+        // 自己发出的消息不转换
+        let mut own = body.clone();
+        own["user"]["id"] = json!("10001");
+        assert!(to_event(&own).is_none());
+    }
+
+    #[test]
+    fn guild_equal_to_channel_has_no_sub_peer() {
+        let body = json!({
+            "type": "message-created",
+            "self_id": "10001",
+            "channel": {"id": "20001", "type": 0},
+            "guild": {"id": "20001"},
+            "user": {"id": "10002"},
+            "message": {"id": "m3", "content": "hi"},
+        });
+        let Some(Event::Message(message)) = to_event(&body) else {
+            panic!("expect message event");
+        };
+        let contact = message.contact.unwrap();
+        assert_eq!(contact.peer, "20001");
+        assert_eq!(contact.sub_peer, None);
+        assert_eq!(group_channel(contact), "20001");
+    }
+
+    #[test]
+    fn non_numeric_ids_are_not_zero() {
+        // 消息中保留字符串id，uin为空
+        let body = json!({
+            "type": "message-created",
+            "self_id": "@avocado:matrix.org",
+            "channel": {"id": "!room:matrix.org", "type": 0},
+            "guild": {"id": "!room:matrix.org"},
+            "user": {"id": "@tester:matrix.org"},
+            "message": {"id": "m4", "content": "hi"},
+        });
+        let Some(Event::Message(message)) = to_event(&body) else {
+            panic!("expect message event");
+        };
+        assert_eq!(message.contact.unwrap().peer, "!room:matrix.org");
+        let sender = message.sender.unwrap();
+        assert_eq!(sender.uid, "@tester:matrix.org");
+        assert_eq!(sender.uin, None);
+
+        // 群号只能为数字的通知被跳过，而不是共用群号0
+        let body = json!({
+            "type": "guild-member-added",
+            "self_id": "@avocado:matrix.org",
+            "timestamp": 1700000000000u64,
+            "guild": {"id": "!room:matrix.org"},
+            "user": {"id": "@tester:matrix.org"},
+        });
+        assert!(to_event(&body).is_none());
+    }
+}